@@ -0,0 +1,90 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::Error;
+use crate::Properties;
+
+use crate::ape;
+use crate::id3v2;
+use crate::mpeg;
+use crate::tools::tag_error;
+
+const SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+const FRAME_SAMPLES: u64 = 1024;
+
+struct Frame {
+    length: u64,
+    samples: u64,
+    sample_rate: u32,
+    // 0 means the channels are described in the stream itself
+    channels: u8,
+}
+
+// an ADTS frame header
+fn parse_frame(arr: &[u8]) -> Option<Frame> {
+    // sync word, then the layer which is always 0
+    if arr.len() < 7 || arr[0] != 0xFF || arr[1] & 0xF6 != 0xF0 {
+        return None;
+    }
+    let sample_rate = *SAMPLE_RATES.get(((arr[2] >> 2) & 0xF) as usize)?;
+    let channels = match ((arr[2] & 1) << 2) | (arr[3] >> 6) {
+        7 => 8,
+        x => x,
+    };
+    let length = ((arr[3] & 3) as u64) << 11 | (arr[4] as u64) << 3 | (arr[5] >> 5) as u64;
+    if length < 7 {
+        return None;
+    }
+
+    Some(Frame {
+        length,
+        samples: ((arr[6] & 3) as u64 + 1) * FRAME_SAMPLES,
+        sample_rate,
+        channels,
+    })
+}
+
+// audio properties from walking the ADTS frames
+pub fn get_properties<T: Read + Seek>(input: &mut T) -> Result<Properties, Error> {
+    let end = ape::audio_end(input)?;
+    let start = id3v2::skip(input)?;
+    let mut pos = mpeg::find_frame(input, start, end, |x| parse_frame(x).map(|f| f.length))?;
+
+    let mut first = None;
+    let mut samples = 0;
+    let mut bytes = 0;
+    let mut arr: [u8; 7] = [0; 7];
+    while pos + 7 <= end {
+        input.seek(SeekFrom::Start(pos))?;
+        input.read_exact(&mut arr)?;
+        let frame = match parse_frame(&arr) {
+            Some(x) => x,
+            None => break,
+        };
+        samples += frame.samples;
+        bytes += frame.length;
+        pos += frame.length;
+        first = first.or(Some(frame));
+    }
+
+    let first = first.ok_or_else(|| tag_error("No audio frames found"))?;
+    let properties = Properties::from_samples(
+        Some(samples),
+        first.sample_rate,
+        first.channels,
+        None,
+        std::cmp::min(bytes, end - start),
+    );
+    Ok(match first.channels {
+        0 => Properties {
+            channels: None,
+            ..properties
+        },
+        _ => properties,
+    })
+}
+
+#[cfg(test)]
+mod tests;
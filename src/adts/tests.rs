@@ -0,0 +1,43 @@
+use crate::Properties;
+use std::io::Cursor;
+use std::time::Duration;
+
+// AAC LC at 16000 Hz, 1024 samples in a 16 byte frame
+fn frame(channels: u8) -> Vec<u8> {
+    let mut vec = vec![
+        0xFF,
+        0xF1,
+        0x60 | channels >> 2,
+        (channels & 3) << 6,
+        0x02,
+        0x1F,
+        0xFC,
+    ];
+    vec.resize(16, 0);
+    vec
+}
+
+#[test]
+fn adts_properties_test() {
+    let mut file = b"ID3\x04\x00\x00\x00\x00\x00\x00".to_vec();
+    for _ in 0..125 {
+        file.append(&mut frame(2));
+    }
+    let properties = super::get_properties(&mut Cursor::new(&file)).unwrap();
+    let ideal = Properties {
+        duration: Some(Duration::from_secs(8)),
+        sample_rate: Some(16000),
+        channels: Some(2),
+        bits_per_sample: None,
+        bitrate: Some(2),
+    };
+    assert_eq!(properties, ideal);
+
+    // 7.1, and channels given in the stream
+    let properties = super::get_properties(&mut Cursor::new(frame(7))).unwrap();
+    assert_eq!(properties.channels, Some(8));
+    let properties = super::get_properties(&mut Cursor::new(frame(0))).unwrap();
+    assert_eq!(properties.channels, None);
+
+    assert!(super::get_properties(&mut Cursor::new(vec![0; 64])).is_err());
+}
@@ -0,0 +1,159 @@
+use std::io::prelude::*;
+use std::io::Cursor;
+
+use crate::Error;
+use crate::TagOption;
+use crate::Tags;
+
+use crate::id3v2;
+use crate::iff;
+use crate::iff::read_chunk;
+use crate::iff::Chunk;
+use crate::iff::Form;
+use crate::iff::Piece;
+use crate::iff::AIFF;
+use crate::tools::bounded::Bounded;
+use crate::tools::encoding::decode_iso_8859_1;
+use crate::tools::encoding::encode_iso_8859_1;
+use crate::tools::tag_error;
+use crate::tools::tags::delta;
+use crate::tools::tags::fill;
+
+// walk the chunks of a FORM/AIFF or FORM/AIFC file
+fn read_form<T: Read + Seek>(input: &mut T) -> Result<Form, Error> {
+    let mut form = AIFF.read_form(input)?;
+
+    if &form.id != b"FORM" {
+        return Err(tag_error("Not an IFF file"));
+    }
+    if &form.kind != b"AIFF" && &form.kind != b"AIFC" {
+        return Err(tag_error("Not an AIFF file"));
+    }
+
+    AIFF.read_chunks(input, &mut form, |_, _, _| Ok(()))?;
+    Ok(form)
+}
+
+fn read_text<T: Read + Seek>(input: &mut T, chunk: &Chunk) -> Result<TagOption<String>, Error> {
+    let vec = read_chunk(input, chunk)?;
+
+    // some writers zero terminate anyway
+    let end = vec.iter().position(|&b| b == 0).unwrap_or(vec.len());
+    Ok(TagOption::Some(decode_iso_8859_1(&vec[..end])))
+}
+
+pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    let form = read_form(input)?;
+
+    let mut tags = match form.chunks.iter().find(|c| c.is_id3()) {
+        Some(chunk) => match id3v2::get(&mut Bounded::new(input, chunk.body(), chunk.size)) {
+            Ok(t) => t,
+            Err(Error::TagError(_)) => Tags::none(),
+            Err(e) => return Err(e),
+        },
+        None => Tags::none(),
+    };
+
+    for chunk in form.chunks.iter() {
+        match &chunk.id {
+            b"NAME" => fill(&mut tags.title, read_text(input, chunk)?),
+            b"AUTH" => fill(&mut tags.artist, read_text(input, chunk)?),
+            b"ANNO" => fill(&mut tags.comment, read_text(input, chunk)?),
+            b"(c) " => fill(&mut tags.copyright, read_text(input, chunk)?),
+            _ => (),
+        }
+    }
+
+    Ok(tags)
+}
+
+fn new_text(id: &[u8], text: &TagOption<String>) -> Vec<u8> {
+    match text {
+        TagOption::Some(ref t) if t.as_str() != "" => AIFF.new_chunk(id, &encode_iso_8859_1(t)),
+        _ => Vec::new(),
+    }
+}
+
+pub fn set<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    new: &Tags,
+) -> Result<(), Error> {
+    let form = read_form(input)?;
+    let tags = delta(&get(input)?, new);
+
+    // the ID3 chunk holds everything, the text chunks are only kept up to date
+    let mut vec = Vec::new();
+    let id = match form.chunks.iter().find(|c| c.is_id3()) {
+        Some(chunk) => {
+            id3v2::set(
+                &mut Bounded::new(input, chunk.body(), chunk.size),
+                &mut vec,
+                &tags,
+            )?;
+            chunk.id
+        }
+        None => {
+            id3v2::set(&mut Cursor::new(Vec::new()), &mut vec, &tags)?;
+            *b"ID3 "
+        }
+    };
+    let id3 = AIFF.new_chunk(&id, &vec);
+    let mut anno = Some(new_text(b"ANNO", &tags.comment));
+
+    rewrite(input, output, &form, Some(id3), |chunk| match &chunk.id {
+        b"NAME" => Some(new_text(b"NAME", &tags.title)),
+        b"AUTH" => Some(new_text(b"AUTH", &tags.artist)),
+        b"(c) " => Some(new_text(b"(c) ", &tags.copyright)),
+        // the first annotation is the comment, any others stay
+        b"ANNO" => anno.take(),
+        _ => None,
+    })
+}
+
+// copy the file without the text chunks and/or the ID3 chunk
+pub fn remove<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    text: bool,
+    id3: bool,
+) -> Result<(), Error> {
+    let form = read_form(input)?;
+    let id3 = if id3 { Some(Vec::new()) } else { None };
+    rewrite(input, output, &form, id3, |chunk| match &chunk.id {
+        b"NAME" | b"AUTH" | b"ANNO" | b"(c) " if text => Some(Vec::new()),
+        _ => None,
+    })
+}
+
+// copy the file, replacing the ID3 chunk (appended if missing, dropped if empty)
+// and any chunk for which replace returns something
+fn rewrite<R, W, F>(
+    input: &mut R,
+    output: &mut W,
+    form: &Form,
+    mut id3: Option<Vec<u8>>,
+    mut replace: F,
+) -> Result<(), Error>
+where
+    R: Read + Seek,
+    W: Write,
+    F: FnMut(&Chunk) -> Option<Vec<u8>>,
+{
+    let mut pieces = iff::pieces(form, |chunk| {
+        Ok(match chunk.is_id3() {
+            // duplicates of the chunk are dropped once it's written
+            true => id3.as_mut().map(std::mem::take),
+            false => replace(chunk),
+        })
+    })?;
+    if let Some(vec) = id3 {
+        pieces.push(Piece::Data(vec));
+    }
+
+    output.write_all(&AIFF.form_header(form, iff::size(&pieces))?)?;
+    iff::write_pieces(input, output, form, pieces)
+}
+
+#[cfg(test)]
+mod tests;
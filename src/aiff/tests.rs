@@ -0,0 +1,91 @@
+use crate::TagOption;
+use crate::Tags;
+use std::io::Cursor;
+
+use crate::iff::AIFF;
+
+use crate::tools::decode_int_be_u32;
+use crate::tools::encode_int_be_u32;
+
+// COMM, odd-sized SSND and a few text chunks
+fn aiff() -> Vec<u8> {
+    let mut chunks = Vec::new();
+    chunks.append(&mut AIFF.new_chunk(
+        b"COMM",
+        &[
+            0, 1, 0, 0, 0, 5, 0, 8, 0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0,
+        ],
+    ));
+    chunks.append(&mut AIFF.new_chunk(b"SSND", &[0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5]));
+    chunks.append(&mut AIFF.new_chunk(b"NAME", b"Old Title"));
+    chunks.append(&mut AIFF.new_chunk(b"ANNO", b"First"));
+    chunks.append(&mut AIFF.new_chunk(b"ANNO", b"Second"));
+
+    let mut vec = b"FORM".to_vec();
+    vec.extend_from_slice(&encode_int_be_u32(chunks.len() as u32 + 4));
+    vec.extend_from_slice(b"AIFF");
+    vec.append(&mut chunks);
+    vec
+}
+
+fn chunk_ids(vec: &[u8]) -> Vec<[u8; 4]> {
+    let form = super::read_form(&mut Cursor::new(vec)).unwrap();
+    form.chunks.iter().map(|c| c.id).collect()
+}
+
+#[test]
+fn aiff_text_test() {
+    let tags = super::get(&mut Cursor::new(aiff())).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("Old Title".to_string()),
+        comment: TagOption::Some("First".to_string()),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+}
+
+#[test]
+fn aiff_set_test() {
+    let new = Tags {
+        title: TagOption::Some("Title".to_string()),
+        comment: TagOption::None,
+        composer: TagOption::Some("Composer".to_string()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(aiff()), &mut output, &new).unwrap();
+
+    assert_eq!(decode_int_be_u32(&output[4..8]) as usize, output.len() - 8);
+    // the comment is gone from the first ANNO, the ID3 chunk is new
+    assert_eq!(
+        chunk_ids(&output),
+        vec![*b"COMM", *b"SSND", *b"NAME", *b"ANNO", *b"ID3 "]
+    );
+
+    // the second annotation shows up as the comment again
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("Title".to_string()),
+        composer: TagOption::Some("Composer".to_string()),
+        comment: TagOption::Some("Second".to_string()),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+
+    // and the ID3 chunk gets updated in place
+    let new = Tags {
+        artist: TagOption::Some("Artist".to_string()),
+        ..Tags::mixed()
+    };
+    let mut again = Vec::new();
+    super::set(&mut Cursor::new(&output), &mut again, &new).unwrap();
+    assert_eq!(chunk_ids(&again), chunk_ids(&output));
+    let tags = super::get(&mut Cursor::new(&again)).unwrap();
+    assert_eq!(tags.artist, TagOption::Some("Artist".to_string()));
+    assert_eq!(tags.composer, TagOption::Some("Composer".to_string()));
+
+    let mut bare = Vec::new();
+    super::remove(&mut Cursor::new(&again), &mut bare, true, true).unwrap();
+    assert_eq!(chunk_ids(&bare), vec![*b"COMM", *b"SSND"]);
+    assert_eq!(decode_int_be_u32(&bare[4..8]) as usize, bare.len() - 8);
+}
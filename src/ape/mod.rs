@@ -0,0 +1,383 @@
+use std::collections::BTreeMap;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::DateTime;
+use crate::Error;
+use crate::Image;
+use crate::TagOption;
+use crate::Tags;
+
+use crate::id3v1;
+use crate::lyrics3;
+use crate::tools::decode_int_le_u32;
+use crate::tools::encode_int_le_u32;
+use crate::tools::tag_error;
+use crate::tools::tags::delta;
+use crate::tools::tags::pair;
+
+// header and footer flags
+const HAS_HEADER: u32 = 0x8000_0000;
+const IS_HEADER: u32 = 0x2000_0000;
+
+// item flags, bits 1-2 give the type of the value
+const ITEM_TYPE: u32 = 0x6;
+const ITEM_TEXT: u32 = 0x0;
+const ITEM_BINARY: u32 = 0x2;
+
+const COVER: &str = "Cover Art (Front)";
+
+#[derive(Debug, Default)]
+pub struct Footer {
+    // 1000 for APEv1, 2000 for APEv2
+    pub version: u32,
+    // size of items and footer, excluding the header
+    pub size: u32,
+    pub count: u32,
+    pub flags: u32,
+}
+
+impl Footer {
+    pub fn has_header(&self) -> bool {
+        self.flags & HAS_HEADER != 0
+    }
+    // size of the whole tag, including the header
+    pub fn tag_size(&self) -> u64 {
+        self.size as u64 + if self.has_header() { 32 } else { 0 }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Item {
+    key: String,
+    flags: u32,
+    value: Vec<u8>,
+}
+
+// read the APE tag footer that ends at `end`, if there is one
+pub fn footer<T: Read + Seek>(input: &mut T, end: u64) -> Result<Option<Footer>, Error> {
+    if end < 32 {
+        return Ok(None);
+    }
+    input.seek(SeekFrom::Start(end - 32))?;
+
+    let mut arr: [u8; 32] = [0; 32];
+    input.read_exact(&mut arr)?;
+
+    if &arr[0..8] != b"APETAGEX" {
+        return Ok(None);
+    }
+
+    let footer = Footer {
+        version: decode_int_le_u32(&arr[8..12]),
+        size: decode_int_le_u32(&arr[12..16]),
+        count: decode_int_le_u32(&arr[16..20]),
+        flags: decode_int_le_u32(&arr[20..24]),
+    };
+
+    // APEv1 has no header, whatever the flags say
+    let footer = match footer.version {
+        1000 => Footer { flags: 0, ..footer },
+        _ => footer,
+    };
+
+    if footer.size < 32 || footer.tag_size() > end {
+        return Ok(None);
+    }
+    Ok(Some(footer))
+}
+
+// locate an APE tag ending at `end` as (start, length)
+pub fn locate<T: Read + Seek>(input: &mut T, end: u64) -> Result<Option<(u64, u64)>, Error> {
+    Ok(footer(input, end)?.map(|f| (end - f.tag_size(), f.tag_size())))
+}
+
+// where an APE tag has to end: before Lyrics3 and ID3v1
+//     [audio] [APE] [Lyrics3] [ID3v1]
+fn tag_end<T: Read + Seek>(input: &mut T) -> Result<u64, Error> {
+    let mut end = input.seek(SeekFrom::End(0))?;
+    if id3v1::has_id3v1(input) {
+        end -= 128;
+        if let Some((start, _)) = lyrics3::locate(input, end)? {
+            end = start;
+        }
+    }
+    Ok(end)
+}
+
+// where the audio data ends, in front of any APE, Lyrics3 and ID3v1 tags
+pub fn audio_end<T: Read + Seek>(input: &mut T) -> Result<u64, Error> {
+    let end = tag_end(input)?;
+    Ok(match footer(input, end)? {
+        Some(f) => end - f.tag_size(),
+        None => end,
+    })
+}
+
+pub fn has_ape<T: Read + Seek>(input: &mut T) -> bool {
+    match tag_end(input) {
+        Ok(end) => matches!(footer(input, end), Ok(Some(_))),
+        Err(_) => false,
+    }
+}
+
+// the items of the tag ending at `end`, along with where the tag starts
+fn read_items<T: Read + Seek>(input: &mut T, end: u64) -> Result<Option<(u64, Vec<Item>)>, Error> {
+    let footer = match footer(input, end)? {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+
+    let mut vec = vec![0; footer.size as usize - 32];
+    input.seek(SeekFrom::Start(end - footer.size as u64))?;
+    input.read_exact(&mut vec)?;
+
+    let mut items = Vec::new();
+    let mut pos = 0;
+    for _ in 0..footer.count {
+        // value size, flags, key and terminator at least
+        if pos + 10 > vec.len() {
+            break;
+        }
+        let size = decode_int_le_u32(&vec[pos..pos + 4]) as usize;
+        let flags = decode_int_le_u32(&vec[pos + 4..pos + 8]);
+        pos += 8;
+
+        let key_end = match vec[pos..].iter().position(|&b| b == 0) {
+            Some(x) => pos + x,
+            None => break,
+        };
+        if key_end + 1 + size > vec.len() {
+            break;
+        }
+        let key = String::from_utf8_lossy(&vec[pos..key_end]).into_owned();
+        let value = vec[key_end + 1..key_end + 1 + size].to_vec();
+        pos = key_end + 1 + size;
+
+        items.push(Item { key, flags, value });
+    }
+
+    Ok(Some((end - footer.tag_size(), items)))
+}
+
+pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    let end = tag_end(input)?;
+    match read_items(input, end)? {
+        Some((_, items)) => Ok(collect_tags(&items)),
+        None => Err(tag_error("APE tag not found")),
+    }
+}
+
+macro_rules! parse_num {
+    ($tag:expr, $str:expr) => {{
+        if let Ok(x) = $str.trim().parse::<i64>() {
+            $tag = TagOption::Some(x);
+        }
+    }};
+}
+
+fn collect_tags(items: &[Item]) -> Tags {
+    let mut tags = Tags::none();
+    let mut custom = BTreeMap::new();
+
+    for item in items.iter() {
+        if item.flags & ITEM_TYPE == ITEM_BINARY {
+            if item.key.eq_ignore_ascii_case(COVER) {
+                tags.front_cover = TagOption::Some(read_cover(&item.value));
+            }
+            continue;
+        }
+        if item.flags & ITEM_TYPE != ITEM_TEXT {
+            continue;
+        }
+
+        // multiple values are separated by zero bytes, we only take the first
+        let text = String::from_utf8_lossy(&item.value);
+        let value = text.split('\0').next().unwrap_or("").to_string();
+
+        match item.key.to_uppercase().as_str() {
+            "TITLE" => tags.title = TagOption::Some(value),
+            "ALBUM" => tags.album = TagOption::Some(value),
+            "ARTIST" => tags.artist = TagOption::Some(value),
+            "ALBUM ARTIST" | "ALBUMARTIST" => tags.album_artist = TagOption::Some(value),
+            "COMPOSER" => tags.composer = TagOption::Some(value),
+            "GROUPING" => tags.grouping = TagOption::Some(value),
+            "GENRE" => tags.genre = TagOption::Some(value),
+
+            "YEAR" => tags.date = DateTime::from_iso_8601(value.as_str()).into(),
+
+            "TRACK" => pair(&value, &mut tags.track_number, &mut tags.track_total),
+            "DISC" => pair(&value, &mut tags.disc_number, &mut tags.disc_total),
+            "BPM" => parse_num!(tags.bpm, value),
+
+            "COMPILATION" => {
+                tags.is_compilation =
+                    TagOption::Some(matches!(value.to_lowercase().as_str(), "true" | "1"))
+            }
+
+            "COMMENT" => tags.comment = TagOption::Some(value),
+            "COPYRIGHT" => tags.copyright = TagOption::Some(value),
+            "TITLESORT" => tags.sort_title = TagOption::Some(value),
+            "ALBUMSORT" => tags.sort_album = TagOption::Some(value),
+            "ARTISTSORT" => tags.sort_artist = TagOption::Some(value),
+            "ALBUMARTISTSORT" => tags.sort_album_artist = TagOption::Some(value),
+            "COMPOSERSORT" => tags.sort_composer = TagOption::Some(value),
+
+            _ => {
+                custom.insert(item.key.clone(), value);
+            }
+        }
+    }
+
+    if !custom.is_empty() {
+        tags.custom = TagOption::Some(custom);
+    }
+    tags
+}
+
+// binary cover items are a file name, a zero byte and the image itself
+fn read_cover(value: &[u8]) -> Image {
+    let data = match value.iter().position(|&b| b == 0) {
+        Some(i) => &value[i + 1..],
+        None => value,
+    };
+    Image::from_bytes(data)
+}
+
+macro_rules! write_text {
+    ($items:ident, $text:expr, $key:expr) => {{
+        if let TagOption::Some(ref t) = $text {
+            if t.as_str() != "" {
+                $items.push(Item {
+                    key: $key.to_string(),
+                    flags: ITEM_TEXT,
+                    value: t.as_bytes().to_vec(),
+                });
+            }
+        }
+    }};
+}
+macro_rules! write_pair {
+    ($items:ident, $number:expr, $total:expr, $key:expr) => {{
+        if let TagOption::Some(n) = $number {
+            let text = match $total {
+                TagOption::Some(t) => format!("{}/{}", n, t),
+                _ => n.to_string(),
+            };
+            write_text!($items, TagOption::Some(text), $key);
+        }
+    }};
+}
+
+fn render(tags: &Tags, keep: Vec<Item>) -> Vec<u8> {
+    let mut items = Vec::new();
+
+    write_text!(items, tags.title, "Title");
+    write_text!(items, tags.album, "Album");
+    write_text!(items, tags.artist, "Artist");
+    write_text!(items, tags.album_artist, "Album Artist");
+    write_text!(items, tags.composer, "Composer");
+    write_text!(items, tags.grouping, "Grouping");
+    write_text!(items, tags.genre, "Genre");
+
+    if let TagOption::Some(ref t) = tags.date {
+        write_text!(items, TagOption::Some(t.to_iso_8601()), "Year");
+    }
+
+    write_pair!(items, tags.track_number, tags.track_total, "Track");
+    write_pair!(items, tags.disc_number, tags.disc_total, "Disc");
+    if let TagOption::Some(x) = tags.bpm {
+        write_text!(items, TagOption::Some(x.to_string()), "BPM");
+    }
+    if let TagOption::Some(true) = tags.is_compilation {
+        write_text!(items, TagOption::Some("1".to_string()), "Compilation");
+    }
+
+    write_text!(items, tags.comment, "Comment");
+    write_text!(items, tags.copyright, "Copyright");
+    write_text!(items, tags.sort_title, "TITLESORT");
+    write_text!(items, tags.sort_album, "ALBUMSORT");
+    write_text!(items, tags.sort_artist, "ARTISTSORT");
+    write_text!(items, tags.sort_album_artist, "ALBUMARTISTSORT");
+    write_text!(items, tags.sort_composer, "COMPOSERSORT");
+
+    if let TagOption::Some(ref x) = tags.custom {
+        for (key, value) in x.iter() {
+            write_text!(items, TagOption::Some(value), key);
+        }
+    }
+
+    if let TagOption::Some(ref i) = tags.front_cover {
+        let name: &[u8] = match i {
+            Image::JPEG(_) => b"cover.jpg\0",
+            Image::PNG(_) => b"cover.png\0",
+            Image::None => b"",
+        };
+        if let Image::JPEG(ref x) | Image::PNG(ref x) = i {
+            items.push(Item {
+                key: COVER.to_string(),
+                flags: ITEM_BINARY,
+                value: [name, x].concat(),
+            });
+        }
+    }
+
+    // binary items we don't understand stay as they are
+    items.extend(keep);
+
+    let mut body = Vec::new();
+    for item in items.iter() {
+        body.extend_from_slice(&encode_int_le_u32(item.value.len() as u32));
+        body.extend_from_slice(&encode_int_le_u32(item.flags));
+        body.extend_from_slice(item.key.as_bytes());
+        body.push(0);
+        body.extend_from_slice(&item.value);
+    }
+
+    let size = body.len() as u32 + 32;
+    let mut vec = header(size, items.len() as u32, HAS_HEADER | IS_HEADER);
+    vec.append(&mut body);
+    vec.append(&mut header(size, items.len() as u32, HAS_HEADER));
+    vec
+}
+
+// an APEv2 header or footer
+fn header(size: u32, count: u32, flags: u32) -> Vec<u8> {
+    let mut vec = b"APETAGEX".to_vec();
+    vec.extend_from_slice(&encode_int_le_u32(2000));
+    vec.extend_from_slice(&encode_int_le_u32(size));
+    vec.extend_from_slice(&encode_int_le_u32(count));
+    vec.extend_from_slice(&encode_int_le_u32(flags));
+    vec.extend_from_slice(&[0; 8]);
+    vec
+}
+
+// write an APEv2 tag in place, in front of any Lyrics3 and ID3v1 tags
+// returns the new length of the file, which the caller has to truncate to
+pub fn set<T: Read + Write + Seek>(file: &mut T, new: &Tags) -> Result<u64, Error> {
+    let end = tag_end(file)?;
+    let (start, items) = read_items(file, end)?.unwrap_or((end, Vec::new()));
+
+    let tags = delta(&collect_tags(&items), new);
+
+    // keep binary and external items, unless they're the cover we manage
+    let keep = items
+        .into_iter()
+        .filter(|i| i.flags & ITEM_TYPE != ITEM_TEXT && !i.key.eq_ignore_ascii_case(COVER))
+        .collect();
+    let vec = render(&tags, keep);
+
+    let mut trailing = Vec::new();
+    file.seek(SeekFrom::Start(end))?;
+    file.read_to_end(&mut trailing)?;
+
+    file.seek(SeekFrom::Start(start))?;
+    file.write_all(&vec)?;
+    file.write_all(&trailing)?;
+    file.flush()?;
+
+    Ok(start + vec.len() as u64 + trailing.len() as u64)
+}
+
+#[cfg(test)]
+mod tests;
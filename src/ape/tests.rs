@@ -0,0 +1,127 @@
+use crate::DateTime;
+use crate::Image;
+use crate::TagOption;
+use crate::Tags;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::Cursor;
+
+fn cover() -> Image {
+    let mut vec = Vec::new();
+    File::open("testfiles/flac-cover.jpg")
+        .unwrap()
+        .read_to_end(&mut vec)
+        .unwrap();
+    Image::JPEG(vec)
+}
+
+#[test]
+fn ape_render_test() {
+    let mut custom = BTreeMap::new();
+    custom.insert("Catalog".to_string(), "XYZ-1".to_string());
+    let tags = Tags {
+        title: TagOption::Some("Title".to_string()),
+        artist: TagOption::Some("Artist Name".to_string()),
+        album_artist: TagOption::Some("Album Artist".to_string()),
+        date: DateTime::from_iso_8601("2017-05-01").into(),
+        track_number: TagOption::Some(3),
+        track_total: TagOption::Some(12),
+        disc_number: TagOption::Some(1),
+        is_compilation: TagOption::Some(true),
+        comment: TagOption::Some("Comment æÖÆ 👀".to_string()),
+        front_cover: TagOption::Some(cover()),
+        custom: TagOption::Some(custom),
+        ..Tags::none()
+    };
+
+    let mut cursor = Cursor::new(vec![0xFF; 256]);
+    let length = super::set(&mut cursor, &tags).unwrap();
+    assert_eq!(length, cursor.get_ref().len() as u64);
+    // header, then footer at the very end
+    assert_eq!(&cursor.get_ref()[256..264], b"APETAGEX");
+
+    assert_eq!(super::get(&mut cursor).unwrap(), tags);
+}
+
+#[test]
+fn ape_v1_test() {
+    let mut vec = vec![0xFF; 64];
+
+    // APEv1 has a footer only
+    let mut items = Vec::new();
+    for &(key, value) in [("Title", "Old Title"), ("Track", "7")].iter() {
+        items.extend_from_slice(&[value.len() as u8, 0, 0, 0, 0, 0, 0, 0]);
+        items.extend_from_slice(key.as_bytes());
+        items.push(0);
+        items.extend_from_slice(value.as_bytes());
+    }
+    vec.extend_from_slice(&items);
+    let mut footer = super::header(items.len() as u32 + 32, 2, 0);
+    footer[8..12].copy_from_slice(&[0xE8, 0x03, 0, 0]); // version 1000
+    vec.append(&mut footer);
+
+    let tags = super::get(&mut Cursor::new(&vec)).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("Old Title".to_string()),
+        track_number: TagOption::Some(7),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+}
+
+#[test]
+fn ape_position_test() {
+    // audio, Lyrics3v2 and ID3v1
+    let mut vec = vec![0xFF; 256];
+    let lyrics = b"LYRICSBEGININD0000200";
+    vec.extend_from_slice(lyrics);
+    vec.extend_from_slice(format!("{:06}LYRICS200", lyrics.len()).as_bytes());
+    let mut id3v1 = b"TAG".to_vec();
+    id3v1.resize(128, 0);
+    vec.extend_from_slice(&id3v1);
+    let trailing = vec[256..].to_vec();
+
+    // the new tag goes in front of Lyrics3, both stay intact
+    let mut cursor = Cursor::new(vec);
+    let new = Tags {
+        title: TagOption::Some("Title".to_string()),
+        ..Tags::mixed()
+    };
+    let length = super::set(&mut cursor, &new).unwrap();
+    let vec = cursor.into_inner();
+    assert_eq!(length, vec.len() as u64);
+    assert!(vec.ends_with(&trailing));
+    assert_eq!(&vec[256..264], b"APETAGEX");
+
+    // then replace it with a smaller one, keeping unknown binary items
+    let mut vec = vec;
+    let start = vec.len() - trailing.len() - 32;
+    let mut binary = vec![4, 0, 0, 0, 2, 0, 0, 0];
+    binary.extend_from_slice(b"Other\0data");
+    vec.splice(start..start, binary.iter().cloned());
+    // fix up item count and size in header and footer
+    for &offset in [256, vec.len() - trailing.len() - 32].iter() {
+        vec[offset + 12] += binary.len() as u8;
+        vec[offset + 16] += 1;
+    }
+
+    let mut cursor = Cursor::new(vec);
+    let new = Tags {
+        title: TagOption::None,
+        artist: TagOption::Some("A".to_string()),
+        ..Tags::mixed()
+    };
+    let length = super::set(&mut cursor, &new).unwrap() as usize;
+    let vec = &cursor.get_ref()[..length];
+    assert!(vec.ends_with(&trailing));
+
+    let items = super::read_items(&mut Cursor::new(vec), (length - trailing.len()) as u64)
+        .unwrap()
+        .unwrap()
+        .1;
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].key, "Artist");
+    assert_eq!(items[1].key, "Other");
+    assert_eq!(items[1].value, b"data");
+}
@@ -0,0 +1,680 @@
+use std::collections::BTreeMap;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::DateTime;
+use crate::Error;
+use crate::Image;
+use crate::TagOption;
+use crate::Tags;
+
+use crate::tools::decode_int_le_u32;
+use crate::tools::decode_int_le_u64;
+use crate::tools::encode_int_le_u32;
+use crate::tools::encode_int_le_u64;
+use crate::tools::encoding::decode_utf16le;
+use crate::tools::encoding::encode_utf16le;
+use crate::tools::tag_error;
+use crate::tools::tags::delta;
+use crate::tools::tags::fill;
+use crate::tools::tags::number;
+use crate::tools::tags::pair;
+
+type Guid = [u8; 16];
+
+const HEADER: Guid = [
+    0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11, 0xA6, 0xD9, 0x00, 0xAA, 0x00, 0x62, 0xCE, 0x6C,
+];
+const FILE_PROPERTIES: Guid = [
+    0xA1, 0xDC, 0xAB, 0x8C, 0x47, 0xA9, 0xCF, 0x11, 0x8E, 0xE4, 0x00, 0xC0, 0x0C, 0x20, 0x53, 0x65,
+];
+const CONTENT_DESCRIPTION: Guid = [
+    0x33, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11, 0xA6, 0xD9, 0x00, 0xAA, 0x00, 0x62, 0xCE, 0x6C,
+];
+const EXTENDED_CONTENT: Guid = [
+    0x40, 0xA4, 0xD0, 0xD2, 0x07, 0xE3, 0xD2, 0x11, 0x97, 0xF0, 0x00, 0xA0, 0xC9, 0x5E, 0xA8, 0x50,
+];
+const HEADER_EXTENSION: Guid = [
+    0xB5, 0x03, 0xBF, 0x5F, 0x2E, 0xA9, 0xCF, 0x11, 0x8E, 0xE3, 0x00, 0xC0, 0x0C, 0x20, 0x53, 0x65,
+];
+// the first field of the header extension, which is always the same
+const HEADER_EXTENSION_RESERVED: Guid = [
+    0x11, 0xD2, 0xD3, 0xAB, 0xBA, 0xA9, 0xCF, 0x11, 0x8E, 0xE6, 0x00, 0xC0, 0x0C, 0x20, 0x53, 0x65,
+];
+const METADATA_LIBRARY: Guid = [
+    0x94, 0x1C, 0x23, 0x44, 0x98, 0x94, 0xD1, 0x49, 0xA1, 0x41, 0x1D, 0x13, 0x4E, 0x45, 0x70, 0x54,
+];
+
+// attribute value types
+const UNICODE: u16 = 0;
+const BYTES: u16 = 1;
+const BOOL: u16 = 2;
+const DWORD: u16 = 3;
+const QWORD: u16 = 4;
+const WORD: u16 = 5;
+
+// picture type of the front cover, as in ID3v2 APIC
+const FRONT_COVER: u8 = 3;
+
+// the attributes we map onto tags, besides front cover pictures
+const MAPPED: [&str; 16] = [
+    "WM/AlbumTitle",
+    "WM/AlbumArtist",
+    "WM/Composer",
+    "WM/ContentGroupDescription",
+    "WM/Genre",
+    "WM/Year",
+    "WM/TrackNumber",
+    "WM/Track",
+    "WM/PartOfSet",
+    "WM/BeatsPerMinute",
+    "WM/IsCompilation",
+    "WM/TitleSortOrder",
+    "WM/AlbumSortOrder",
+    "WM/ArtistSortOrder",
+    "WM/AlbumArtistSortOrder",
+    "WM/ComposerSortOrder",
+];
+
+struct Attribute {
+    name: String,
+    kind: u16,
+    value: Vec<u8>,
+    // Metadata Library records can be about a single stream, or in another language
+    stream: u16,
+    language: u16,
+}
+
+impl Attribute {
+    fn text(&self) -> Option<String> {
+        let number = decode_int_le_u64(&self.value[..std::cmp::min(8, self.value.len())]);
+        match self.kind {
+            UNICODE => Some(decode_utf16le(&self.value)),
+            BOOL | DWORD | QWORD | WORD => Some(number.to_string()),
+            _ => None,
+        }
+    }
+    // only attributes about the whole file are ours
+    fn is_global(&self) -> bool {
+        self.stream == 0 && self.language == 0
+    }
+    // other pictures are left alone
+    fn is_front_cover(&self) -> bool {
+        self.name == "WM/Picture" && self.value.first() == Some(&FRONT_COVER)
+    }
+}
+
+struct Header {
+    // the whole header object, and what's in it
+    size: u64,
+    objects: Vec<(Guid, Vec<u8>)>,
+}
+
+fn decode_u16(value: &[u8]) -> u16 {
+    decode_int_le_u32(value) as u16
+}
+
+// the objects in an object's data, starting at pos
+fn objects(vec: &[u8], mut pos: usize) -> Vec<(Guid, Vec<u8>)> {
+    let mut objects = Vec::new();
+    while pos + 24 <= vec.len() {
+        let mut guid: Guid = [0; 16];
+        guid.copy_from_slice(&vec[pos..pos + 16]);
+        let size = decode_int_le_u64(&vec[pos + 16..pos + 24]) as usize;
+        if size < 24 {
+            break;
+        }
+        let end = std::cmp::min(pos.saturating_add(size), vec.len());
+        objects.push((guid, vec[pos + 24..end].to_vec()));
+        pos = end;
+    }
+    objects
+}
+
+fn read_header<T: Read + Seek>(input: &mut T) -> Result<Header, Error> {
+    let mut arr: [u8; 30] = [0; 30];
+    input.seek(SeekFrom::Start(0))?;
+    input.read_exact(&mut arr)?;
+
+    if arr[0..16] != HEADER {
+        return Err(tag_error("Not an ASF file"));
+    }
+    let size = decode_int_le_u64(&arr[16..24]);
+    if size < 30 {
+        return Err(tag_error("Invalid ASF header size"));
+    }
+
+    let mut vec = Vec::new();
+    input.take(size - 30).read_to_end(&mut vec)?;
+
+    Ok(Header {
+        size,
+        objects: objects(&vec, 0),
+    })
+}
+
+// five lengths, then title, author, copyright, description and rating
+fn read_content_description(data: &[u8]) -> Vec<String> {
+    let mut strings = Vec::new();
+    if data.len() < 10 {
+        return strings;
+    }
+    let mut pos = 10;
+    for i in 0..5 {
+        let length = decode_u16(&data[i * 2..i * 2 + 2]) as usize;
+        let end = std::cmp::min(pos + length, data.len());
+        strings.push(decode_utf16le(&data[std::cmp::min(pos, end)..end]));
+        pos = end;
+    }
+    strings
+}
+
+fn read_extended_content(data: &[u8]) -> Vec<Attribute> {
+    let mut attributes = Vec::new();
+    let mut pos = 2;
+    while pos + 2 <= data.len() {
+        let name_length = decode_u16(&data[pos..pos + 2]) as usize;
+        let name_end = pos + 2 + name_length;
+        if name_end + 4 > data.len() {
+            break;
+        }
+        let kind = decode_u16(&data[name_end..name_end + 2]);
+        let value_length = decode_u16(&data[name_end + 2..name_end + 4]) as usize;
+        let value_end = std::cmp::min(name_end + 4 + value_length, data.len());
+        attributes.push(Attribute {
+            name: decode_utf16le(&data[pos + 2..name_end]),
+            kind,
+            value: data[name_end + 4..value_end].to_vec(),
+            stream: 0,
+            language: 0,
+        });
+        pos = value_end;
+    }
+    attributes
+}
+
+fn read_metadata_library(data: &[u8]) -> Vec<Attribute> {
+    let mut attributes = Vec::new();
+    let mut pos = 2;
+    while pos + 12 <= data.len() {
+        let name_length = decode_u16(&data[pos + 4..pos + 6]) as usize;
+        let value_length = decode_int_le_u32(&data[pos + 8..pos + 12]) as usize;
+        let name_end = std::cmp::min(pos + 12 + name_length, data.len());
+        let value_end = std::cmp::min(name_end.saturating_add(value_length), data.len());
+        attributes.push(Attribute {
+            name: decode_utf16le(&data[pos + 12..name_end]),
+            kind: decode_u16(&data[pos + 6..pos + 8]),
+            value: data[name_end..value_end].to_vec(),
+            stream: decode_u16(&data[pos + 2..pos + 4]),
+            language: decode_u16(&data[pos..pos + 2]),
+        });
+        pos = value_end;
+    }
+    attributes
+}
+
+// the Metadata Library lives in the header extension
+fn header_extension(data: &[u8]) -> Vec<(Guid, Vec<u8>)> {
+    objects(data, 22)
+}
+
+// content description strings, then the attributes of both the extended
+// content description and the metadata library
+fn read_all(header: &Header) -> (Vec<String>, Vec<Attribute>) {
+    let mut strings = Vec::new();
+    let mut attributes = Vec::new();
+    let mut library = Vec::new();
+    for (guid, data) in header.objects.iter() {
+        match *guid {
+            CONTENT_DESCRIPTION => strings = read_content_description(data),
+            EXTENDED_CONTENT => attributes.append(&mut read_extended_content(data)),
+            HEADER_EXTENSION => {
+                for (guid, data) in header_extension(data) {
+                    if guid == METADATA_LIBRARY {
+                        library.append(&mut read_metadata_library(&data));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    attributes.append(&mut library);
+    (strings, attributes)
+}
+
+// type, data length, mime type, description and the picture itself
+fn read_picture(value: &[u8]) -> Option<Image> {
+    if value.len() < 5 {
+        return None;
+    }
+    let length = decode_int_le_u32(&value[1..5]) as usize;
+
+    // skip two zero terminated UTF-16 strings
+    let mut pos = 5;
+    for _ in 0..2 {
+        while pos + 1 < value.len() && (value[pos] != 0 || value[pos + 1] != 0) {
+            pos += 2;
+        }
+        pos += 2;
+    }
+    let data = value.get(pos..pos.checked_add(length)?)?;
+    Some(Image::from_bytes(data)).filter(Image::is_some)
+}
+
+fn collect_tags(strings: &[String], attributes: &[Attribute]) -> Tags {
+    let mut tags = Tags::none();
+    let mut custom = BTreeMap::new();
+
+    let string = |i: usize| match strings.get(i) {
+        Some(x) if x.as_str() != "" => TagOption::Some(x.clone()),
+        _ => TagOption::None,
+    };
+    tags.title = string(0);
+    tags.artist = string(1);
+    tags.copyright = string(2);
+    tags.comment = string(3);
+
+    for attribute in attributes.iter().filter(|a| a.is_global()) {
+        if attribute.is_front_cover() {
+            if let Some(image) = read_picture(&attribute.value) {
+                fill(&mut tags.front_cover, TagOption::Some(image));
+            }
+            continue;
+        }
+
+        let value = match attribute.text() {
+            Some(x) => x,
+            None => continue,
+        };
+        let text = TagOption::Some(value.clone());
+        match attribute.name.as_str() {
+            "WM/AlbumTitle" => fill(&mut tags.album, text),
+            "WM/AlbumArtist" => fill(&mut tags.album_artist, text),
+            "WM/Composer" => fill(&mut tags.composer, text),
+            "WM/ContentGroupDescription" => fill(&mut tags.grouping, text),
+            "WM/Genre" => fill(&mut tags.genre, text),
+            "WM/Year" => fill(&mut tags.date, DateTime::from_iso_8601(&value).into()),
+            "WM/TrackNumber" => pair(&value, &mut tags.track_number, &mut tags.track_total),
+            // the old attribute counts from 0
+            "WM/Track" => fill(
+                &mut tags.track_number,
+                value.trim().parse::<i64>().ok().map(|x| x + 1).into(),
+            ),
+            "WM/PartOfSet" => pair(&value, &mut tags.disc_number, &mut tags.disc_total),
+            "WM/BeatsPerMinute" => fill(&mut tags.bpm, number(&value)),
+            "WM/IsCompilation" => fill(
+                &mut tags.is_compilation,
+                TagOption::Some(matches!(value.to_lowercase().as_str(), "1" | "true")),
+            ),
+            "WM/TitleSortOrder" => fill(&mut tags.sort_title, text),
+            "WM/AlbumSortOrder" => fill(&mut tags.sort_album, text),
+            "WM/ArtistSortOrder" => fill(&mut tags.sort_artist, text),
+            "WM/AlbumArtistSortOrder" => fill(&mut tags.sort_album_artist, text),
+            "WM/ComposerSortOrder" => fill(&mut tags.sort_composer, text),
+            _ => {
+                if attribute.kind == UNICODE {
+                    custom.entry(attribute.name.clone()).or_insert(value);
+                }
+            }
+        }
+    }
+
+    if !custom.is_empty() {
+        tags.custom = TagOption::Some(custom);
+    }
+    tags
+}
+
+pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    let header = read_header(input)?;
+    let (strings, attributes) = read_all(&header);
+    Ok(collect_tags(&strings, &attributes))
+}
+
+// zero terminated UTF-16
+fn encode_string(value: &str) -> Vec<u8> {
+    let mut vec = encode_utf16le(value);
+    vec.extend_from_slice(&[0, 0]);
+    vec
+}
+
+fn new_object(guid: &Guid, data: &[u8]) -> Vec<u8> {
+    let mut vec = guid.to_vec();
+    vec.append(&mut encode_int_le_u64(data.len() as u64 + 24));
+    vec.extend_from_slice(data);
+    vec
+}
+
+fn new_attribute(name: &str, kind: u16, value: Vec<u8>) -> Attribute {
+    Attribute {
+        name: name.to_string(),
+        kind,
+        value,
+        stream: 0,
+        language: 0,
+    }
+}
+
+macro_rules! write_text {
+    ($attributes:ident, $text:expr, $name:expr) => {{
+        if let TagOption::Some(ref t) = $text {
+            if t.as_str() != "" {
+                $attributes.push(new_attribute($name, UNICODE, encode_string(t)));
+            }
+        }
+    }};
+}
+
+// the attributes for everything we map, except the content description
+fn attributes(tags: &Tags) -> Vec<Attribute> {
+    let mut attributes = Vec::new();
+
+    write_text!(attributes, tags.album, "WM/AlbumTitle");
+    write_text!(attributes, tags.album_artist, "WM/AlbumArtist");
+    write_text!(attributes, tags.composer, "WM/Composer");
+    write_text!(attributes, tags.grouping, "WM/ContentGroupDescription");
+    write_text!(attributes, tags.genre, "WM/Genre");
+    if let TagOption::Some(ref x) = tags.date {
+        write_text!(attributes, TagOption::Some(x.to_iso_8601()), "WM/Year");
+    }
+    // a total only fits into the text form
+    if let TagOption::Some(x) = tags.track_number {
+        match tags.track_total {
+            TagOption::Some(total) => {
+                let value = format!("{}/{}", x, total);
+                write_text!(attributes, TagOption::Some(value), "WM/TrackNumber");
+            }
+            _ => {
+                let value = encode_int_le_u32(x as u32);
+                attributes.push(new_attribute("WM/TrackNumber", DWORD, value));
+            }
+        }
+    }
+    if let TagOption::Some(x) = tags.disc_number {
+        let value = match tags.disc_total {
+            TagOption::Some(total) => format!("{}/{}", x, total),
+            _ => x.to_string(),
+        };
+        write_text!(attributes, TagOption::Some(value), "WM/PartOfSet");
+    }
+    if let TagOption::Some(x) = tags.bpm {
+        write_text!(
+            attributes,
+            TagOption::Some(x.to_string()),
+            "WM/BeatsPerMinute"
+        );
+    }
+    if let TagOption::Some(x) = tags.is_compilation {
+        let value = encode_int_le_u32(x as u32);
+        attributes.push(new_attribute("WM/IsCompilation", BOOL, value));
+    }
+    write_text!(attributes, tags.sort_title, "WM/TitleSortOrder");
+    write_text!(attributes, tags.sort_album, "WM/AlbumSortOrder");
+    write_text!(attributes, tags.sort_artist, "WM/ArtistSortOrder");
+    write_text!(
+        attributes,
+        tags.sort_album_artist,
+        "WM/AlbumArtistSortOrder"
+    );
+    write_text!(attributes, tags.sort_composer, "WM/ComposerSortOrder");
+
+    if let TagOption::Some(Image::JPEG(ref data)) | TagOption::Some(Image::PNG(ref data)) =
+        tags.front_cover
+    {
+        let image = tags.front_cover.as_ref().unwrap();
+        let mut value = vec![FRONT_COVER];
+        value.append(&mut encode_int_le_u32(data.len() as u32));
+        value.append(&mut encode_string(&image.mime()));
+        value.append(&mut encode_string(""));
+        value.extend_from_slice(data);
+        attributes.push(new_attribute("WM/Picture", BYTES, value));
+    }
+
+    if let TagOption::Some(ref x) = tags.custom {
+        for (name, value) in x.iter() {
+            write_text!(attributes, TagOption::Some(value.clone()), name);
+        }
+    }
+    attributes
+}
+
+fn render_content_description(strings: &[String]) -> Vec<u8> {
+    let encoded: Vec<Vec<u8>> = strings
+        .iter()
+        .map(|s| match s.as_str() {
+            "" => Vec::new(),
+            x => encode_string(x),
+        })
+        .collect();
+    let mut data = Vec::new();
+    for x in encoded.iter() {
+        data.extend_from_slice(&(x.len() as u16).to_le_bytes());
+    }
+    for x in encoded.iter() {
+        data.extend_from_slice(x);
+    }
+    data
+}
+
+fn render_extended_content(attributes: &[Attribute]) -> Vec<u8> {
+    let mut data = (attributes.len() as u16).to_le_bytes().to_vec();
+    for attribute in attributes.iter() {
+        let name = encode_string(&attribute.name);
+        data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        data.extend_from_slice(&name);
+        data.extend_from_slice(&attribute.kind.to_le_bytes());
+        data.extend_from_slice(&(attribute.value.len() as u16).to_le_bytes());
+        data.extend_from_slice(&attribute.value);
+    }
+    data
+}
+
+fn render_metadata_library(attributes: &[Attribute]) -> Vec<u8> {
+    let mut data = (attributes.len() as u16).to_le_bytes().to_vec();
+    for attribute in attributes.iter() {
+        let name = encode_string(&attribute.name);
+        data.extend_from_slice(&attribute.language.to_le_bytes());
+        data.extend_from_slice(&attribute.stream.to_le_bytes());
+        data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        data.extend_from_slice(&attribute.kind.to_le_bytes());
+        data.append(&mut encode_int_le_u32(attribute.value.len() as u32));
+        data.extend_from_slice(&name);
+        data.extend_from_slice(&attribute.value);
+    }
+    data
+}
+
+// does an attribute we read stay as it is? everything we map, the front
+// cover, and any text (which is in custom) is written anew
+fn keep(attribute: &Attribute) -> bool {
+    !attribute.is_global()
+        || !(MAPPED.contains(&attribute.name.as_str())
+            || attribute.is_front_cover()
+            || attribute.kind == UNICODE)
+}
+
+pub fn set<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    new: &Tags,
+) -> Result<(), Error> {
+    let header = read_header(input)?;
+    let (strings, old) = read_all(&header);
+    let tags = delta(&collect_tags(&strings, &old), new);
+
+    // the rating is the only part of the content description we don't map
+    let text = |x: &TagOption<String>| match x {
+        TagOption::Some(ref x) => x.clone(),
+        _ => String::new(),
+    };
+    let rating = strings.get(4).cloned().unwrap_or_default();
+    let content = [
+        text(&tags.title),
+        text(&tags.artist),
+        text(&tags.copyright),
+        text(&tags.comment),
+        rating,
+    ];
+    let content = match content.iter().all(|s| s.is_empty()) {
+        true => None,
+        false => Some(render_content_description(&content)),
+    };
+
+    // values over 64k only fit into the metadata library
+    let mut extended = Vec::new();
+    let mut library = Vec::new();
+    for attribute in attributes(&tags) {
+        match attribute.value.len() > 0xFFFF {
+            true => library.push(attribute),
+            false => extended.push(attribute),
+        }
+    }
+    let extended = old_extended(&header)
+        .into_iter()
+        .chain(extended)
+        .collect::<Vec<_>>();
+
+    rewrite(
+        input,
+        output,
+        &header,
+        content,
+        Some(render_extended_content(&extended)),
+        library,
+    )
+}
+
+// copy the file without the content description, and without what we map
+// from the extended content description and the metadata library
+pub fn remove<R: Read + Seek, W: Write>(input: &mut R, output: &mut W) -> Result<(), Error> {
+    let header = read_header(input)?;
+    let extended = old_extended(&header);
+    let extended = match extended.is_empty() {
+        true => None,
+        false => Some(render_extended_content(&extended)),
+    };
+    rewrite(input, output, &header, None, extended, Vec::new())
+}
+
+// the extended content attributes we don't manage
+fn old_extended(header: &Header) -> Vec<Attribute> {
+    let mut attributes = Vec::new();
+    for (guid, data) in header.objects.iter() {
+        if *guid == EXTENDED_CONTENT {
+            attributes.append(&mut read_extended_content(data));
+        }
+    }
+    attributes.retain(keep);
+    attributes
+}
+
+// copy the file with a new header: the content description and extended
+// content description replaced (or dropped if None), and the metadata library
+// holding whatever we don't manage plus the given attributes
+fn rewrite<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    header: &Header,
+    mut content: Option<Vec<u8>>,
+    mut extended: Option<Vec<u8>>,
+    library: Vec<Attribute>,
+) -> Result<(), Error> {
+    let mut library = Some(library);
+
+    let mut objects = Vec::new();
+    for (guid, data) in header.objects.iter() {
+        let data = match *guid {
+            // duplicates are dropped once it's written
+            CONTENT_DESCRIPTION => match content.take() {
+                Some(x) => x,
+                None => continue,
+            },
+            EXTENDED_CONTENT => match extended.take() {
+                Some(x) => x,
+                None => continue,
+            },
+            HEADER_EXTENSION => render_header_extension(data, library.take()),
+            _ => data.clone(),
+        };
+        objects.push((*guid, data));
+    }
+    if let Some(x) = content {
+        objects.push((CONTENT_DESCRIPTION, x));
+    }
+    if let Some(x) = extended {
+        objects.push((EXTENDED_CONTENT, x));
+    }
+    if let Some(x) = library.filter(|x| !x.is_empty()) {
+        objects.push((HEADER_EXTENSION, render_header_extension(&[], Some(x))));
+    }
+
+    let size = objects
+        .iter()
+        .fold(30, |size, (_, data)| size + data.len() as u64 + 24);
+    let length = input.seek(SeekFrom::End(0))?;
+
+    output.write_all(&HEADER)?;
+    output.write_all(&encode_int_le_u64(size))?;
+    output.write_all(&encode_int_le_u32(objects.len() as u32))?;
+    output.write_all(&[1, 2])?;
+    for (guid, data) in objects.iter_mut() {
+        // the file properties know the size of the whole file
+        if *guid == FILE_PROPERTIES && data.len() >= 24 {
+            let file_size = length - header.size + size;
+            data[16..24].copy_from_slice(&encode_int_le_u64(file_size));
+        }
+        output.write_all(&new_object(guid, data))?;
+    }
+
+    input.seek(SeekFrom::Start(header.size))?;
+    std::io::copy(input, output)?;
+    output.flush()?;
+    Ok(())
+}
+
+// the header extension with a new metadata library, keeping what we don't manage;
+// None leaves it as it is
+fn render_header_extension(data: &[u8], new: Option<Vec<Attribute>>) -> Vec<u8> {
+    let mut new = match new {
+        Some(x) => x,
+        None => return data.to_vec(),
+    };
+
+    let mut objects = Vec::new();
+    let mut written = false;
+    for (guid, data) in header_extension(data) {
+        if guid != METADATA_LIBRARY {
+            objects.append(&mut new_object(&guid, &data));
+            continue;
+        }
+        let mut attributes: Vec<Attribute> = read_metadata_library(&data)
+            .into_iter()
+            .filter(keep)
+            .collect();
+        if !written {
+            attributes.append(&mut new);
+            written = true;
+        }
+        if !attributes.is_empty() {
+            objects.append(&mut new_object(
+                &guid,
+                &render_metadata_library(&attributes),
+            ));
+        }
+    }
+    if !written && !new.is_empty() {
+        objects.append(&mut new_object(
+            &METADATA_LIBRARY,
+            &render_metadata_library(&new),
+        ));
+    }
+
+    let mut vec = HEADER_EXTENSION_RESERVED.to_vec();
+    vec.extend_from_slice(&6u16.to_le_bytes());
+    vec.append(&mut encode_int_le_u32(objects.len() as u32));
+    vec.append(&mut objects);
+    vec
+}
+
+#[cfg(test)]
+mod tests;
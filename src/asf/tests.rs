@@ -0,0 +1,296 @@
+use crate::DateTime;
+use crate::Image;
+use crate::TagOption;
+use crate::Tags;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+use crate::tools::decode_int_le_u64;
+use crate::tools::encode_int_le_u32;
+use crate::tools::encode_int_le_u64;
+
+use super::new_attribute;
+use super::new_object;
+
+fn text(name: &str, value: &str) -> super::Attribute {
+    new_attribute(name, super::UNICODE, super::encode_string(value))
+}
+
+// file properties, content description, extended content description and
+// a metadata library, then a data object
+fn asf() -> Vec<u8> {
+    let mut properties = vec![0; 80];
+    properties[16..24].copy_from_slice(&encode_int_le_u64(0xDEAD));
+
+    let content = super::render_content_description(&[
+        "Title".to_string(),
+        "Artist".to_string(),
+        String::new(),
+        String::new(),
+        "Rating".to_string(),
+    ]);
+    let extended = super::render_extended_content(&[
+        text("WM/AlbumTitle", "Album"),
+        new_attribute("WM/TrackNumber", super::DWORD, encode_int_le_u32(7)),
+        text("WM/Year", "2004"),
+        text("WM/PartOfSet", "1/2"),
+        text("MusicBrainz/Track Id", "1234"),
+        new_attribute("WM/Unknown", super::BYTES, vec![1, 2, 3]),
+    ]);
+
+    let mut picture = vec![super::FRONT_COVER];
+    picture.append(&mut encode_int_le_u32(4));
+    picture.append(&mut super::encode_string("image/jpeg"));
+    picture.append(&mut super::encode_string("Cover"));
+    picture.extend_from_slice(&[0xFF, 0xD8, 0xFF, 0xE0]);
+    let mut stream = text("WM/Genre", "Stream Genre");
+    stream.stream = 1;
+    let extension = super::render_header_extension(
+        &[],
+        Some(vec![
+            new_attribute("WM/Picture", super::BYTES, picture),
+            text("WM/Genre", "Genre"),
+            stream,
+        ]),
+    );
+
+    let objects = [
+        new_object(&super::FILE_PROPERTIES, &properties),
+        new_object(&super::CONTENT_DESCRIPTION, &content),
+        new_object(&super::EXTENDED_CONTENT, &extended),
+        new_object(&super::HEADER_EXTENSION, &extension),
+    ];
+    let mut vec = super::HEADER.to_vec();
+    vec.append(&mut encode_int_le_u64(30 + objects.concat().len() as u64));
+    vec.append(&mut encode_int_le_u32(objects.len() as u32));
+    vec.extend_from_slice(&[1, 2]);
+    vec.append(&mut objects.concat());
+    vec.extend_from_slice(b"the data object");
+    vec
+}
+
+fn data(vec: &[u8]) -> &[u8] {
+    &vec[decode_int_le_u64(&vec[16..24]) as usize..]
+}
+
+fn file_size(vec: &[u8]) -> u64 {
+    let header = super::read_header(&mut Cursor::new(vec)).unwrap();
+    let (_, data) = header
+        .objects
+        .iter()
+        .find(|(guid, _)| *guid == super::FILE_PROPERTIES)
+        .unwrap();
+    decode_int_le_u64(&data[16..24])
+}
+
+#[test]
+fn asf_get_test() {
+    let tags = super::get(&mut Cursor::new(asf())).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("Title".to_string()),
+        artist: TagOption::Some("Artist".to_string()),
+        album: TagOption::Some("Album".to_string()),
+        genre: TagOption::Some("Genre".to_string()),
+        date: DateTime::from_iso_8601("2004").into(),
+        track_number: TagOption::Some(7),
+        disc_number: TagOption::Some(1),
+        disc_total: TagOption::Some(2),
+        front_cover: TagOption::Some(Image::JPEG(vec![0xFF, 0xD8, 0xFF, 0xE0])),
+        custom: TagOption::Some({
+            let mut map = BTreeMap::new();
+            map.insert("MusicBrainz/Track Id".to_string(), "1234".to_string());
+            map
+        }),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+}
+
+#[test]
+fn asf_set_test() {
+    let original = asf();
+
+    // too large for the extended content description
+    let mut cover = vec![0xFF, 0xD8];
+    cover.resize(70000, 0x11);
+    let new = Tags {
+        title: TagOption::Some("New Title ÆØÅ".to_string()),
+        album_artist: TagOption::Some("Album Artist".to_string()),
+        is_compilation: TagOption::Some(true),
+        genre: TagOption::Some("New Genre".to_string()),
+        front_cover: TagOption::Some(Image::JPEG(cover.clone())),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&original), &mut output, &new).unwrap();
+
+    assert_eq!(data(&output), b"the data object");
+    assert_eq!(file_size(&output), output.len() as u64);
+
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("New Title ÆØÅ".to_string()));
+    assert_eq!(tags.artist, TagOption::Some("Artist".to_string()));
+    assert_eq!(tags.album, TagOption::Some("Album".to_string()));
+    assert_eq!(
+        tags.album_artist,
+        TagOption::Some("Album Artist".to_string())
+    );
+    assert_eq!(tags.genre, TagOption::Some("New Genre".to_string()));
+    assert_eq!(tags.track_number, TagOption::Some(7));
+    assert_eq!(tags.is_compilation, TagOption::Some(true));
+    assert_eq!(tags.front_cover, TagOption::Some(Image::JPEG(cover)));
+
+    // the rating, unknown binary and stream specific attributes stay
+    let header = super::read_header(&mut Cursor::new(&output)).unwrap();
+    let (strings, attributes) = super::read_all(&header);
+    assert_eq!(strings[4], "Rating");
+    assert!(attributes.iter().any(|a| a.name == "WM/Unknown"));
+    assert!(attributes.iter().any(|a| a.stream == 1));
+    assert_eq!(
+        attributes.iter().filter(|a| a.name == "WM/Genre").count(),
+        2
+    );
+
+    let mut bare = Vec::new();
+    super::remove(&mut Cursor::new(&output), &mut bare).unwrap();
+    assert_eq!(data(&bare), b"the data object");
+    assert_eq!(file_size(&bare), bare.len() as u64);
+    assert_eq!(super::get(&mut Cursor::new(&bare)).unwrap(), Tags::none());
+}
+
+#[test]
+fn asf_track_total_test() {
+    let new = Tags {
+        track_number: TagOption::Some(3),
+        track_total: TagOption::Some(12),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(asf()), &mut output, &new).unwrap();
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.track_number, TagOption::Some(3));
+    assert_eq!(tags.track_total, TagOption::Some(12));
+
+    // and back to a plain number without the total
+    let new = Tags {
+        track_total: TagOption::None,
+        ..Tags::mixed()
+    };
+    let mut again = Vec::new();
+    super::set(&mut Cursor::new(&output), &mut again, &new).unwrap();
+    let tags = super::get(&mut Cursor::new(&again)).unwrap();
+    assert_eq!(tags.track_number, TagOption::Some(3));
+    assert_eq!(tags.track_total, TagOption::None);
+
+    let header = super::read_header(&mut Cursor::new(&again)).unwrap();
+    let (_, attributes) = super::read_all(&header);
+    let track = attributes
+        .iter()
+        .find(|a| a.name == "WM/TrackNumber")
+        .unwrap();
+    assert_eq!(track.kind, super::DWORD);
+}
+
+fn picture(kind: u8, data: &[u8]) -> super::Attribute {
+    let mut value = vec![kind];
+    value.append(&mut encode_int_le_u32(data.len() as u32));
+    value.append(&mut super::encode_string("image/png"));
+    value.append(&mut super::encode_string(""));
+    value.extend_from_slice(data);
+    new_attribute("WM/Picture", super::BYTES, value)
+}
+
+fn asf_with(attributes: &[super::Attribute]) -> Vec<u8> {
+    let objects = [
+        new_object(&super::FILE_PROPERTIES, &[0; 80]),
+        new_object(
+            &super::EXTENDED_CONTENT,
+            &super::render_extended_content(attributes),
+        ),
+    ];
+    let mut vec = super::HEADER.to_vec();
+    vec.append(&mut encode_int_le_u64(30 + objects.concat().len() as u64));
+    vec.append(&mut encode_int_le_u32(objects.len() as u32));
+    vec.extend_from_slice(&[1, 2]);
+    vec.append(&mut objects.concat());
+    vec
+}
+
+fn pictures(vec: &[u8]) -> Vec<Vec<u8>> {
+    let header = super::read_header(&mut Cursor::new(vec)).unwrap();
+    let (_, attributes) = super::read_all(&header);
+    attributes
+        .into_iter()
+        .filter(|a| a.name == "WM/Picture")
+        .map(|a| a.value)
+        .collect()
+}
+
+#[test]
+fn asf_pictures_test() {
+    let front = picture(super::FRONT_COVER, b"\x89PNG front");
+    let back = || picture(4, b"\x89PNG back");
+    let input = asf_with(&[front, back()]);
+    assert_eq!(
+        super::get(&mut Cursor::new(&input)).unwrap().front_cover,
+        TagOption::Some(Image::PNG(b"\x89PNG front".to_vec()))
+    );
+
+    // the back cover stays as it is when only the title changes
+    let new = Tags {
+        title: TagOption::Some("Title".to_string()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&input), &mut output, &new).unwrap();
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("Title".to_string()));
+    assert_eq!(
+        tags.front_cover,
+        TagOption::Some(Image::PNG(b"\x89PNG front".to_vec()))
+    );
+    let found = pictures(&output);
+    assert_eq!(found.len(), 2);
+    assert!(found.contains(&back().value));
+
+    // a back cover alone is not a front cover
+    let input = asf_with(&[back()]);
+    assert_eq!(
+        super::get(&mut Cursor::new(&input)).unwrap().front_cover,
+        TagOption::None
+    );
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&input), &mut output, &new).unwrap();
+    assert_eq!(pictures(&output), vec![back().value]);
+
+    // and removing the tags keeps it too
+    let mut bare = Vec::new();
+    super::remove(&mut Cursor::new(&output), &mut bare).unwrap();
+    assert_eq!(pictures(&bare), vec![back().value]);
+}
+
+#[test]
+fn asf_remove_test() {
+    let input = asf_with(&[
+        text("WM/AlbumTitle", "Album"),
+        text("MusicBrainz/Track Id", "1234"),
+        new_attribute("IsVBR", super::BOOL, encode_int_le_u32(1)),
+        new_attribute("WM/MediaClassPrimaryID", super::BYTES, vec![0; 16]),
+    ]);
+
+    let names = |vec: &[u8]| {
+        let header = super::read_header(&mut Cursor::new(vec)).unwrap();
+        let (_, attributes) = super::read_all(&header);
+        attributes.into_iter().map(|a| a.name).collect::<Vec<_>>()
+    };
+
+    // remove drops what set would write anew, and nothing else
+    let mut bare = Vec::new();
+    super::remove(&mut Cursor::new(&input), &mut bare).unwrap();
+    assert_eq!(names(&bare), vec!["IsVBR", "WM/MediaClassPrimaryID"]);
+    assert_eq!(super::get(&mut Cursor::new(&bare)).unwrap(), Tags::none());
+
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&input), &mut output, &Tags::none()).unwrap();
+    assert_eq!(names(&output), names(&bare));
+}
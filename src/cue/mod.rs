@@ -0,0 +1,346 @@
+use std::time::Duration;
+
+use crate::CueIndex;
+use crate::CueSheet;
+use crate::CueTrack;
+use crate::Error;
+use crate::FlacBlock;
+use crate::FlacCueIndex;
+use crate::FlacCueSheet;
+use crate::FlacCueTrack;
+use crate::FlacMetadata;
+use crate::StreamInfo;
+
+use crate::tools::encoding::decode_iso_8859_1;
+use crate::tools::tag_error;
+
+// cue sheet times count CD frames
+const FRAMES_PER_SECOND: u64 = 75;
+const CD_SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: u64 = 588;
+
+// units at some rate to a duration and back, rounded to the nearest
+fn to_duration(units: u64, rate: u64) -> Duration {
+    let nanos = (units as u128 * 1_000_000_000 + rate as u128 / 2) / rate as u128;
+    Duration::from_nanos(nanos as u64)
+}
+fn from_duration(time: Duration, rate: u64) -> u64 {
+    ((time.as_nanos() * rate as u128 + 500_000_000) / 1_000_000_000) as u64
+}
+
+// mm:ss:ff
+fn parse_time(text: &str) -> Option<Duration> {
+    let parts: Vec<u64> = text
+        .split(':')
+        .map(|x| x.parse::<u64>().ok())
+        .collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [m, s, f] if *s < 60 && *f < FRAMES_PER_SECOND => Some(to_duration(
+            (m * 60 + s) * FRAMES_PER_SECOND + f,
+            FRAMES_PER_SECOND,
+        )),
+        _ => None,
+    }
+}
+fn render_time(time: Duration) -> String {
+    let frames = from_duration(time, FRAMES_PER_SECOND);
+    format!(
+        "{:02}:{:02}:{:02}",
+        frames / FRAMES_PER_SECOND / 60,
+        frames / FRAMES_PER_SECOND % 60,
+        frames % FRAMES_PER_SECOND
+    )
+}
+
+// the first argument of a command, quoted or not, and whatever follows it
+fn argument(text: &str) -> (String, &str) {
+    let text = text.trim();
+    if let Some(rest) = text.strip_prefix('"') {
+        return match rest.find('"') {
+            Some(end) => (rest[..end].to_string(), rest[end + 1..].trim()),
+            None => (rest.to_string(), ""),
+        };
+    }
+    match text.find(char::is_whitespace) {
+        Some(end) => (text[..end].to_string(), text[end..].trim()),
+        None => (text.to_string(), ""),
+    }
+}
+
+// .cue files come in UTF-8 (often with a BOM) or some 8-bit code page
+pub fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(x) => x.to_string(),
+        Err(_) => decode_iso_8859_1(bytes),
+    }
+}
+
+pub fn parse(text: &str) -> Result<CueSheet, Error> {
+    let mut sheet = CueSheet::default();
+
+    for line in text.lines() {
+        let (command, rest) = argument(line);
+        let (value, rest) = argument(rest);
+
+        let command = command.to_uppercase();
+        if command == "TRACK" {
+            sheet.tracks.push(CueTrack {
+                number: value
+                    .parse()
+                    .map_err(|_| tag_error("Invalid track number in cue sheet"))?,
+                is_audio: rest.eq_ignore_ascii_case("AUDIO"),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        // everything after the first TRACK belongs to a track
+        match (command.as_str(), sheet.tracks.last_mut()) {
+            ("INDEX", Some(track)) => track.indices.push(CueIndex {
+                number: value
+                    .parse()
+                    .map_err(|_| tag_error("Invalid index number in cue sheet"))?,
+                time: parse_time(rest)
+                    .ok_or_else(|| tag_error("Invalid index time in cue sheet"))?,
+            }),
+            ("TITLE", Some(track)) => track.title = Some(value),
+            ("PERFORMER", Some(track)) => track.performer = Some(value),
+            ("ISRC", Some(track)) => track.isrc = Some(value),
+            ("FLAGS", Some(track)) => {
+                let flags = line.to_uppercase();
+                track.pre_emphasis = flags.split_whitespace().any(|x| x == "PRE");
+            }
+            ("TITLE", None) => sheet.title = Some(value),
+            ("PERFORMER", None) => sheet.performer = Some(value),
+            ("CATALOG", None) => sheet.catalog = Some(value),
+            // only the first file, sheets for several files are rare
+            ("FILE", _) if sheet.file.is_none() => sheet.file = Some(value),
+            // REM, SONGWRITER, PREGAP and the like
+            _ => (),
+        }
+    }
+
+    if sheet.tracks.is_empty() {
+        return Err(tag_error("No tracks found in cue sheet"));
+    }
+    Ok(sheet)
+}
+
+pub fn render(sheet: &CueSheet) -> String {
+    let mut lines = Vec::new();
+
+    if let Some(ref x) = sheet.catalog {
+        lines.push(format!("CATALOG {}", x));
+    }
+    if let Some(ref x) = sheet.performer {
+        lines.push(format!("PERFORMER \"{}\"", x));
+    }
+    if let Some(ref x) = sheet.title {
+        lines.push(format!("TITLE \"{}\"", x));
+    }
+    if let Some(ref x) = sheet.file {
+        lines.push(format!("FILE \"{}\" WAVE", x));
+    }
+
+    for track in sheet.tracks.iter() {
+        let mode = if track.is_audio {
+            "AUDIO"
+        } else {
+            "MODE1/2352"
+        };
+        lines.push(format!("  TRACK {:02} {}", track.number, mode));
+        if let Some(ref x) = track.title {
+            lines.push(format!("    TITLE \"{}\"", x));
+        }
+        if let Some(ref x) = track.performer {
+            lines.push(format!("    PERFORMER \"{}\"", x));
+        }
+        if let Some(ref x) = track.isrc {
+            lines.push(format!("    ISRC {}", x));
+        }
+        if track.pre_emphasis {
+            lines.push("    FLAGS PRE".to_string());
+        }
+        for index in track.indices.iter() {
+            lines.push(format!(
+                "    INDEX {:02} {}",
+                index.number,
+                render_time(index.time)
+            ));
+        }
+    }
+
+    lines.push(String::new());
+    lines.join("\r\n")
+}
+
+fn none_if_empty(text: &str) -> Option<String> {
+    match text {
+        "" => None,
+        x => Some(x.to_string()),
+    }
+}
+
+// offsets in a CUESHEET block are samples, the track's plus the index's
+pub fn from_flac(cue: &FlacCueSheet, sample_rate: u32) -> Result<CueSheet, Error> {
+    if sample_rate == 0 {
+        return Err(tag_error("Unknown sample rate for the FLAC cue sheet"));
+    }
+    let rate = sample_rate as u64;
+
+    let tracks = cue
+        .tracks
+        .iter()
+        // the lead-out has no indices
+        .filter(|x| !x.indices.is_empty())
+        .map(|track| CueTrack {
+            number: track.number,
+            isrc: none_if_empty(&track.isrc),
+            is_audio: track.is_audio,
+            pre_emphasis: track.pre_emphasis,
+            indices: track
+                .indices
+                .iter()
+                .map(|index| CueIndex {
+                    number: index.number,
+                    time: to_duration(track.offset + index.offset, rate),
+                })
+                .collect(),
+            ..Default::default()
+        })
+        .collect();
+
+    Ok(CueSheet {
+        catalog: none_if_empty(&cue.catalog),
+        tracks,
+        ..Default::default()
+    })
+}
+
+// a CUESHEET block for a stream, with the lead-out at its end; it's a CD
+// cue sheet if the stream and every index fit onto a CD
+pub fn to_flac(sheet: &CueSheet, info: &StreamInfo) -> Result<FlacCueSheet, Error> {
+    if info.sample_rate == 0 {
+        return Err(tag_error("Unknown sample rate for the FLAC cue sheet"));
+    }
+    let rate = info.sample_rate as u64;
+
+    let mut tracks = Vec::new();
+    for track in sheet.tracks.iter() {
+        let start = match track.indices.iter().map(|x| x.time).min() {
+            Some(x) => from_duration(x, rate),
+            None => return Err(tag_error("Cue sheet track without an index")),
+        };
+        tracks.push(FlacCueTrack {
+            offset: start,
+            number: track.number,
+            isrc: track.isrc.clone().unwrap_or_default(),
+            is_audio: track.is_audio,
+            pre_emphasis: track.pre_emphasis,
+            indices: track
+                .indices
+                .iter()
+                .map(|index| FlacCueIndex {
+                    offset: from_duration(index.time, rate) - start,
+                    number: index.number,
+                })
+                .collect(),
+        });
+    }
+
+    let is_cd = info.sample_rate == CD_SAMPLE_RATE
+        && tracks.iter().all(|t| {
+            t.offset % SAMPLES_PER_FRAME == 0
+                && t.indices.iter().all(|i| i.offset % SAMPLES_PER_FRAME == 0)
+        });
+    tracks.push(FlacCueTrack {
+        offset: info.total_samples,
+        number: if is_cd { 170 } else { 255 },
+        is_audio: true,
+        ..Default::default()
+    });
+
+    Ok(FlacCueSheet {
+        catalog: sheet.catalog.clone().unwrap_or_default(),
+        // the usual two seconds before the first track on a CD
+        lead_in: if is_cd { 2 * CD_SAMPLE_RATE as u64 } else { 0 },
+        is_cd,
+        tracks,
+    })
+}
+
+// the CUESHEET= comment some rippers write, with titles and all
+fn comment(metadata: &FlacMetadata) -> Option<(usize, usize)> {
+    metadata
+        .blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, x)| match x {
+            FlacBlock::VorbisComment(x) => Some((i, x)),
+            _ => None,
+        })
+        .find_map(|(i, x)| {
+            x.comments
+                .iter()
+                .position(|c| {
+                    c.get(..9)
+                        .is_some_and(|x| x.eq_ignore_ascii_case("CUESHEET="))
+                })
+                .map(|j| (i, j))
+        })
+}
+
+// the CUESHEET comment if there's one, or else the CUESHEET block
+pub fn get_flac(metadata: &FlacMetadata) -> Result<CueSheet, Error> {
+    if let Some((i, j)) = comment(metadata) {
+        if let FlacBlock::VorbisComment(ref x) = metadata.blocks[i] {
+            return parse(&x.comments[j][9..]);
+        }
+    }
+
+    let sample_rate = metadata.stream_info().map_or(0, |x| x.sample_rate);
+    match metadata.blocks.iter().find_map(|x| match x {
+        FlacBlock::CueSheet(x) => Some(x),
+        _ => None,
+    }) {
+        Some(x) => from_flac(x, sample_rate),
+        None => Err(tag_error("No cue sheet found")),
+    }
+}
+
+// replace (or add) the CUESHEET block, and keep a CUESHEET comment in sync
+pub fn set_flac(metadata: &mut FlacMetadata, sheet: &CueSheet) -> Result<(), Error> {
+    let info = match metadata.stream_info() {
+        Some(x) => x,
+        None => return Err(tag_error("FLAC metadata has to start with STREAMINFO")),
+    };
+    let block = FlacBlock::CueSheet(to_flac(sheet, info)?);
+
+    let position = metadata
+        .blocks
+        .iter()
+        .position(|x| matches!(x, FlacBlock::CueSheet(_)));
+    match position {
+        Some(i) => metadata.blocks[i] = block,
+        // in front of any padding at the end
+        None => {
+            let i = metadata
+                .blocks
+                .iter()
+                .rposition(|x| !matches!(x, FlacBlock::Padding(_)))
+                .map_or(metadata.blocks.len(), |i| i + 1);
+            metadata.blocks.insert(i, block);
+        }
+    }
+
+    if let Some((i, j)) = comment(metadata) {
+        if let FlacBlock::VorbisComment(ref mut x) = metadata.blocks[i] {
+            x.comments[j] = format!("CUESHEET={}", render(sheet));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
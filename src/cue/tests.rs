@@ -0,0 +1,130 @@
+use crate::CueIndex;
+use crate::CueSheet;
+use crate::CueTrack;
+use crate::FlacBlock;
+use crate::FlacMetadata;
+use crate::StreamInfo;
+use crate::VorbisComment;
+use std::time::Duration;
+
+const CUE: &str = "\u{FEFF}REM GENRE Rock\r
+CATALOG 0123456789012\r
+PERFORMER \"The Band\"\r
+TITLE \"The Album\"\r
+FILE \"album.flac\" WAVE\r
+  TRACK 01 AUDIO\r
+    TITLE \"First\"\r
+    ISRC USRC17607839\r
+    FLAGS DCP PRE\r
+    INDEX 01 00:00:00\r
+  TRACK 02 AUDIO\r
+    TITLE \"Second\"\r
+    PERFORMER Guest\r
+    INDEX 00 03:58:70\r
+    INDEX 01 04:00:00\r
+";
+
+fn sheet() -> CueSheet {
+    CueSheet {
+        catalog: Some("0123456789012".to_string()),
+        title: Some("The Album".to_string()),
+        performer: Some("The Band".to_string()),
+        file: Some("album.flac".to_string()),
+        tracks: vec![
+            CueTrack {
+                number: 1,
+                title: Some("First".to_string()),
+                isrc: Some("USRC17607839".to_string()),
+                is_audio: true,
+                pre_emphasis: true,
+                indices: vec![CueIndex {
+                    number: 1,
+                    time: Duration::from_secs(0),
+                }],
+                ..Default::default()
+            },
+            CueTrack {
+                number: 2,
+                title: Some("Second".to_string()),
+                performer: Some("Guest".to_string()),
+                is_audio: true,
+                indices: vec![
+                    CueIndex {
+                        number: 0,
+                        time: Duration::from_nanos(238_933_333_333),
+                    },
+                    CueIndex {
+                        number: 1,
+                        time: Duration::from_secs(240),
+                    },
+                ],
+                ..Default::default()
+            },
+        ],
+    }
+}
+
+#[test]
+fn cue_parse_test() {
+    let text = super::decode(CUE.as_bytes());
+    assert_eq!(super::parse(&text).unwrap(), sheet());
+
+    // and back, times included
+    let rendered = super::render(&sheet());
+    assert!(rendered.contains("    INDEX 00 03:58:70\r\n"));
+    assert_eq!(super::parse(&rendered).unwrap(), sheet());
+
+    // not UTF-8
+    let text = super::decode(b"TITLE \"Caf\xE9\"\nTRACK 1 AUDIO\nINDEX 01 00:00:00");
+    assert_eq!(super::parse(&text).unwrap().title, Some("Café".to_string()));
+
+    assert!(super::parse("TITLE \"No Tracks\"").is_err());
+    assert!(super::parse("TRACK 01 AUDIO\nINDEX 01 00:61:00").is_err());
+}
+
+#[test]
+fn cue_flac_test() {
+    let info = StreamInfo {
+        sample_rate: 44100,
+        channels: 2,
+        bits_per_sample: 16,
+        total_samples: 44100 * 300,
+        ..Default::default()
+    };
+
+    let flac = super::to_flac(&sheet(), &info).unwrap();
+    assert!(flac.is_cd);
+    assert_eq!(flac.lead_in, 88200);
+    assert_eq!(flac.tracks.len(), 3);
+    assert_eq!(flac.tracks[1].offset, (238 * 75 + 70) * 588);
+    // two seconds less 70 frames after index 0
+    assert_eq!(flac.tracks[1].indices[1].offset, 80 * 588);
+    assert_eq!(flac.tracks[2].number, 170);
+    assert_eq!(flac.tracks[2].offset, 44100 * 300);
+
+    // titles and performers only live in the text
+    let back = super::from_flac(&flac, 44100).unwrap();
+    assert_eq!(back.catalog, sheet().catalog);
+    assert_eq!(back.tracks[1].indices, sheet().tracks[1].indices);
+    assert_eq!(back.tracks[0].isrc, sheet().tracks[0].isrc);
+    assert_eq!(back.tracks[1].title, None);
+
+    // a comment, if there's one, is kept up to date
+    let mut metadata = FlacMetadata {
+        blocks: vec![
+            FlacBlock::StreamInfo(info),
+            FlacBlock::VorbisComment(VorbisComment {
+                vendor: String::new(),
+                comments: vec!["CUESHEET=TRACK 01 AUDIO\nINDEX 01 00:00:00".to_string()],
+            }),
+            FlacBlock::Padding(100),
+        ],
+    };
+    assert_eq!(super::get_flac(&metadata).unwrap().tracks.len(), 1);
+    super::set_flac(&mut metadata, &sheet()).unwrap();
+    assert_eq!(super::get_flac(&metadata).unwrap(), sheet());
+    assert!(matches!(metadata.blocks[2], FlacBlock::CueSheet(_)));
+
+    metadata.blocks.remove(1);
+    assert_eq!(super::get_flac(&metadata).unwrap(), back);
+}
@@ -0,0 +1,201 @@
+use std::io::prelude::*;
+use std::io::Cursor;
+
+use crate::Error;
+use crate::Properties;
+use crate::TagOption;
+use crate::Tags;
+
+use crate::id3v2;
+use crate::iff;
+use crate::iff::read_chunk;
+use crate::iff::Form;
+use crate::iff::Piece;
+use crate::iff::DSDIFF;
+use crate::tools::decode_int_be_u32;
+use crate::tools::encode_int_be_u32;
+use crate::tools::encoding::decode_iso_8859_1;
+use crate::tools::tag_error;
+use crate::tools::tags::delta;
+use crate::tools::tags::fill;
+
+// walk the chunks of a FRM8/DSD (DSDIFF) file
+fn read_form<T: Read + Seek>(input: &mut T) -> Result<Form, Error> {
+    let mut form = DSDIFF.read_form(input)?;
+
+    if &form.id != b"FRM8" || &form.kind != b"DSD " {
+        return Err(tag_error("Not a DSDIFF file"));
+    }
+
+    DSDIFF.read_chunks(input, &mut form, |_, _, _| Ok(()))?;
+    Ok(form)
+}
+
+// the chunks inside a container, starting at pos
+fn collect_chunks(vec: &[u8], pos: usize) -> Vec<([u8; 4], Vec<u8>)> {
+    DSDIFF.collect(vec, pos)
+}
+
+// DIAR and DITI hold a count, then that many characters
+fn decode_text(value: &[u8]) -> String {
+    if value.len() < 4 {
+        return String::new();
+    }
+    let end = std::cmp::min(4 + decode_int_be_u32(&value[0..4]) as usize, value.len());
+    match std::str::from_utf8(&value[4..end]) {
+        Ok(x) => x.to_string(),
+        Err(_) => decode_iso_8859_1(&value[4..end]),
+    }
+}
+
+pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    let form = read_form(input)?;
+
+    let mut tags = match form.chunks.iter().find(|c| c.is_id3()) {
+        Some(chunk) => match id3v2::get(&mut Cursor::new(read_chunk(input, chunk)?)) {
+            Ok(t) => t,
+            Err(Error::TagError(_)) => Tags::none(),
+            Err(e) => return Err(e),
+        },
+        None => Tags::none(),
+    };
+
+    for chunk in form.chunks.iter().filter(|c| &c.id == b"DIIN") {
+        for (id, value) in collect_chunks(&read_chunk(input, chunk)?, 0) {
+            match &id {
+                b"DITI" => fill(&mut tags.title, TagOption::Some(decode_text(&value))),
+                b"DIAR" => fill(&mut tags.artist, TagOption::Some(decode_text(&value))),
+                _ => (),
+            }
+        }
+    }
+
+    Ok(tags)
+}
+
+fn new_text(id: &[u8], text: &TagOption<String>) -> Vec<u8> {
+    match text {
+        TagOption::Some(ref t) if t.as_str() != "" => {
+            let mut data = encode_int_be_u32(t.len() as u32);
+            data.extend_from_slice(t.as_bytes());
+            DSDIFF.new_chunk(id, &data)
+        }
+        _ => Vec::new(),
+    }
+}
+
+pub fn set<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    new: &Tags,
+) -> Result<(), Error> {
+    let form = read_form(input)?;
+    let tags = delta(&get(input)?, new);
+
+    // the ID3 chunk holds everything, the DIIN chunk is only kept up to date
+    let diin = match form.chunks.iter().find(|c| &c.id == b"DIIN") {
+        Some(chunk) => {
+            let mut vec = Vec::new();
+            for (id, value) in collect_chunks(&read_chunk(input, chunk)?, 0) {
+                match &id {
+                    b"DITI" => vec.append(&mut new_text(b"DITI", &tags.title)),
+                    b"DIAR" => vec.append(&mut new_text(b"DIAR", &tags.artist)),
+                    _ => vec.append(&mut DSDIFF.new_chunk(&id, &value)),
+                }
+            }
+            Some(DSDIFF.new_chunk(b"DIIN", &vec))
+        }
+        None => None,
+    };
+
+    let (id, old_id3) = match form.chunks.iter().find(|c| c.is_id3()) {
+        Some(chunk) => (chunk.id, read_chunk(input, chunk)?),
+        None => (*b"ID3 ", Vec::new()),
+    };
+    let mut vec = Vec::new();
+    id3v2::set(&mut Cursor::new(old_id3), &mut vec, &tags)?;
+    let id3 = DSDIFF.new_chunk(&id, &vec);
+
+    rewrite(input, output, &form, diin, Some(id3))
+}
+
+// copy the file without the ID3 chunk; DIIN also has markers, so it stays
+pub fn remove<R: Read + Seek, W: Write>(input: &mut R, output: &mut W) -> Result<(), Error> {
+    let form = read_form(input)?;
+    rewrite(input, output, &form, None, Some(Vec::new()))
+}
+
+// copy the file, replacing the DIIN and ID3 chunks where they are given;
+// a missing ID3 chunk is appended, empty ones are dropped
+fn rewrite<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    form: &Form,
+    mut diin: Option<Vec<u8>>,
+    mut id3: Option<Vec<u8>>,
+) -> Result<(), Error> {
+    let mut pieces = iff::pieces(form, |chunk| {
+        let replacement = if chunk.is_id3() {
+            id3.as_mut()
+        } else if &chunk.id == b"DIIN" {
+            diin.as_mut()
+        } else {
+            None
+        };
+        // duplicates of the chunk are dropped once it's written
+        Ok(replacement.map(std::mem::take))
+    })?;
+    if let Some(vec) = id3 {
+        pieces.push(Piece::Data(vec));
+    }
+
+    output.write_all(&DSDIFF.form_header(form, iff::size(&pieces))?)?;
+    iff::write_pieces(input, output, form, pieces)
+}
+
+// audio properties from the PROP chunk and the size of the sound data
+pub fn get_properties<T: Read + Seek>(input: &mut T) -> Result<Properties, Error> {
+    let form = read_form(input)?;
+
+    let prop = match form.chunks.iter().find(|c| &c.id == b"PROP") {
+        Some(chunk) => read_chunk(input, chunk)?,
+        None => return Err(tag_error("DSDIFF PROP chunk not found")),
+    };
+    if prop.len() < 4 || &prop[0..4] != b"SND " {
+        return Err(tag_error("DSDIFF PROP chunk not found"));
+    }
+
+    let mut sample_rate = 0;
+    let mut channels = 0;
+    let mut compressed = false;
+    for (id, value) in collect_chunks(&prop, 4) {
+        match &id {
+            b"FS  " if value.len() >= 4 => sample_rate = decode_int_be_u32(&value[0..4]),
+            b"CHNL" if value.len() >= 2 => channels = decode_int_be_u32(&value[0..2]) as u8,
+            b"CMPR" if value.len() >= 4 => compressed = &value[0..4] != b"DSD ",
+            _ => (),
+        }
+    }
+
+    // DST compressed audio doesn't tell its length as easily
+    let (samples, audio_bytes) = match form.chunks.iter().find(|c| &c.id == b"DSD ") {
+        Some(chunk) if !compressed && channels > 0 => {
+            (Some(chunk.size * 8 / channels as u64), chunk.size)
+        }
+        _ => match form.chunks.iter().find(|c| &c.id == b"DST ") {
+            Some(chunk) => (None, chunk.size),
+            None => (None, 0),
+        },
+    };
+
+    Ok(Properties::from_samples(
+        samples,
+        sample_rate,
+        channels,
+        Some(1),
+        audio_bytes,
+    ))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,105 @@
+use crate::TagOption;
+use crate::Tags;
+use std::io::Cursor;
+use std::time::Duration;
+
+use crate::iff::DSDIFF;
+
+use crate::tools::decode_int_be_u64;
+use crate::tools::encode_int_be_u32;
+use crate::tools::encode_int_be_u64;
+
+fn text(id: &[u8], text: &[u8]) -> Vec<u8> {
+    DSDIFF.new_chunk(
+        id,
+        &[&encode_int_be_u32(text.len() as u32)[..], text].concat(),
+    )
+}
+
+// stereo DSD64 with one second of audio, a title and an edit marker
+fn dff() -> Vec<u8> {
+    let mut chunks = DSDIFF.new_chunk(b"FVER", &[1, 5, 0, 0]);
+    chunks.append(
+        &mut DSDIFF.new_chunk(
+            b"PROP",
+            &[
+                &b"SND "[..],
+                &DSDIFF.new_chunk(b"FS  ", &encode_int_be_u32(2822400)),
+                &DSDIFF.new_chunk(b"CHNL", b"\x00\x02SLFTSRGT"),
+                &DSDIFF.new_chunk(b"CMPR", b"DSD \x0enot compressed\x00"),
+            ]
+            .concat(),
+        ),
+    );
+    chunks.append(&mut DSDIFF.new_chunk(b"DSD ", &vec![0x69; 705600]));
+    chunks.append(
+        &mut DSDIFF.new_chunk(
+            b"DIIN",
+            &[
+                &DSDIFF.new_chunk(b"EMID", b"marker")[..],
+                &text(b"DITI", b"Old Title"),
+            ]
+            .concat(),
+        ),
+    );
+
+    let mut vec = b"FRM8".to_vec();
+    vec.extend_from_slice(&encode_int_be_u64(chunks.len() as u64 + 4));
+    vec.extend_from_slice(b"DSD ");
+    vec.append(&mut chunks);
+    vec
+}
+
+#[test]
+fn dff_set_test() {
+    let original = dff();
+    let tags = super::get(&mut Cursor::new(&original)).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("Old Title".to_string()),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+
+    let new = Tags {
+        title: TagOption::Some("New Title".to_string()),
+        artist: TagOption::Some("Artist".to_string()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&original), &mut output, &new).unwrap();
+
+    assert_eq!(
+        decode_int_be_u64(&output[4..12]) as usize,
+        output.len() - 12
+    );
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("New Title".to_string()));
+    assert_eq!(tags.artist, TagOption::Some("Artist".to_string()));
+
+    // DITI was updated, and the marker kept
+    let mut cursor = Cursor::new(&output);
+    let form = super::read_form(&mut cursor).unwrap();
+    let chunk = form.chunks.iter().find(|c| &c.id == b"DIIN").unwrap();
+    let diin = super::collect_chunks(&super::read_chunk(&mut cursor, chunk).unwrap(), 0);
+    assert_eq!(diin[0], (*b"EMID", b"marker".to_vec()));
+    assert_eq!(super::decode_text(&diin[1].1), "New Title");
+    assert!(form.chunks.iter().any(|c| c.is_id3()));
+
+    let mut bare = Vec::new();
+    super::remove(&mut Cursor::new(&output), &mut bare).unwrap();
+    assert_eq!(decode_int_be_u64(&bare[4..12]) as usize, bare.len() - 12);
+    let tags = super::get(&mut Cursor::new(&bare)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("New Title".to_string()));
+    assert_eq!(tags.artist, TagOption::None);
+}
+
+#[test]
+fn dff_properties_test() {
+    let properties = super::get_properties(&mut Cursor::new(dff())).unwrap();
+    assert_eq!(properties.duration, Some(Duration::from_secs(1)));
+    assert_eq!(properties.sample_rate, Some(2822400));
+    assert_eq!(properties.channels, Some(2));
+    assert_eq!(properties.bitrate, Some(5644));
+
+    assert!(super::get_properties(&mut Cursor::new(b"FORM".repeat(8))).is_err());
+}
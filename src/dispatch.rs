@@ -1,23 +1,76 @@
 use std;
 use std::fs::File;
+use std::io::prelude::*;
 use std::io::BufWriter;
+use std::io::SeekFrom;
 use std::path::Path;
 
+use crate::tools::file;
 use crate::tools::tag_error;
+use crate::tools::tags::merge;
+use crate::CueSheet;
 use crate::Error;
+use crate::FlacMetadata;
+use crate::Id3v2Restrictions;
 use crate::Image;
+use crate::Properties;
+use crate::TagTypes;
 use crate::Tags;
+use crate::WriteOptions;
 
+use crate::adts;
+use crate::aiff;
+use crate::ape;
+use crate::asf;
+use crate::cue;
+use crate::dff;
+use crate::dsf;
 use crate::flac;
 use crate::id3v1;
 use crate::id3v2;
+use crate::lyrics3;
 use crate::m4a;
+use crate::matroska;
+use crate::monkeys_audio;
+use crate::mpeg;
+use crate::musepack;
+use crate::ogg;
+use crate::wav;
+use crate::wavpack;
+
+// map file extensions onto the formats we handle
+fn format(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "mp3" => Some("mp3"),
+        // other MPEG audio streams, tagged just like mp3
+        "aac" => Some("aac"),
+        "mp2" | "mp1" => Some("mp2"),
+        "flac" => Some("flac"),
+        // Vorbis comments in Ogg, for FLAC, Speex and Vorbis itself
+        "ogg" | "oga" | "spx" => Some("ogg"),
+        "wav" => Some("wav"),
+        "aiff" | "aif" | "aifc" => Some("aiff"),
+        "dsf" => Some("dsf"),
+        "dff" => Some("dff"),
+        // formats with APEv2 as their native tag
+        "wv" => Some("wv"),
+        "ape" => Some("ape"),
+        "mpc" | "mp+" | "mpp" => Some("mpc"),
+        // everything based on the MPEG-4 container
+        "m4a" | "m4b" | "m4r" | "m4p" | "mp4" | "m4v" | "3gp" => Some("m4a"),
+        // QuickTime movies, which may carry keys style metadata
+        "mov" | "qt" => Some("m4a"),
+        "mka" | "mkv" | "webm" => Some("mka"),
+        "wma" | "wmv" | "asf" => Some("asf"),
+        _ => None,
+    }
+}
 
 macro_rules! unsupported {
     ($path:ident) => {{
-        match $path.extension().unwrap_or_default().to_str() {
-            Some("mp3") | Some("flac") | Some("m4a") => (),
-            None | Some(_) => return Err(tag_error("Unsupported file format")),
+        if format($path).is_none() {
+            return Err(tag_error("Unsupported file format"));
         }
     }};
 }
@@ -28,21 +81,123 @@ pub fn get_tags<P: AsRef<Path>>(path: P) -> Result<Tags, Error> {
 
     let mut file = File::open(path)?;
 
-    match path.extension().unwrap_or_default().to_str() {
-        Some("mp3") => {
-            // try id3v2 first; try falling back on id3v1
+    match format(path) {
+        Some("mp3") | Some("aac") | Some("mp2") => {
+            // try id3v2 first; try falling back on APE, then id3v1,
+            // with lyrics from a Lyrics3 tag for any of them
+            let lyrics3 = lyrics3::get(&mut file)?;
             match id3v2::get(&mut file) {
-                Ok(t) => Ok(t),
+                Ok(t) => Ok(merge(t, lyrics3)),
                 Err(Error::IOError(x)) => Err(Error::IOError(x)),
-                Err(Error::TagError(_)) => id3v1::get(&mut file),
+                Err(Error::TagError(_)) => match ape::get(&mut file) {
+                    Ok(t) => Ok(merge(t, lyrics3)),
+                    Err(Error::IOError(x)) => Err(Error::IOError(x)),
+                    Err(Error::TagError(_)) => {
+                        id3v1::get(&mut file).map(|t| lyrics3::extend(t, lyrics3))
+                    }
+                },
             }
         }
         Some("flac") => flac::get(&mut file),
+        Some("ogg") => ogg::get(&mut file),
         Some("m4a") => m4a::get(&mut file),
+        Some("mka") => matroska::get(&mut file),
+        Some("asf") => asf::get(&mut file),
+        Some("wav") => wav::get(&mut file),
+        Some("aiff") => aiff::get(&mut file),
+        Some("dsf") => dsf::get(&mut file),
+        Some("dff") => dff::get(&mut file),
+        Some("wv") | Some("ape") | Some("mpc") => {
+            let lyrics3 = lyrics3::get(&mut file)?;
+            match ape::get(&mut file) {
+                Ok(t) => Ok(merge(t, lyrics3)),
+                Err(Error::IOError(x)) => Err(Error::IOError(x)),
+                Err(Error::TagError(_)) => {
+                    id3v1::get(&mut file).map(|t| lyrics3::extend(t, lyrics3))
+                }
+            }
+        }
         None | Some(_) => Err(tag_error("Unsupported file format")),
     }
 }
 
+pub fn get_properties<P: AsRef<Path>>(path: P) -> Result<Properties, Error> {
+    let path = path.as_ref();
+    unsupported!(path);
+
+    let mut file = File::open(path)?;
+
+    match format(path) {
+        Some("aac") => adts::get_properties(&mut file),
+        Some("mp2") => mpeg::get_properties(&mut file),
+        Some("wv") => wavpack::get_properties(&mut file),
+        Some("ape") => monkeys_audio::get_properties(&mut file),
+        Some("mpc") => musepack::get_properties(&mut file),
+        Some("dsf") => dsf::get_properties(&mut file),
+        Some("dff") => dff::get_properties(&mut file),
+        None | Some(_) => Err(tag_error("Audio properties not supported for this format")),
+    }
+}
+
+// every metadata block of a FLAC file
+pub fn get_flac_metadata<P: AsRef<Path>>(path: P) -> Result<FlacMetadata, Error> {
+    let path = path.as_ref();
+    if format(path) != Some("flac") {
+        return Err(tag_error("Not a FLAC file"));
+    }
+    FlacMetadata::read(&mut File::open(path)?)
+}
+
+// replace all metadata blocks of a FLAC file, the audio stays as it is
+pub fn set_flac_metadata<P: AsRef<Path>>(path: P, metadata: &FlacMetadata) -> Result<(), Error> {
+    let path = path.as_ref();
+    if format(path) != Some("flac") {
+        return Err(tag_error("Not a FLAC file"));
+    }
+
+    let (tmp_path, tmp_file) = file::create_temp(path)?;
+
+    let result = File::open(path)
+        .map_err(Error::from)
+        .and_then(|mut file| {
+            let mut tmp_file = BufWriter::new(tmp_file);
+            metadata.write(&mut file, &mut tmp_file)?;
+            into_inner(tmp_file)
+        })
+        .and_then(|tmp_file| {
+            file::replace(tmp_file, &tmp_path, path, &WriteOptions::default()).map_err(Error::from)
+        });
+
+    if result.is_err() {
+        std::fs::remove_file(&tmp_path).ok();
+    }
+    result
+}
+
+fn is_cue(path: &Path) -> bool {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some(x) => x.eq_ignore_ascii_case("cue"),
+        None => false,
+    }
+}
+
+// the cue sheet of a .cue file, or the one embedded in a FLAC file
+pub fn get_cue_sheet<P: AsRef<Path>>(path: P) -> Result<CueSheet, Error> {
+    let path = path.as_ref();
+    if is_cue(path) {
+        return cue::parse(&cue::decode(&std::fs::read(path)?));
+    }
+    cue::get_flac(&get_flac_metadata(path)?)
+}
+
+// embed a cue sheet into a FLAC file
+pub fn set_cue_sheet<P: AsRef<Path>>(path: P, sheet: &CueSheet) -> Result<(), Error> {
+    let path = path.as_ref();
+    let mut metadata = get_flac_metadata(path)?;
+    cue::set_flac(&mut metadata, sheet)?;
+    set_flac_metadata(path, &metadata)
+}
+
 pub fn get_front_cover<P: AsRef<Path>>(path: P) -> Result<Image, Error> {
     let tags = get_tags(path)?;
     match tags.front_cover {
@@ -51,81 +206,239 @@ pub fn get_front_cover<P: AsRef<Path>>(path: P) -> Result<Image, Error> {
     }
 }
 
+// the restrictions an ID3v2 tag at the start of the file declares,
+// None if it has none
+pub fn get_id3v2_restrictions<P: AsRef<Path>>(path: P) -> Result<Option<Id3v2Restrictions>, Error> {
+    let path = path.as_ref();
+    unsupported!(path);
+
+    match format(path) {
+        Some("mp3") | Some("aac") | Some("mp2") | Some("flac") => {
+            id3v2::restrictions(&mut File::open(path)?)
+        }
+        None | Some(_) => Err(tag_error("No leading ID3v2 tag in this format")),
+    }
+}
+
+// whether the frames of an ID3v2 tag at the start of the file match its CRC,
+// None if it has no CRC
+pub fn check_id3v2_crc<P: AsRef<Path>>(path: P) -> Result<Option<bool>, Error> {
+    let path = path.as_ref();
+    unsupported!(path);
+
+    match format(path) {
+        Some("mp3") | Some("aac") | Some("mp2") | Some("flac") => {
+            id3v2::crc_matches(&mut File::open(path)?)
+        }
+        None | Some(_) => Err(tag_error("No leading ID3v2 tag in this format")),
+    }
+}
+
 pub fn set_tags<P: AsRef<Path>>(path: P, tags: &Tags) -> Result<(), Error> {
+    set_tags_with_options(path, tags, &WriteOptions::default())
+}
+
+pub fn set_tags_with_options<P: AsRef<Path>>(
+    path: P,
+    tags: &Tags,
+    options: &WriteOptions,
+) -> Result<(), Error> {
     // check path validity
     let path = path.as_ref();
     unsupported!(path);
 
-    // whyyyy
-    let tmp_path = {
-        let mut p = path.to_path_buf();
-        let mut e = std::ffi::OsString::from(p.extension().unwrap_or_default());
-        e.push("tmp");
-        p.set_extension(e);
-        p
-    };
+    // write into a fresh temporary file first, so that the original
+    // stays intact if anything goes wrong along the way
+    let (tmp_path, tmp_file) = file::create_temp(path)?;
+
+    let result = write_tags(path, tmp_file, tags, options).and_then(|tmp_file| {
+        // replace original file
+        file::replace(tmp_file, &tmp_path, path, options).map_err(Error::from)
+    });
+
+    if result.is_err() {
+        // on failure, delete temporary file
+        std::fs::remove_file(&tmp_path).ok();
+    }
+    result
+}
+
+pub fn copy_with_tags<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    tags: &Tags,
+) -> Result<(), Error> {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    unsupported!(src);
+
+    // we'd truncate our own input otherwise
+    if dst.exists() && std::fs::canonicalize(src)? == std::fs::canonicalize(dst)? {
+        return Err(tag_error("Source and destination are the same file"));
+    }
+
+    // an existing destination stays as it is until the copy is complete
+    let (tmp_path, tmp_file) = file::create_temp(dst)?;
+
+    let result = write_tags(src, tmp_file, tags, &WriteOptions::default())
+        .and_then(|tmp_file| file::persist(tmp_file, &tmp_path, dst).map_err(Error::from));
+
+    if result.is_err() {
+        // don't leave half-written copies around
+        std::fs::remove_file(&tmp_path).ok();
+    }
+    result
+}
+
+// write the file at path with new tags into another (empty) file
+fn write_tags(
+    path: &Path,
+    tmp_file: File,
+    tags: &Tags,
+    options: &WriteOptions,
+) -> Result<File, Error> {
+    let mut file = File::open(path)?;
+    let mut tmp_file = BufWriter::new(tmp_file);
+
+    match format(path) {
+        Some("mp3") | Some("aac") | Some("mp2") => {
+            id3v2::set_with_options(&mut file, &mut tmp_file, tags, options)?
+        }
+        Some("flac") => flac::set_with_options(&mut file, &mut tmp_file, tags, options)?,
+        Some("ogg") => ogg::set(&mut file, &mut tmp_file, tags)?,
+        Some("m4a") => m4a::set_with_options(&mut file, &mut tmp_file, tags, options)?,
+        Some("mka") => matroska::set(&mut file, &mut tmp_file, tags)?,
+        Some("asf") => asf::set(&mut file, &mut tmp_file, tags)?,
+        Some("wav") => wav::set(&mut file, &mut tmp_file, tags)?,
+        Some("aiff") => aiff::set(&mut file, &mut tmp_file, tags)?,
+        Some("dsf") => dsf::set(&mut file, &mut tmp_file, tags)?,
+        Some("dff") => dff::set(&mut file, &mut tmp_file, tags)?,
+        // the APE tag is written in place below
+        Some("wv") | Some("ape") | Some("mpc") => {
+            std::io::copy(&mut file, &mut tmp_file)?;
+        }
+        None | Some(_) => return Err(tag_error("Unsupported file format")),
+    }
+
+    let mut tmp_file = into_inner(tmp_file)?;
+
+    // APE is the native tag of some formats; for MPEG streams, keep
+    // existing APE and ID3v1 tags in sync for other players
+    let ape_native = matches!(format(path), Some("wv") | Some("ape") | Some("mpc"));
+    if ape_native || matches!(format(path), Some("mp3") | Some("aac") | Some("mp2")) {
+        if ape_native || ape::has_ape(&mut tmp_file) {
+            let length = ape::set(&mut tmp_file, tags)?;
+            tmp_file.set_len(length)?;
+        }
+        if id3v1::has_id3v1(&mut tmp_file) {
+            id3v1::set(&mut tmp_file, tags)?;
+        }
+    }
+
+    Ok(tmp_file)
+}
+
+pub fn remove_tags<P: AsRef<Path>>(path: P, which: &TagTypes) -> Result<(), Error> {
+    let path = path.as_ref();
+    unsupported!(path);
+
+    let (tmp_path, tmp_file) = file::create_temp(path)?;
+
+    let result = strip_tags(path, tmp_file, which).and_then(|tmp_file| {
+        file::replace(tmp_file, &tmp_path, path, &WriteOptions::default()).map_err(Error::from)
+    });
+
+    if result.is_err() {
+        std::fs::remove_file(&tmp_path).ok();
+    }
+    result
+}
+
+// copy the original file without the unwanted tags into the temporary file
+fn strip_tags(path: &Path, tmp_file: File, which: &TagTypes) -> Result<File, Error> {
+    let mut file = File::open(path)?;
+    let mut tmp_file = BufWriter::new(tmp_file);
+
+    match format(path) {
+        Some("mp3") | Some("aac") | Some("mp2") if which.id3v2 => {
+            id3v2::remove(&mut file, &mut tmp_file)?
+        }
+        Some("ogg") if which.vorbis => ogg::remove(&mut file, &mut tmp_file)?,
+        Some("m4a") if which.m4a => m4a::remove(&mut file, &mut tmp_file)?,
+        Some("mka") if which.matroska => matroska::remove(&mut file, &mut tmp_file)?,
+        Some("asf") if which.asf => asf::remove(&mut file, &mut tmp_file)?,
+        Some("dsf") if which.id3v2 => dsf::remove(&mut file, &mut tmp_file)?,
+        Some("dff") if which.id3v2 => dff::remove(&mut file, &mut tmp_file)?,
+        Some("flac") => flac::remove(&mut file, &mut tmp_file, which.vorbis, which.id3v2)?,
+        Some("wav") => wav::remove(&mut file, &mut tmp_file, which.riff_info, which.id3v2)?,
+        Some("aiff") => aiff::remove(&mut file, &mut tmp_file, which.aiff_text, which.id3v2)?,
+        Some("mp3") | Some("aac") | Some("mp2") | Some("ogg") | Some("m4a") | Some("mka")
+        | Some("asf") | Some("dsf") | Some("dff") | Some("wv") | Some("ape") | Some("mpc") => {
+            std::io::copy(&mut file, &mut tmp_file)?;
+        }
+        None | Some(_) => return Err(tag_error("Unsupported file format")),
+    }
+
+    let mut tmp_file = into_inner(tmp_file)?;
 
+    if let Some("mp3") | Some("aac") | Some("mp2") | Some("wv") | Some("ape") | Some("mpc") =
+        format(path)
     {
-        let mut file = File::open(path)?;
-        // create temporary file to write to
-        let mut tmp_file = BufWriter::new(
-            std::fs::OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(&tmp_path)?,
-        );
-
-        match path.extension().unwrap_or_default().to_str() {
-            Some("mp3") => {
-                // write id3v2
-                if let Err(x) = id3v2::set(&mut file, &mut tmp_file, tags) {
-                    // on failure, delete temporary file
-                    std::fs::remove_file(tmp_path)?;
-                    return Err(x);
-                }
+        strip_trailing(&mut tmp_file, which)?;
+    }
 
-                // get back the file from buffered writer
-                let mut tmp_file = match tmp_file.into_inner() {
-                    Ok(tmp_file) => tmp_file,
-                    Err(x) => {
-                        std::fs::remove_file(tmp_path)?;
-                        return Err(Error::TagError(format!(
-                            "Could not unwrap buffered writer: {:?}",
-                            x
-                        )));
-                    }
-                };
+    Ok(tmp_file)
+}
 
-                if id3v1::has_id3v1(&mut tmp_file) {
-                    if let Ok(x) = tmp_file.metadata() {
-                        tmp_file.set_len(x.len() - 128).ok();
-                    }
-                }
-            }
+// tags at the end of the file, in the order they appear:
+//     [audio] [APE] [Lyrics3] [ID3v1]
+fn strip_trailing(file: &mut File, which: &TagTypes) -> Result<(), Error> {
+    let mut end = file.metadata()?.len();
+    let mut trailing = Vec::new();
 
-            Some("flac") => {
-                if let Err(x) = flac::set(&mut file, &mut tmp_file, tags) {
-                    // on failure, delete temporary file
-                    std::fs::remove_file(tmp_path)?;
-                    return Err(x);
-                }
-            }
-            Some("m4a") => {
-                if let Err(x) = m4a::set(&mut file, &mut tmp_file, tags) {
-                    // on failure, delete temporary file
-                    std::fs::remove_file(tmp_path)?;
-                    return Err(x);
-                }
-            }
-            None | Some(_) => {
-                std::fs::remove_file(tmp_path)?;
-                return Err(tag_error("Unsupported file format"));
-            }
+    if id3v1::has_id3v1(file) {
+        end -= 128;
+        trailing.push((end, 128, which.id3v1));
+
+        // Lyrics3 only exists in front of ID3v1, and can't stay without it
+        if let Some((start, length)) = lyrics3::locate(file, end)? {
+            end = start;
+            trailing.push((start, length, which.lyrics3 || which.id3v1));
+        }
+    }
+    if let Some((start, length)) = ape::locate(file, end)? {
+        end = start;
+        trailing.push((start, length, which.ape));
+    }
+
+    if trailing.iter().all(|&(_, _, strip)| !strip) {
+        return Ok(());
+    }
+
+    // collect the tags we keep, then chop everything off and put them back
+    let mut keep = Vec::new();
+    for &(start, length, strip) in trailing.iter().rev() {
+        if !strip {
+            let mut vec = vec![0; length as usize];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(&mut vec)?;
+            keep.append(&mut vec);
         }
     }
 
-    // replace original file
-    std::fs::rename(tmp_path, path)?;
+    file.set_len(end)?;
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(&keep)?;
     Ok(())
 }
+
+// get back the file from buffered writer
+fn into_inner(writer: BufWriter<File>) -> Result<File, Error> {
+    match writer.into_inner() {
+        Ok(file) => Ok(file),
+        Err(x) => Err(Error::TagError(format!(
+            "Could not unwrap buffered writer: {:?}",
+            x
+        ))),
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::prelude::*;
+use std::io::Cursor;
+use std::io::SeekFrom;
+
+use crate::Error;
+use crate::Properties;
+use crate::Tags;
+
+use crate::id3v2;
+use crate::tools::decode_int_le_u32;
+use crate::tools::decode_int_le_u64;
+use crate::tools::encode_int_le_u64;
+use crate::tools::tag_error;
+
+// size of the "DSD " chunk, which is the file header
+const HEADER_SIZE: u64 = 28;
+
+struct Dsf {
+    // start of the ID3v2 tag, which is where the audio ends
+    audio_end: u64,
+    has_tag: bool,
+}
+
+fn read_header<T: Read + Seek>(input: &mut T) -> Result<Dsf, Error> {
+    let length = input.seek(SeekFrom::End(0))?;
+    input.seek(SeekFrom::Start(0))?;
+
+    let mut arr: [u8; 28] = [0; 28];
+    input.read_exact(&mut arr)?;
+
+    if &arr[0..4] != b"DSD " || decode_int_le_u64(&arr[4..12]) != HEADER_SIZE {
+        return Err(tag_error("Not a DSF file"));
+    }
+
+    // the metadata pointer is 0 without a tag
+    let pointer = decode_int_le_u64(&arr[20..28]);
+    Ok(match pointer {
+        x if x >= HEADER_SIZE && x < length => Dsf {
+            audio_end: x,
+            has_tag: true,
+        },
+        _ => Dsf {
+            audio_end: length,
+            has_tag: false,
+        },
+    })
+}
+
+fn read_tag<T: Read + Seek>(input: &mut T, dsf: &Dsf) -> Result<Vec<u8>, Error> {
+    let mut vec = Vec::new();
+    if dsf.has_tag {
+        input.seek(SeekFrom::Start(dsf.audio_end))?;
+        input.read_to_end(&mut vec)?;
+    }
+    Ok(vec)
+}
+
+pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    let dsf = read_header(input)?;
+    match id3v2::get(&mut Cursor::new(read_tag(input, &dsf)?)) {
+        Ok(t) => Ok(t),
+        Err(Error::TagError(_)) => Ok(Tags::none()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn set<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    new: &Tags,
+) -> Result<(), Error> {
+    let dsf = read_header(input)?;
+
+    let mut vec = Vec::new();
+    id3v2::set(&mut Cursor::new(read_tag(input, &dsf)?), &mut vec, new)?;
+
+    rewrite(input, output, &dsf, &vec)
+}
+
+// copy the file without the ID3v2 tag
+pub fn remove<R: Read + Seek, W: Write>(input: &mut R, output: &mut W) -> Result<(), Error> {
+    let dsf = read_header(input)?;
+    rewrite(input, output, &dsf, &[])
+}
+
+// copy the audio and put the tag behind it, updating the header to match
+fn rewrite<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    dsf: &Dsf,
+    tag: &[u8],
+) -> Result<(), Error> {
+    let pointer = match tag.len() {
+        0 => 0,
+        _ => dsf.audio_end,
+    };
+
+    output.write_all(b"DSD ")?;
+    output.write_all(&encode_int_le_u64(HEADER_SIZE))?;
+    output.write_all(&encode_int_le_u64(dsf.audio_end + tag.len() as u64))?;
+    output.write_all(&encode_int_le_u64(pointer))?;
+
+    input.seek(SeekFrom::Start(HEADER_SIZE))?;
+    std::io::copy(
+        &mut input.by_ref().take(dsf.audio_end - HEADER_SIZE),
+        output,
+    )?;
+    output.write_all(tag)?;
+
+    output.flush()?;
+    Ok(())
+}
+
+// audio properties from the fmt and data chunks following the header
+pub fn get_properties<T: Read + Seek>(input: &mut T) -> Result<Properties, Error> {
+    read_header(input)?;
+
+    let mut arr: [u8; 52] = [0; 52];
+    input.read_exact(&mut arr)?;
+    if &arr[0..4] != b"fmt " {
+        return Err(tag_error("DSF fmt chunk not found"));
+    }
+
+    let mut data: [u8; 12] = [0; 12];
+    input.seek(SeekFrom::Start(
+        HEADER_SIZE + decode_int_le_u64(&arr[4..12]),
+    ))?;
+    input.read_exact(&mut data)?;
+    if &data[0..4] != b"data" {
+        return Err(tag_error("DSF data chunk not found"));
+    }
+
+    Ok(Properties::from_samples(
+        Some(decode_int_le_u64(&arr[36..44])),
+        decode_int_le_u32(&arr[28..32]),
+        decode_int_le_u32(&arr[24..28]) as u8,
+        Some(decode_int_le_u32(&arr[32..36]) as u8),
+        decode_int_le_u64(&data[4..12]).saturating_sub(12),
+    ))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,90 @@
+use crate::TagOption;
+use crate::Tags;
+use std::io::Cursor;
+use std::time::Duration;
+
+use crate::tools::decode_int_le_u64;
+use crate::tools::encode_int_le_u32;
+use crate::tools::encode_int_le_u64;
+
+// stereo DSD64 with 2822400 samples per channel (one second)
+fn dsf() -> Vec<u8> {
+    let mut vec = b"DSD ".to_vec();
+    vec.extend_from_slice(&encode_int_le_u64(28));
+    vec.extend_from_slice(&encode_int_le_u64(28 + 52 + 12 + 16));
+    vec.extend_from_slice(&encode_int_le_u64(0));
+
+    vec.extend_from_slice(b"fmt ");
+    vec.extend_from_slice(&encode_int_le_u64(52));
+    for x in [1, 0, 2, 2, 2822400, 1].iter() {
+        vec.extend_from_slice(&encode_int_le_u32(*x));
+    }
+    vec.extend_from_slice(&encode_int_le_u64(2822400));
+    vec.extend_from_slice(&encode_int_le_u32(4096));
+    vec.extend_from_slice(&[0; 4]);
+
+    vec.extend_from_slice(b"data");
+    vec.extend_from_slice(&encode_int_le_u64(12 + 16));
+    vec.extend_from_slice(&[0x69; 16]);
+    vec
+}
+
+#[test]
+fn dsf_set_test() {
+    let original = dsf();
+    assert_eq!(
+        super::get(&mut Cursor::new(&original)).unwrap(),
+        Tags::none()
+    );
+
+    let new = Tags {
+        title: TagOption::Some("DSD Title".to_string()),
+        album: TagOption::Some("DSD Album".to_string()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&original), &mut output, &new).unwrap();
+
+    // the tag goes behind the audio, and the header says so
+    assert_eq!(
+        &output[..28 + 52 + 28],
+        &{
+            let mut vec = original.clone();
+            vec[12..20].copy_from_slice(&encode_int_le_u64(output.len() as u64));
+            vec[20..28].copy_from_slice(&encode_int_le_u64(original.len() as u64));
+            vec
+        }[..]
+    );
+    assert_eq!(&output[original.len()..original.len() + 3], b"ID3");
+
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("DSD Title".to_string()));
+    assert_eq!(tags.album, TagOption::Some("DSD Album".to_string()));
+
+    // a second write replaces the tag rather than adding another
+    let new = Tags {
+        title: TagOption::Some("Other".to_string()),
+        ..Tags::mixed()
+    };
+    let mut again = Vec::new();
+    super::set(&mut Cursor::new(&output), &mut again, &new).unwrap();
+    assert_eq!(decode_int_le_u64(&again[20..28]), original.len() as u64);
+    let tags = super::get(&mut Cursor::new(&again)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("Other".to_string()));
+    assert_eq!(tags.album, TagOption::Some("DSD Album".to_string()));
+
+    let mut bare = Vec::new();
+    super::remove(&mut Cursor::new(&again), &mut bare).unwrap();
+    assert_eq!(bare, original);
+}
+
+#[test]
+fn dsf_properties_test() {
+    let properties = super::get_properties(&mut Cursor::new(dsf())).unwrap();
+    assert_eq!(properties.duration, Some(Duration::from_secs(1)));
+    assert_eq!(properties.sample_rate, Some(2822400));
+    assert_eq!(properties.channels, Some(2));
+    assert_eq!(properties.bits_per_sample, Some(1));
+
+    assert!(super::get_properties(&mut Cursor::new(vec![0; 100])).is_err());
+}
@@ -0,0 +1,449 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::Error;
+
+use crate::id3v2;
+use crate::tools::decode_int_be_u32;
+use crate::tools::decode_int_be_u64;
+use crate::tools::decode_int_le_u32;
+use crate::tools::encode_int_be_u16;
+use crate::tools::encode_int_be_u32;
+use crate::tools::encode_int_be_u64;
+use crate::tools::encode_int_le_u32;
+use crate::tools::tag_error;
+
+// all metadata blocks of a FLAC file, in the order they appear,
+// the first one always being STREAMINFO
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct FlacMetadata {
+    pub blocks: Vec<FlacBlock>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum FlacBlock {
+    StreamInfo(StreamInfo),
+    // number of zero bytes
+    Padding(u32),
+    Application { id: [u8; 4], data: Vec<u8> },
+    SeekTable(Vec<SeekPoint>),
+    VorbisComment(VorbisComment),
+    CueSheet(FlacCueSheet),
+    Picture(FlacPicture),
+    // reserved block types, kept as they are
+    Unknown { kind: u8, data: Vec<u8> },
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct StreamInfo {
+    pub min_block_size: u16,
+    pub max_block_size: u16,
+    // in bytes, 0 when unknown
+    pub min_frame_size: u32,
+    pub max_frame_size: u32,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub bits_per_sample: u8,
+    // per channel, 0 when unknown
+    pub total_samples: u64,
+    pub md5: [u8; 16],
+}
+
+// placeholder points have a sample number of 0xFFFFFFFFFFFFFFFF
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct SeekPoint {
+    pub sample: u64,
+    // from the first frame header
+    pub offset: u64,
+    pub samples: u16,
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct VorbisComment {
+    pub vendor: String,
+    // NAME=value
+    pub comments: Vec<String>,
+}
+
+// offsets are in samples
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct FlacCueSheet {
+    pub catalog: String,
+    pub lead_in: u64,
+    pub is_cd: bool,
+    // the lead-out is the last track, numbered 170 on CDs and 255 otherwise
+    pub tracks: Vec<FlacCueTrack>,
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct FlacCueTrack {
+    pub offset: u64,
+    pub number: u8,
+    pub isrc: String,
+    pub is_audio: bool,
+    pub pre_emphasis: bool,
+    pub indices: Vec<FlacCueIndex>,
+}
+
+// relative to the start of the track
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct FlacCueIndex {
+    pub offset: u64,
+    pub number: u8,
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct FlacPicture {
+    // ID3v2 APIC types, 3 is the front cover
+    pub kind: u32,
+    pub mime: String,
+    pub description: String,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub colors: u32,
+    pub data: Vec<u8>,
+}
+
+// reads through a block, running out of data is an error
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() - self.pos < length {
+            return Err(tag_error("FLAC metadata block is too short"));
+        }
+        self.pos += length;
+        Ok(&self.data[self.pos - length..self.pos])
+    }
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(decode_int_be_u32(self.bytes(4)?))
+    }
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(decode_int_be_u64(self.bytes(8)?))
+    }
+    fn string(&mut self, length: usize) -> Result<String, Error> {
+        Ok(String::from_utf8_lossy(self.bytes(length)?).into_owned())
+    }
+    // fixed size fields are padded with zeros
+    fn padded(&mut self, length: usize) -> Result<String, Error> {
+        let bytes = self.bytes(length)?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(length);
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+fn padded(text: &str, length: usize) -> Vec<u8> {
+    let mut vec = text.as_bytes().to_vec();
+    vec.resize(length, 0);
+    vec
+}
+
+impl FlacBlock {
+    pub fn parse(kind: u8, data: &[u8]) -> Result<FlacBlock, Error> {
+        let mut r = Reader { data, pos: 0 };
+        Ok(match kind {
+            0 => {
+                let bytes = r.bytes(34)?;
+                let mut md5 = [0; 16];
+                md5.copy_from_slice(&bytes[18..34]);
+                // 20 bits of sample rate, 3 of channels, 5 of bits per sample, 36 of samples
+                let packed = decode_int_be_u64(&bytes[10..18]);
+                FlacBlock::StreamInfo(StreamInfo {
+                    min_block_size: decode_int_be_u32(&bytes[0..2]) as u16,
+                    max_block_size: decode_int_be_u32(&bytes[2..4]) as u16,
+                    min_frame_size: decode_int_be_u32(&bytes[4..7]),
+                    max_frame_size: decode_int_be_u32(&bytes[7..10]),
+                    sample_rate: (packed >> 44) as u32,
+                    channels: ((packed >> 41) & 0x7) as u8 + 1,
+                    bits_per_sample: ((packed >> 36) & 0x1F) as u8 + 1,
+                    total_samples: packed & 0xF_FFFF_FFFF,
+                    md5,
+                })
+            }
+            1 => FlacBlock::Padding(data.len() as u32),
+            2 => {
+                let mut id = [0; 4];
+                id.copy_from_slice(r.bytes(4)?);
+                FlacBlock::Application {
+                    id,
+                    data: data[4..].to_vec(),
+                }
+            }
+            3 => {
+                let mut points = Vec::new();
+                for _ in 0..data.len() / 18 {
+                    points.push(SeekPoint {
+                        sample: r.u64()?,
+                        offset: r.u64()?,
+                        samples: decode_int_be_u32(r.bytes(2)?) as u16,
+                    });
+                }
+                FlacBlock::SeekTable(points)
+            }
+            // little endian, like everywhere else Vorbis comments show up
+            4 => {
+                let length = decode_int_le_u32(r.bytes(4)?) as usize;
+                let vendor = r.string(length)?;
+                let count = decode_int_le_u32(r.bytes(4)?);
+                let mut comments = Vec::new();
+                for _ in 0..count {
+                    let length = decode_int_le_u32(r.bytes(4)?) as usize;
+                    comments.push(r.string(length)?);
+                }
+                FlacBlock::VorbisComment(VorbisComment { vendor, comments })
+            }
+            5 => {
+                let catalog = r.padded(128)?;
+                let lead_in = r.u64()?;
+                let is_cd = r.bytes(259)?[0] & 0x80 != 0;
+                let mut tracks = Vec::new();
+                for _ in 0..r.u8()? {
+                    let offset = r.u64()?;
+                    let number = r.u8()?;
+                    let isrc = r.padded(12)?;
+                    let flags = r.bytes(14)?[0];
+                    let mut indices = Vec::new();
+                    for _ in 0..r.u8()? {
+                        indices.push(FlacCueIndex {
+                            offset: r.u64()?,
+                            number: r.bytes(4)?[0],
+                        });
+                    }
+                    tracks.push(FlacCueTrack {
+                        offset,
+                        number,
+                        isrc,
+                        is_audio: flags & 0x80 == 0,
+                        pre_emphasis: flags & 0x40 != 0,
+                        indices,
+                    });
+                }
+                FlacBlock::CueSheet(FlacCueSheet {
+                    catalog,
+                    lead_in,
+                    is_cd,
+                    tracks,
+                })
+            }
+            6 => {
+                let kind = r.u32()?;
+                let length = r.u32()? as usize;
+                let mime = r.string(length)?;
+                let length = r.u32()? as usize;
+                let description = r.string(length)?;
+                let width = r.u32()?;
+                let height = r.u32()?;
+                let depth = r.u32()?;
+                let colors = r.u32()?;
+                let length = r.u32()? as usize;
+                FlacBlock::Picture(FlacPicture {
+                    kind,
+                    mime,
+                    description,
+                    width,
+                    height,
+                    depth,
+                    colors,
+                    data: r.bytes(length)?.to_vec(),
+                })
+            }
+            127 => {
+                return Err(tag_error(
+                    "Invalid block type (127) detected when parsing FLAC metadata",
+                ))
+            }
+            _ => FlacBlock::Unknown {
+                kind,
+                data: data.to_vec(),
+            },
+        })
+    }
+
+    // the block type and its contents, without the header
+    pub fn render(&self) -> (u8, Vec<u8>) {
+        let mut vec = Vec::new();
+        let kind = match self {
+            FlacBlock::StreamInfo(x) => {
+                vec.extend_from_slice(&encode_int_be_u16(x.min_block_size));
+                vec.extend_from_slice(&encode_int_be_u16(x.max_block_size));
+                vec.extend_from_slice(&encode_int_be_u32(x.min_frame_size)[1..]);
+                vec.extend_from_slice(&encode_int_be_u32(x.max_frame_size)[1..]);
+                let packed = (x.sample_rate as u64 & 0xF_FFFF) << 44
+                    | (x.channels.wrapping_sub(1) as u64 & 0x7) << 41
+                    | (x.bits_per_sample.wrapping_sub(1) as u64 & 0x1F) << 36
+                    | x.total_samples & 0xF_FFFF_FFFF;
+                vec.extend_from_slice(&encode_int_be_u64(packed));
+                vec.extend_from_slice(&x.md5);
+                0
+            }
+            FlacBlock::Padding(x) => {
+                vec.resize(*x as usize, 0);
+                1
+            }
+            FlacBlock::Application { id, data } => {
+                vec.extend_from_slice(id);
+                vec.extend_from_slice(data);
+                2
+            }
+            FlacBlock::SeekTable(points) => {
+                for x in points.iter() {
+                    vec.extend_from_slice(&encode_int_be_u64(x.sample));
+                    vec.extend_from_slice(&encode_int_be_u64(x.offset));
+                    vec.extend_from_slice(&encode_int_be_u16(x.samples));
+                }
+                3
+            }
+            FlacBlock::VorbisComment(x) => {
+                vec.extend_from_slice(&encode_int_le_u32(x.vendor.len() as u32));
+                vec.extend_from_slice(x.vendor.as_bytes());
+                vec.extend_from_slice(&encode_int_le_u32(x.comments.len() as u32));
+                for comment in x.comments.iter() {
+                    vec.extend_from_slice(&encode_int_le_u32(comment.len() as u32));
+                    vec.extend_from_slice(comment.as_bytes());
+                }
+                4
+            }
+            FlacBlock::CueSheet(x) => {
+                vec.append(&mut padded(&x.catalog, 128));
+                vec.extend_from_slice(&encode_int_be_u64(x.lead_in));
+                let mut reserved = vec![0; 259];
+                reserved[0] = if x.is_cd { 0x80 } else { 0 };
+                vec.append(&mut reserved);
+                vec.push(x.tracks.len() as u8);
+                for track in x.tracks.iter() {
+                    vec.extend_from_slice(&encode_int_be_u64(track.offset));
+                    vec.push(track.number);
+                    vec.append(&mut padded(&track.isrc, 12));
+                    let mut reserved = vec![0; 14];
+                    reserved[0] = if track.is_audio { 0 } else { 0x80 }
+                        | if track.pre_emphasis { 0x40 } else { 0 };
+                    vec.append(&mut reserved);
+                    vec.push(track.indices.len() as u8);
+                    for index in track.indices.iter() {
+                        vec.extend_from_slice(&encode_int_be_u64(index.offset));
+                        vec.extend_from_slice(&[index.number, 0, 0, 0]);
+                    }
+                }
+                5
+            }
+            FlacBlock::Picture(x) => {
+                vec.extend_from_slice(&encode_int_be_u32(x.kind));
+                vec.extend_from_slice(&encode_int_be_u32(x.mime.len() as u32));
+                vec.extend_from_slice(x.mime.as_bytes());
+                vec.extend_from_slice(&encode_int_be_u32(x.description.len() as u32));
+                vec.extend_from_slice(x.description.as_bytes());
+                for &x in [x.width, x.height, x.depth, x.colors].iter() {
+                    vec.extend_from_slice(&encode_int_be_u32(x));
+                }
+                vec.extend_from_slice(&encode_int_be_u32(x.data.len() as u32));
+                vec.extend_from_slice(&x.data);
+                6
+            }
+            FlacBlock::Unknown { kind, data } => {
+                vec.extend_from_slice(data);
+                *kind
+            }
+        };
+        (kind, vec)
+    }
+}
+
+impl FlacMetadata {
+    pub fn read<T: Read + Seek>(input: &mut T) -> Result<FlacMetadata, Error> {
+        Ok(read_blocks(input)?.0)
+    }
+
+    pub fn stream_info(&self) -> Option<&StreamInfo> {
+        match self.blocks.first() {
+            Some(FlacBlock::StreamInfo(x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    // the stream marker and all blocks, the last one flagged as such
+    pub fn render(&self) -> Result<Vec<u8>, Error> {
+        if self.stream_info().is_none() {
+            return Err(tag_error("FLAC metadata has to start with STREAMINFO"));
+        }
+
+        let mut vec = b"fLaC".to_vec();
+        for (i, block) in self.blocks.iter().enumerate() {
+            let (kind, data) = block.render();
+            if data.len() > 0x00FF_FFFF {
+                return Err(tag_error(
+                    "Could not write FLAC metadata block as it is larger than 16,777,215 bytes",
+                ));
+            }
+            let mut header = encode_int_be_u32(data.len() as u32);
+            header[0] = kind;
+            if i == self.blocks.len() - 1 {
+                header[0] |= 0b10000000;
+            }
+            vec.append(&mut header);
+            vec.extend_from_slice(&data);
+        }
+        Ok(vec)
+    }
+
+    // copy a FLAC file with these blocks instead of its own
+    pub fn write<R: Read + Seek, W: Write>(
+        &self,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<(), Error> {
+        let (_, start, end) = read_blocks(input)?;
+        let vec = self.render()?;
+
+        // a leading ID3v2 tag stays where it is
+        input.seek(SeekFrom::Start(0))?;
+        std::io::copy(&mut input.by_ref().take(start), output)?;
+        output.write_all(&vec)?;
+        input.seek(SeekFrom::Start(end))?;
+        std::io::copy(input, output)?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+// the blocks, along with where the stream marker and the audio frames start
+pub fn read_blocks<T: Read + Seek>(input: &mut T) -> Result<(FlacMetadata, u64, u64), Error> {
+    let start = id3v2::skip(input)?;
+
+    let mut buf: [u8; 4] = [0; 4];
+    input.read_exact(&mut buf)?;
+    if &buf != b"fLaC" {
+        return Err(tag_error(
+            "FLAC stream marker not found (is this a valid FLAC file?)",
+        ));
+    }
+
+    let mut metadata = FlacMetadata::default();
+    loop {
+        input.read_exact(&mut buf)?;
+        let kind = buf[0] & 0b01111111;
+        let mut data = vec![0; decode_int_be_u32(&buf[1..]) as usize];
+        input.read_exact(&mut data)?;
+
+        // broken blocks are kept as they are, unless we can't do without them
+        let block = match FlacBlock::parse(kind, &data) {
+            Err(Error::TagError(_)) if kind != 0 && kind != 127 => {
+                FlacBlock::Unknown { kind, data }
+            }
+            x => x?,
+        };
+        metadata.blocks.push(block);
+
+        if buf[0] & 0b10000000 != 0 {
+            break;
+        }
+    }
+
+    Ok((metadata, start, input.stream_position()?))
+}
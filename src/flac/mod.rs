@@ -1,19 +1,28 @@
 use std;
 use std::io::prelude::*;
+use std::io::SeekFrom;
 
 use crate::Error;
 use crate::Image;
 use crate::TagOption;
 use crate::Tags;
+use crate::WriteOptions;
 
+use crate::id3v2;
 use crate::vorbis;
 
+pub mod metadata;
+use self::metadata::read_blocks;
+use self::metadata::FlacBlock;
+
+use crate::tools::bounded::Bounded;
 use crate::tools::decode_int_be_u32;
 use crate::tools::encode_int_be_u32;
 use crate::tools::tag_error;
 use crate::tools::tags;
 
 pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    let start = id3v2::skip(input)?;
     let mut buf: [u8; 4] = [0; 4];
 
     input.read(&mut buf)?;
@@ -69,127 +78,92 @@ pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
         tags.front_cover = TagOption::Some(x);
     }
 
+    // a leading ID3v2 tag only fills in what the Vorbis comment doesn't have
+    Ok(tags::merge(tags, leading_id3v2(input, start)?))
+}
+
+// the tags of an ID3v2 tag ending at start, which some rippers
+// put in front of the stream
+fn leading_id3v2<T: Read + Seek>(input: &mut T, start: u64) -> Result<Tags, Error> {
+    if start == 0 {
+        return Ok(Tags::none());
+    }
+    let tags = match id3v2::get(&mut Bounded::new(input, 0, start)) {
+        Ok(t) => t,
+        Err(Error::TagError(_)) => Tags::none(),
+        Err(e) => return Err(e),
+    };
+    input.seek(SeekFrom::Start(start))?;
     Ok(tags)
 }
 
-// same loop as get mostly, so that we can do it all in one loop
-pub fn set<R: Read + Seek, W: Write>(
+pub fn set_with_options<R: Read + Seek, W: Write>(
     input: &mut R,
     output: &mut W,
     new: &Tags,
+    options: &WriteOptions,
 ) -> Result<(), Error> {
-    let mut buf: [u8; 4] = [0; 4];
-
-    input.read(&mut buf)?;
-
-    if &buf != b"fLaC" {
-        return Err(tag_error(
-            "FLAC stream marker not found (is this a valid FLAC file?)",
-        ));
-    }
-
-    // write header
-    output.write(&buf)?;
-
-    let mut image: Option<Image> = None;
-    let mut found_best_cover = false;
-
-    let mut old = Tags::none();
-
-    loop {
-        // considering that on failure to fill it,
-        // we would return with an error
-        // should i still zero out the buffer?
-        input.read(&mut buf)?;
-        let len = decode_int_be_u32(&buf[1..]);
-
-        match buf[0] & 0b01111111 {
-            4 => {
-                // vorbis comment
-                old = vorbis::get_tags(input)?;
-            }
-            6 => {
-                // picture
-                let (img, apic_type) = read_image(input, len)?;
-
-                if apic_type == 0x03 || !found_best_cover {
-                    image = Some(img);
-                    found_best_cover = apic_type == 0x03;
-                }
-            }
-            127 => {
-                return Err(tag_error(
-                    "Invalid block type (127) detected when parsing FLAC metadata",
-                ))
-            }
-            1 => {
-                input.seek(std::io::SeekFrom::Current(len as i64))?;
-            }
-
-            // copy write all non-tag blocks
-            _ => {
-                // this should be possible with take,
-                // but we don't own the reader
-                let mut vec = Vec::with_capacity(4 + len as usize);
-                vec.extend_from_slice(&buf);
-                vec.resize(4 + len as usize, 0);
-                input.read(&mut vec[4..])?;
-                // last metadata block will be our content
-                // so unset the last metadata block flag
-                vec[0] &= 0b11111111;
-                // copy the block
-                output.write(&vec)?;
-            }
-        }
-
-        if buf[0] & 0b10000000 != 0 {
-            break;
-        }
-    }
-
-    // if we found an image metadata block, apply it here
-    if let Some(x) = image {
-        old.front_cover = TagOption::Some(x);
-    }
-
-    let tags = tags::delta(&old, new); // obtain the final tags
+    let tags = tags::delta(&get(input)?, new); // obtain the final tags
+    let (mut metadata, start, end) = read_blocks(input)?;
 
-    // get the vorbis comment
+    // the comment goes right after STREAMINFO with the picture behind it,
+    // every other block stays where it was
+    metadata.blocks.retain(|x| !is_tag(x));
     let vc = vorbis::from_tags(&tags, false);
-
-    if vc.len() > 0x00FFFFFF {
-        return Err(tag_error(
-            "Could not write FLAC comment as it is larger than 16,777,215 bytes",
-        ));
+    metadata.blocks.insert(1, FlacBlock::parse(4, &vc)?);
+    if let TagOption::Some(ref x) = tags.front_cover {
+        let picture = FlacBlock::parse(6, &get_picture_block(x)?)?;
+        metadata.blocks.insert(2, picture);
     }
+    let vec = metadata.render()?;
 
-    let mut vc_header = encode_int_be_u32(vc.len() as u32);
-
-    vc_header[0] = 0x04; // last comment header
+    // keep a leading ID3v2 tag in sync, unless asked to drop it
+    if start > 0 && !options.strip_leading_id3v2 {
+        id3v2::set_with_options(&mut Bounded::new(input, 0, start), output, &tags, options)?;
+    }
+    output.write_all(&vec)?;
 
-    output.write(&vc_header)?;
-    output.write(&vc)?;
+    // now copy the rest of the file
+    input.seek(SeekFrom::Start(end))?;
+    std::io::copy(input, output)?;
+    output.flush()?;
 
-    if let TagOption::Some(ref x) = tags.front_cover {
-        let img_block = get_picture_block(x)?;
+    Ok(())
+}
 
-        if img_block.len() > 0x00FFFFFF {
-            return Err(tag_error(
-                "Could not write FLAC picture block as it is larger than 16,777,215 bytes",
-            ));
-        }
+// VORBIS_COMMENT and PICTURE blocks, even ones we couldn't make sense of
+fn is_tag(block: &FlacBlock) -> bool {
+    matches!(
+        block,
+        FlacBlock::VorbisComment(_)
+            | FlacBlock::Picture(_)
+            | FlacBlock::Unknown { kind: 4, .. }
+            | FlacBlock::Unknown { kind: 6, .. }
+    )
+}
 
-        let mut img_block_header = encode_int_be_u32(img_block.len() as u32);
-        img_block_header[0] = 0x06; // last block
+// copy the file without VORBIS_COMMENT and PICTURE blocks
+// and/or without a leading ID3v2 tag
+pub fn remove<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    vorbis: bool,
+    id3: bool,
+) -> Result<(), Error> {
+    let (mut metadata, start, end) = read_blocks(input)?;
 
-        output.write(&img_block_header)?;
-        output.write(&img_block)?;
+    if vorbis {
+        // STREAMINFO is mandatory, so there is always a block left
+        metadata.blocks.retain(|x| !is_tag(x));
     }
-
-    // minimal padding block
-    output.write(&[0b10000001, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00])?;
+    if !id3 {
+        input.seek(SeekFrom::Start(0))?;
+        std::io::copy(&mut input.by_ref().take(start), output)?;
+    }
+    output.write_all(&metadata.render()?)?;
 
     // now copy the rest of the file
+    input.seek(SeekFrom::Start(end))?;
     std::io::copy(input, output)?;
     output.flush()?;
 
@@ -31,3 +31,169 @@ fn picture_block_encode() {
 
     assert_eq!(image, image2);
 }
+
+#[test]
+fn remove_test() {
+    let mut file = File::open("testfiles/flac.flac").unwrap();
+    let mut output = Vec::new();
+    super::remove(&mut file, &mut output, true, true).unwrap();
+
+    let tags = super::get(&mut std::io::Cursor::new(&output)).unwrap();
+    assert_eq!(tags, crate::Tags::none());
+}
+
+#[test]
+fn leading_id3v2_test() {
+    use crate::TagOption;
+    use crate::Tags;
+    use crate::WriteOptions;
+    use std::io::Cursor;
+
+    // a tag in front of the stream, with a title the Vorbis comment
+    // already has and a composer it doesn't
+    let id3 = Tags {
+        title: TagOption::Some("ID3 Title".to_string()),
+        composer: TagOption::Some("Composer".to_string()),
+        ..Tags::none()
+    };
+    let mut file = Vec::new();
+    crate::id3v2::set(&mut Cursor::new(Vec::new()), &mut file, &id3).unwrap();
+    let start = file.len();
+    File::open("testfiles/flac.flac")
+        .unwrap()
+        .read_to_end(&mut file)
+        .unwrap();
+
+    let tags = super::get(&mut Cursor::new(&file)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("drippy".to_string()));
+    assert_eq!(tags.composer, TagOption::Some("Composer".to_string()));
+
+    // the leading tag is kept in sync by default
+    let new = Tags {
+        album: TagOption::Some("Album".to_string()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    let options = WriteOptions::default();
+    super::set_with_options(&mut Cursor::new(&file), &mut output, &new, &options).unwrap();
+    let id3 = crate::id3v2::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(id3.album, TagOption::Some("Album".to_string()));
+    assert_eq!(id3.title, TagOption::Some("drippy".to_string()));
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.composer, TagOption::Some("Composer".to_string()));
+
+    // or dropped, with everything moved into the Vorbis comment
+    let options = WriteOptions {
+        strip_leading_id3v2: true,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    super::set_with_options(&mut Cursor::new(&file), &mut output, &new, &options).unwrap();
+    assert_eq!(&output[0..4], b"fLaC");
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.composer, TagOption::Some("Composer".to_string()));
+    assert_eq!(tags.album, TagOption::Some("Album".to_string()));
+
+    // removing only the ID3v2 tag leaves the FLAC file as it was
+    let mut output = Vec::new();
+    super::remove(&mut Cursor::new(&file), &mut output, false, true).unwrap();
+    assert_eq!(&output[..], &file[start..]);
+}
+
+#[test]
+fn metadata_test() {
+    use super::metadata::*;
+    use std::io::Cursor;
+
+    let mut original = Vec::new();
+    File::open("testfiles/flac.flac")
+        .unwrap()
+        .read_to_end(&mut original)
+        .unwrap();
+
+    // every block comes back byte for byte
+    let (metadata, start, end) = read_blocks(&mut Cursor::new(&original)).unwrap();
+    assert_eq!(start, 0);
+    assert_eq!(metadata.render().unwrap(), &original[..end as usize]);
+
+    let info = metadata.stream_info().unwrap();
+    assert_eq!(info.sample_rate, 44100);
+    assert_eq!(info.channels, 1);
+    assert_eq!(info.bits_per_sample, 16);
+    assert_eq!(info.total_samples, 6754);
+    match &metadata.blocks[2] {
+        FlacBlock::VorbisComment(x) => assert_eq!(x.comments[3], "TITLE=drippy"),
+        x => panic!("{:?}", x),
+    }
+
+    // add some blocks, move the padding up front and write it all back
+    let mut edited = metadata.clone();
+    let padding = edited.blocks.pop().unwrap();
+    edited.blocks.insert(1, padding);
+    edited.blocks.push(FlacBlock::SeekTable(vec![
+        SeekPoint {
+            sample: 0,
+            offset: 0,
+            samples: 4608,
+        },
+        SeekPoint {
+            sample: 0xFFFF_FFFF_FFFF_FFFF,
+            offset: 0,
+            samples: 0,
+        },
+    ]));
+    edited.blocks.push(FlacBlock::Application {
+        id: *b"test",
+        data: vec![1, 2, 3],
+    });
+    edited.blocks.push(FlacBlock::CueSheet(FlacCueSheet {
+        catalog: "1234567890123".to_string(),
+        lead_in: 88200,
+        is_cd: true,
+        tracks: vec![
+            FlacCueTrack {
+                offset: 0,
+                number: 1,
+                isrc: "USRC17607839".to_string(),
+                is_audio: true,
+                pre_emphasis: false,
+                indices: vec![FlacCueIndex {
+                    offset: 0,
+                    number: 1,
+                }],
+            },
+            FlacCueTrack {
+                offset: 6754,
+                number: 170,
+                ..Default::default()
+            },
+        ],
+    }));
+
+    let mut output = Vec::new();
+    edited
+        .write(&mut Cursor::new(&original), &mut output)
+        .unwrap();
+    let (metadata, _, new_end) = read_blocks(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(metadata, edited);
+    assert_eq!(&output[new_end as usize..], &original[end as usize..]);
+
+    // tags are still found wherever the blocks are
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.title, crate::TagOption::Some("drippy".to_string()));
+
+    // writing tags leaves the other blocks alone
+    let new = crate::Tags {
+        title: crate::TagOption::Some("Title".to_string()),
+        ..crate::Tags::mixed()
+    };
+    let mut tagged = Vec::new();
+    let options = crate::WriteOptions::default();
+    super::set_with_options(&mut Cursor::new(&output), &mut tagged, &new, &options).unwrap();
+    let (metadata, _, _) = read_blocks(&mut Cursor::new(&tagged)).unwrap();
+    let kinds: Vec<u8> = metadata.blocks.iter().map(|x| x.render().0).collect();
+    assert_eq!(kinds, vec![0, 4, 6, 1, 3, 2, 5]);
+    assert_eq!(metadata.blocks[6], edited.blocks[6]);
+
+    assert!(FlacMetadata::default().render().is_err());
+}
@@ -2,99 +2,223 @@ use std;
 use std::io::prelude::*;
 
 use crate::tools::encoding::decode_iso_8859_1;
+use crate::tools::encoding::encode_iso_8859_1;
 use crate::tools::tag_error;
+use crate::tools::tags::delta;
 use crate::DateTime;
 use crate::Error;
 use crate::TagOption;
 use crate::Tags;
 
+// ID3v1 genres, including the Winamp extensions (80..191)
+const GENRES: [&str; 192] = [
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "AlternRock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native American",
+    "Cabaret",
+    "New Wave",
+    "Psychedelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+    // Winamp extensions
+    "Folk",
+    "Folk-Rock",
+    "National Folk",
+    "Swing",
+    "Fast Fusion",
+    "Bebop",
+    "Latin",
+    "Revival",
+    "Celtic",
+    "Bluegrass",
+    "Avantgarde",
+    "Gothic Rock",
+    "Progressive Rock",
+    "Psychedelic Rock",
+    "Symphonic Rock",
+    "Slow Rock",
+    "Big Band",
+    "Chorus",
+    "Easy Listening",
+    "Acoustic",
+    "Humour",
+    "Speech",
+    "Chanson",
+    "Opera",
+    "Chamber Music",
+    "Sonata",
+    "Symphony",
+    "Booty Bass",
+    "Primus",
+    "Porn Groove",
+    "Satire",
+    "Slow Jam",
+    "Club",
+    "Tango",
+    "Samba",
+    "Folklore",
+    "Ballad",
+    "Power Ballad",
+    "Rhythmic Soul",
+    "Freestyle",
+    "Duet",
+    "Punk Rock",
+    "Drum Solo",
+    "A Cappella",
+    "Euro-House",
+    "Dance Hall",
+    "Goa",
+    "Drum & Bass",
+    "Club-House",
+    "Hardcore",
+    "Terror",
+    "Indie",
+    "BritPop",
+    "Afro-Punk",
+    "Polsk Punk",
+    "Beat",
+    "Christian Gangsta Rap",
+    "Heavy Metal",
+    "Black Metal",
+    "Crossover",
+    "Contemporary Christian",
+    "Christian Rock",
+    "Merengue",
+    "Salsa",
+    "Thrash Metal",
+    "Anime",
+    "JPop",
+    "Synthpop",
+    "Abstract",
+    "Art Rock",
+    "Baroque",
+    "Bhangra",
+    "Big Beat",
+    "Breakbeat",
+    "Chillout",
+    "Downtempo",
+    "Dub",
+    "EBM",
+    "Eclectic",
+    "Electro",
+    "Electroclash",
+    "Emo",
+    "Experimental",
+    "Garage",
+    "Global",
+    "IDM",
+    "Illbient",
+    "Industro-Goth",
+    "Jam Band",
+    "Krautrock",
+    "Leftfield",
+    "Lounge",
+    "Math Rock",
+    "New Romantic",
+    "Nu-Breakz",
+    "Post-Punk",
+    "Post-Rock",
+    "Psytrance",
+    "Shoegaze",
+    "Space Rock",
+    "Trop Rock",
+    "World Music",
+    "Neoclassical",
+    "Audiobook",
+    "Audio Theatre",
+    "Neue Deutsche Welle",
+    "Podcast",
+    "Indie Rock",
+    "G-Funk",
+    "Dubstep",
+    "Garage Rock",
+    "Psybient",
+];
+
 pub fn get_genre(g: u8) -> String {
-    if g >= 80 {
-        return "".to_string();
-    } else {
-        return [
-            "Blues",
-            "Classic Rock",
-            "Country",
-            "Dance",
-            "Disco",
-            "Funk",
-            "Grunge",
-            "Hip-Hop",
-            "Jazz",
-            "Metal",
-            "New Age",
-            "Oldies",
-            "Other",
-            "Pop",
-            "R&B",
-            "Rap",
-            "Reggae",
-            "Rock",
-            "Techno",
-            "Industrial",
-            "Alternative",
-            "Ska",
-            "Death Metal",
-            "Pranks",
-            "Soundtrack",
-            "Euro-Techno",
-            "Ambient",
-            "Trip-Hop",
-            "Vocal",
-            "Jazz+Funk",
-            "Fusion",
-            "Trance",
-            "Classical",
-            "Instrumental",
-            "Acid",
-            "House",
-            "Game",
-            "Sound Clip",
-            "Gospel",
-            "Noise",
-            "AlternRock",
-            "Bass",
-            "Soul",
-            "Punk",
-            "Space",
-            "Meditative",
-            "Instrumental Pop",
-            "Instrumental Rock",
-            "Ethnic",
-            "Gothic",
-            "Darkwave",
-            "Techno-Industrial",
-            "Electronic",
-            "Pop-Folk",
-            "Eurodance",
-            "Dream",
-            "Southern Rock",
-            "Comedy",
-            "Cult",
-            "Gangsta",
-            "Top 40",
-            "Christian Rap",
-            "Pop/Funk",
-            "Jungle",
-            "Native American",
-            "Cabaret",
-            "New Wave",
-            "Psychedelic",
-            "Rave",
-            "Showtunes",
-            "Trailer",
-            "Lo-Fi",
-            "Tribal",
-            "Acid Punk",
-            "Acid Jazz",
-            "Polka",
-            "Retro",
-            "Musical",
-            "Rock & Roll",
-            "Hard Rock",
-        ][g as usize]
-            .to_string();
+    GENRES.get(g as usize).unwrap_or(&"").to_string()
+}
+
+// reverse lookup for get_genre, 0xFF means "no genre"
+pub fn get_genre_id(name: &str) -> u8 {
+    match GENRES
+        .iter()
+        .position(|g| g.eq_ignore_ascii_case(name.trim()))
+    {
+        Some(i) => i as u8,
+        None => 0xFF,
     }
 }
 
@@ -121,8 +245,8 @@ pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
             album: TagOption::Some(decode_iso_8859_1(&arr[63..63 + 30])),
             date: DateTime::from_iso_8601(decode_iso_8859_1(&arr[93..93 + 4]).as_str()).into(),
             comment: TagOption::Some(decode_iso_8859_1(&arr[97..97 + 28])),
-            track_number: match arr[125] {
-                0 => TagOption::Some(arr[126] as i64),
+            track_number: match (arr[125], arr[126]) {
+                (0, x) if x != 0 => TagOption::Some(x as i64),
                 _ => TagOption::None,
             },
             genre: TagOption::Some(get_genre(arr[127])),
@@ -133,5 +257,68 @@ pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
     }
 }
 
+// write a fixed-size latin-1 field, truncating or zero-padding as needed
+fn write_field(vec: &mut Vec<u8>, text: &TagOption<String>, length: usize) {
+    let mut field = match text {
+        TagOption::Some(ref x) => encode_iso_8859_1(x),
+        _ => Vec::new(),
+    };
+    field.resize(length, 0);
+    vec.extend_from_slice(&field);
+}
+
+// render a 128 byte ID3v1.1 tag
+// ID3v1.1 steals the last two bytes of the comment for the track number,
+// so only do that when the track number actually fits
+pub fn render(tags: &Tags) -> Vec<u8> {
+    let mut vec = Vec::with_capacity(128);
+    vec.extend_from_slice(b"TAG");
+
+    write_field(&mut vec, &tags.title, 30);
+    write_field(&mut vec, &tags.artist, 30);
+    write_field(&mut vec, &tags.album, 30);
+
+    let year = match tags.date {
+        TagOption::Some(DateTime { year: Some(y), .. }) if (0..=9999).contains(&y) => {
+            TagOption::Some(format!("{:04}", y))
+        }
+        _ => TagOption::None,
+    };
+    write_field(&mut vec, &year, 4);
+
+    match tags.track_number {
+        TagOption::Some(x) if (1..=0xFF).contains(&x) => {
+            write_field(&mut vec, &tags.comment, 28);
+            vec.push(0x00);
+            vec.push(x as u8);
+        }
+        _ => write_field(&mut vec, &tags.comment, 30),
+    }
+
+    vec.push(match tags.genre {
+        TagOption::Some(ref x) => get_genre_id(x),
+        _ => 0xFF,
+    });
+
+    vec
+}
+
+// update the ID3v1 tag at the end of the file in place,
+// or append a new one if there is none
+pub fn set<T: Read + Write + Seek>(file: &mut T, new: &Tags) -> Result<(), Error> {
+    let (old, position) = if has_id3v1(file) {
+        (get(file)?, std::io::SeekFrom::End(-128))
+    } else {
+        (Tags::none(), std::io::SeekFrom::End(0))
+    };
+
+    let tags = delta(&old, new);
+
+    file.seek(position)?;
+    file.write_all(&render(&tags))?;
+    file.flush()?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests;
@@ -2,6 +2,7 @@ use crate::DateTime;
 use crate::TagOption;
 use crate::Tags;
 use std::fs::File;
+use std::io::Cursor;
 
 #[test]
 fn id3v1_tags_test() {
@@ -23,3 +24,69 @@ fn id3v1_tags_test() {
     };
     assert_eq!(tag, orig);
 }
+
+#[test]
+fn id3v1_render_test() {
+    let tags = Tags {
+        title: TagOption::Some("A title that is far too long for ID3v1".to_string()),
+        artist: TagOption::Some("Artist Name".to_string()),
+        album: TagOption::Some("Album Name".to_string()),
+        genre: TagOption::Some("classical".to_string()),
+        date: DateTime::from_iso_8601("2017-05-01").into(),
+        track_number: TagOption::Some(12),
+        comment: TagOption::Some("Comment æÖÆ 👀".to_string()),
+        ..Default::default()
+    };
+    let vec = super::render(&tags);
+    assert_eq!(vec.len(), 128);
+
+    let tag = super::get(&mut Cursor::new(&vec)).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("A title that is far too long f".to_string()),
+        artist: TagOption::Some("Artist Name".to_string()),
+        album: TagOption::Some("Album Name".to_string()),
+        genre: TagOption::Some("Classical".to_string()),
+        date: DateTime::from_iso_8601("2017").into(),
+        track_number: TagOption::Some(12),
+        comment: TagOption::Some("Comment æÖÆ ?".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(tag, ideal);
+}
+
+#[test]
+fn id3v1_set_test() {
+    // no tag yet, so one should be appended
+    let mut cursor = Cursor::new(vec![0xFF; 256]);
+    let tags = Tags {
+        title: TagOption::Some("Title".to_string()),
+        genre: TagOption::Some("Rock".to_string()),
+        ..Default::default()
+    };
+    super::set(&mut cursor, &tags).unwrap();
+    assert_eq!(cursor.get_ref().len(), 256 + 128);
+
+    // existing tag gets updated in place, keeping untouched fields
+    let tags = Tags {
+        artist: TagOption::Some("Artist".to_string()),
+        ..Tags::mixed()
+    };
+    super::set(&mut cursor, &tags).unwrap();
+    assert_eq!(cursor.get_ref().len(), 256 + 128);
+
+    let tag = super::get(&mut cursor).unwrap();
+    assert_eq!(tag.title, TagOption::Some("Title".to_string()));
+    assert_eq!(tag.artist, TagOption::Some("Artist".to_string()));
+    assert_eq!(tag.genre, TagOption::Some("Rock".to_string()));
+}
+
+#[test]
+fn id3v1_genre_test() {
+    assert_eq!(super::get_genre(0), "Blues");
+    assert_eq!(super::get_genre(79), "Hard Rock");
+    assert_eq!(super::get_genre(145), "Anime");
+    assert_eq!(super::get_genre(191), "Psybient");
+    assert_eq!(super::get_genre(192), "");
+    assert_eq!(super::get_genre_id("psybient"), 191);
+    assert_eq!(super::get_genre_id("Not A Genre"), 0xFF);
+}
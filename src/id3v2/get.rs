@@ -36,8 +36,10 @@ pub fn tags<T: Read + Seek>(input: &mut T, header: &structure::Header) -> Result
 
             "TIT1" | "TT1" => t.grouping = TagOption::Some(read::string(input, f.size)),
 
-            // give up on that ID3v1 compatibility
-            "TCON" | "TCO" => t.genre = TagOption::Some(read::string(input, f.size)),
+            // resolve ID3v1 genre references
+            "TCON" | "TCO" => {
+                t.genre = TagOption::Some(regex::get_genre(&read::string(input, f.size)))
+            }
 
             // read in date for 2.4 or at least get the year
             "TDRC" | "TYER" | "TYE" => date = read::string(input, f.size),
@@ -65,6 +67,7 @@ pub fn tags<T: Read + Seek>(input: &mut T, header: &structure::Header) -> Result
                 None => (),
             },
 
+            "TCOP" | "TCR" => t.copyright = TagOption::Some(read::string(input, f.size)),
             "TSOT" | "TST" => t.sort_title = TagOption::Some(read::string(input, f.size)),
             "TSOA" | "TSA" => t.sort_album = TagOption::Some(read::string(input, f.size)),
             "TSOP" | "TSP" => t.sort_artist = TagOption::Some(read::string(input, f.size)),
@@ -168,8 +171,10 @@ pub fn tags_unsynch_v4<T: Read + Seek>(
 
             "TIT1" => t.grouping = TagOption::Some(read_string_shim_v4(input, f.size, u)),
 
-            // give up on that ID3v1 compatibility
-            "TCON" => t.genre = TagOption::Some(read_string_shim_v4(input, f.size, u)),
+            // resolve ID3v1 genre references
+            "TCON" => {
+                t.genre = TagOption::Some(regex::get_genre(&read_string_shim_v4(input, f.size, u)))
+            }
 
             // read in date for 2.4 or at least get the year
             "TDRC" => date = read_string_shim_v4(input, f.size, u),
@@ -209,6 +214,7 @@ pub fn tags_unsynch_v4<T: Read + Seek>(
                 }
             },
 
+            "TCOP" => t.copyright = TagOption::Some(read_string_shim_v4(input, f.size, u)),
             "TSOT" => t.sort_title = TagOption::Some(read_string_shim_v4(input, f.size, u)),
             "TSOA" => t.sort_album = TagOption::Some(read_string_shim_v4(input, f.size, u)),
             "TSOP" => t.sort_artist = TagOption::Some(read_string_shim_v4(input, f.size, u)),
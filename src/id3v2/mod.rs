@@ -4,8 +4,12 @@ use std::io::prelude::*;
 
 use crate::DateTime;
 use crate::Error;
+use crate::Id3v2Restrictions;
 use crate::TagOption;
 use crate::Tags;
+use crate::WriteOptions;
+
+use crc::crc32::checksum_ieee;
 
 mod get;
 mod read;
@@ -13,11 +17,53 @@ mod regex;
 mod structure;
 mod tools;
 
+// frames that don't add up to the CRC are read all the same,
+// crc_matches tells whether they do
 pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
     let header = read::header(input)?;
     get_internal(input, &header)
 }
 
+// whether the frames add up to the CRC in the extended header,
+// None if there is no CRC
+pub fn crc_matches<T: Read + Seek>(input: &mut T) -> Result<Option<bool>, Error> {
+    let header = read::header(input)?;
+    Ok(header
+        .extended_header
+        .and_then(|x| x.crc32.map(|_| !x.crc_mismatch)))
+}
+
+// the restrictions from the extended header, if there are any
+pub fn restrictions<T: Read + Seek>(input: &mut T) -> Result<Option<Id3v2Restrictions>, Error> {
+    let header = read::header(input)?;
+    Ok(header
+        .extended_header
+        .and_then(|x| x.restrictions)
+        .map(Id3v2Restrictions::from))
+}
+
+// where the data after a leading ID3v2 tag starts (0 without one), leaving
+// the input there; only the header is looked at, so any tag we can't read
+// is skipped just the same, whatever container follows it
+pub fn skip<T: Read + Seek>(input: &mut T) -> Result<u64, Error> {
+    let mut arr = Vec::new();
+    input.seek(std::io::SeekFrom::Start(0))?;
+    input.take(10).read_to_end(&mut arr)?;
+
+    let start = match arr.as_slice() {
+        [b'I', b'D', b'3', major, minor, flags, size @ ..] if *major != 0xFF && *minor != 0xFF => {
+            match tools::decode_synch_int(size) {
+                Ok(x) => 10 + x as u64 + if flags & 0x10 != 0 { 10 } else { 0 },
+                Err(_) => 0,
+            }
+        }
+        _ => 0,
+    };
+
+    input.seek(std::io::SeekFrom::Start(start))?;
+    Ok(start)
+}
+
 macro_rules! write_string {
     ($vec:ident, $str:expr, $id:expr) => {{
         // frame id
@@ -47,11 +93,19 @@ pub fn set<R: Read + Seek, W: Write>(
     input: &mut R,
     output: &mut W,
     new: &Tags,
+) -> Result<(), Error> {
+    set_with_options(input, output, new, &WriteOptions::default())
+}
+
+pub fn set_with_options<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    new: &Tags,
+    options: &WriteOptions,
 ) -> Result<(), Error> {
     use crate::tools::tags::delta;
-    use std::io::SeekFrom;
     let (old_size, tags) = match read::header(input) {
-        // id3v2 tag found
+        // id3v2 tag found; frames that don't match the CRC are kept, as get reads them
         Ok(h) => {
             let old = get_internal(input, &h)?;
             (h.size as u64 + 10, delta(&old, new))
@@ -74,6 +128,13 @@ pub fn set<R: Read + Seek, W: Write>(
     vec.extend_from_slice(b"ID3\x04\x00\x00"); // [0..6] id3v24; no flags
     vec.extend_from_slice(b"\x00\x00\x00\x00"); // [6..10] - reserve for size
 
+    // extended header of 12 bytes with just a CRC, which is filled in last
+    if options.id3v2_crc {
+        vec[5] |= 0b01000000;
+        vec.extend_from_slice(b"\x00\x00\x00\x0C\x01\x20\x05");
+        vec.extend_from_slice(&[0; 5]);
+    }
+
     write_text_frame!(vec, tags.title, "TIT2");
     write_text_frame!(vec, tags.artist, "TPE1");
     write_text_frame!(vec, tags.album, "TALB");
@@ -116,6 +177,8 @@ pub fn set<R: Read + Seek, W: Write>(
         vec.extend_from_slice(b"TCMP\x00\x00\x00\x03\x00\x00\x031\x00");
     }
 
+    write_text_frame!(vec, tags.copyright, "TCOP");
+
     write_text_frame!(vec, tags.sort_title, "TSOT");
     write_text_frame!(vec, tags.sort_artist, "TSOP");
     write_text_frame!(vec, tags.sort_album, "TSOA");
@@ -183,8 +246,14 @@ pub fn set<R: Read + Seek, W: Write>(
         vec[6 + i] = size[i];
     }
 
+    // of the frames and the padding, in 35 synchsafe bits
+    if options.id3v2_crc {
+        let crc = tools::encode_synch_int(checksum_ieee(&vec[22..]), true)?;
+        vec[17..22].copy_from_slice(&crc);
+    }
+
     // move to the start of music data in the input
-    input.seek(SeekFrom::Start(old_size + 10))?;
+    skip(input)?;
 
     // start writing
     output.write(&vec)?;
@@ -193,7 +262,18 @@ pub fn set<R: Read + Seek, W: Write>(
     Ok(())
 }
 
+// copy the file without its ID3v2 tag
+pub fn remove<R: Read + Seek, W: Write>(input: &mut R, output: &mut W) -> Result<(), Error> {
+    skip(input)?;
+    std::io::copy(input, output)?;
+    output.flush()?;
+    Ok(())
+}
+
 fn get_internal<T: Read + Seek>(input: &mut T, header: &structure::Header) -> Result<Tags, Error> {
+    // frames start after the extended header
+    let extended_size = header.extended_header.as_ref().map_or(0, |x| x.size);
+
     // with older id3 versions, run unsynch on the whole tag
     if header.is_unsynchronized && header.version < 4 {
         // read the tag into memory
@@ -204,9 +284,13 @@ fn get_internal<T: Read + Seek>(input: &mut T, header: &structure::Header) -> Re
 
         // then use cursor to read and seek across the tags
         let mut unsynch_input = std::io::Cursor::new(vec);
+        unsynch_input.set_position(extended_size as u64);
 
         return get::tags(&mut unsynch_input, header);
-    } else if header.is_unsynchronized && header.version == 4 {
+    }
+
+    input.seek(std::io::SeekFrom::Current(extended_size as i64))?;
+    if header.is_unsynchronized && header.version == 4 {
         return get::tags_unsynch_v4(input, header);
     } else {
         return get::tags(input, header);
@@ -11,6 +11,8 @@ use crate::Image;
 use crate::tools::encoding::*;
 use crate::tools::tag_error;
 
+use crc::crc32::checksum_ieee;
+
 // allow some functions to fail silently here
 // invalidate just a single frame instead of the whole tag
 macro_rules! try_silent {
@@ -343,14 +345,91 @@ pub fn header<T: Read + Seek>(input: &mut T) -> Result<structure::Header, Error>
         return Err(tag_error("Unsupported flags found in ID3 header"));
     }
 
-    // TODO: Find examples of MP3s with extended headers to add support for this
-    if has_extended_header {
-        return Err(tag_error(
-            "Extended ID3 headers are currently not supported",
-        ));
+    // in v2.2, the same flag means compression, which nobody ever defined
+    if has_extended_header && header.version == 2 {
+        return Err(tag_error("Compressed ID3v2.2 tags are not supported"));
     }
 
     header.size = decode_synch_int(&arr[6..10])?;
 
+    if has_extended_header {
+        header.extended_header = Some(extended_header(input, &header)?);
+        input.seek(std::io::SeekFrom::Start(10))?;
+    }
+
     Ok(header)
 }
+
+// the extended header at the start of the tag, with its CRC checked
+// (a mismatch is up to the caller);
+// in v2.3 it's unsynchronized along with the rest of the tag, so its size
+// counts the bytes after undoing that
+fn extended_header<T: Read>(
+    input: &mut T,
+    header: &structure::Header,
+) -> Result<structure::ExtendedHeader, Error> {
+    let mut vec = vec![0; header.size as usize];
+    input.read_exact(&mut vec)?;
+    if header.version == 3 && header.is_unsynchronized {
+        undo_unsynch(&mut vec);
+    }
+
+    let invalid = || tag_error("Invalid ID3v2 extended header");
+    let mut extended = structure::ExtendedHeader::default();
+
+    // the part of the tag the CRC is calculated on
+    let data = match header.version {
+        // size (without itself), flags, padding size, then the CRC if flagged
+        3 => {
+            if vec.len() < 10 {
+                return Err(invalid());
+            }
+            let size = decode_int_be_u32(&vec[0..4]) as usize + 4;
+            let has_crc = vec[4] & 0x80 != 0;
+            let padding = decode_int_be_u32(&vec[6..10]) as usize;
+            if size < if has_crc { 14 } else { 10 } || size + padding > vec.len() {
+                return Err(invalid());
+            }
+
+            extended.size = size as u32;
+            if has_crc {
+                extended.crc32 = Some(decode_int_be_u32(&vec[10..14]));
+            }
+            // the frames, without padding
+            &vec[size..vec.len() - padding]
+        }
+        // size (with itself), number of flag bytes (always 1), flags,
+        // then the data of each flag that is set, led by its length
+        4 => {
+            if vec.len() < 6 || vec[4] != 1 {
+                return Err(invalid());
+            }
+            let size = decode_synch_int(&vec[0..4])? as usize;
+            if size < 6 || size > vec.len() {
+                return Err(invalid());
+            }
+
+            let mut rest = &vec[6..size];
+            for flag in [0x40, 0x20, 0x10].iter().filter(|&x| vec[5] & x != 0) {
+                let length = *rest.first().ok_or_else(invalid)? as usize;
+                let value = rest.get(1..1 + length).ok_or_else(invalid)?;
+                match (flag, length) {
+                    (0x40, 0) => extended.tag_is_update = true,
+                    // 35 bits, synchsafe
+                    (0x20, 5) => extended.crc32 = Some(decode_synch_int(value)?),
+                    (0x10, 1) => extended.restrictions = Some(value[0]),
+                    _ => return Err(invalid()),
+                }
+                rest = &rest[1 + length..];
+            }
+
+            extended.size = size as u32;
+            // the frames and padding
+            &vec[size..]
+        }
+        _ => return Err(tag_error("Unknown ID3 version")),
+    };
+
+    extended.crc_mismatch = extended.crc32.is_some_and(|x| checksum_ieee(data) != x);
+    Ok(extended)
+}
@@ -20,3 +20,68 @@ pub fn get_track_number(input: &str) -> (String, String) {
         ),
     }
 }
+
+// resolve a single numeric genre reference
+fn genre_reference(reference: &str) -> Option<String> {
+    use crate::id3v1::get_genre;
+    match reference {
+        "RX" => Some("Remix".to_string()),
+        "CR" => Some("Cover".to_string()),
+        _ => match reference.parse::<u8>() {
+            Ok(i) if !get_genre(i).is_empty() => Some(get_genre(i)),
+            _ => None,
+        },
+    }
+}
+
+// normalise TCON values into plain genre names:
+//     "(17)", "17"       => "Rock"
+//     "(17)Rock Opera"   => "Rock Opera" (text refines the reference)
+//     "(RX)", "(CR)"     => "Remix", "Cover"
+//     "((Fake)"          => "(Fake)"
+// multiple genres (v2.4 null-separated or several references) are joined with " / "
+pub fn get_genre(input: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^\((\d+|RX|CR)\)").unwrap();
+    }
+
+    let mut genres: Vec<String> = Vec::new();
+
+    for part in input.split(" / ") {
+        let mut rest = part.trim();
+
+        // bare v2.4 references
+        if let Some(g) = genre_reference(rest) {
+            genres.push(g);
+            continue;
+        }
+
+        let mut references = Vec::new();
+        while let Some(c) = RE.captures(rest) {
+            let whole = c.get(0).map_or(0, |m| m.end());
+            if let Some(g) = c.get(1).and_then(|m| genre_reference(m.as_str())) {
+                references.push(g);
+            }
+            rest = &rest[whole..];
+        }
+
+        // escaped leading parenthesis
+        if rest.starts_with("((") {
+            rest = &rest[1..];
+        }
+
+        if !rest.is_empty() {
+            references.pop();
+            references.push(rest.to_string());
+        }
+        genres.append(&mut references);
+    }
+
+    genres.dedup();
+
+    // nothing could be resolved, so keep whatever was there
+    if genres.is_empty() {
+        return input.trim().to_string();
+    }
+    genres.join(" / ")
+}
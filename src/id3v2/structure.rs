@@ -12,11 +12,14 @@ pub struct Header {
     pub extended_header: Option<ExtendedHeader>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ExtendedHeader {
+    // in bytes, the size field included
     pub size: u32,
     pub tag_is_update: bool,
     pub crc32: Option<u32>,
+    // the frames don't add up to the CRC
+    pub crc_mismatch: bool,
     pub restrictions: Option<u8>,
 }
 
@@ -1,3 +1,4 @@
+mod regex;
 mod tools;
 
 use std::fs;
@@ -28,6 +29,123 @@ fn write_test() {
     assert_eq!(tags, ideal);
 }
 
+#[test]
+fn skip_test() {
+    use std::io::Cursor;
+
+    // the tag is kept where it is, and the rest of the file follows it
+    let mut input = File::open("testfiles/id3v24-utf8-png.mp3").unwrap();
+    let mut output = Vec::new();
+    super::set(&mut input, &mut output, &Tags::mixed()).unwrap();
+    let mut original = Vec::new();
+    input.seek(std::io::SeekFrom::Start(0)).unwrap();
+    input.read_to_end(&mut original).unwrap();
+    let start = super::skip(&mut Cursor::new(&original)).unwrap() as usize;
+    let new_start = super::skip(&mut Cursor::new(&output)).unwrap() as usize;
+    assert_eq!(&output[new_start..], &original[start..]);
+
+    // a footer, and a tag we couldn't read anyway
+    let mut cursor = Cursor::new(b"ID3\x04\x00\x50\x00\x00\x01\x00rest".to_vec());
+    assert_eq!(super::skip(&mut cursor).unwrap(), 148);
+    assert_eq!(cursor.position(), 148);
+
+    let mut cursor = Cursor::new(b"ID3\x04\x00\x00\x00\x00\x80\x00".to_vec());
+    assert_eq!(super::skip(&mut cursor).unwrap(), 0);
+    assert_eq!(super::skip(&mut Cursor::new(b"fLaC")).unwrap(), 0);
+}
+
+#[test]
+fn extended_header_test() {
+    use crate::Id3v2Restrictions;
+    use crate::WriteOptions;
+    use crc::crc32::checksum_ieee;
+    use std::io::Cursor;
+
+    let tags = Tags {
+        title: TagOption::Some("Title".to_string()),
+        ..Tags::mixed()
+    };
+
+    // a CRC of everything after the extended header
+    let options = WriteOptions {
+        id3v2_crc: true,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    super::set_with_options(&mut Cursor::new(Vec::new()), &mut output, &tags, &options).unwrap();
+    assert_eq!(output[5], 0x40);
+    assert_eq!(&output[10..17], b"\x00\x00\x00\x0C\x01\x20\x05");
+    let new_tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(new_tags.title, TagOption::Some("Title".to_string()));
+    assert_eq!(
+        super::restrictions(&mut Cursor::new(&output)).unwrap(),
+        None
+    );
+    assert_eq!(
+        super::crc_matches(&mut Cursor::new(&output)).unwrap(),
+        Some(true)
+    );
+
+    // "Title" becomes "title", which is read and rewritten all the same,
+    // but no longer matches the CRC until it is
+    output[33] ^= 0x20;
+    let broken_tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(broken_tags.title, TagOption::Some("title".to_string()));
+    assert_eq!(
+        super::crc_matches(&mut Cursor::new(&output)).unwrap(),
+        Some(false)
+    );
+    let mut rewritten = Vec::new();
+    super::set_with_options(
+        &mut Cursor::new(&output),
+        &mut rewritten,
+        &Tags::mixed(),
+        &options,
+    )
+    .unwrap();
+    let rewritten_tags = super::get(&mut Cursor::new(&rewritten)).unwrap();
+    assert_eq!(rewritten_tags.title, TagOption::Some("title".to_string()));
+    assert_eq!(
+        super::crc_matches(&mut Cursor::new(&rewritten)).unwrap(),
+        Some(true)
+    );
+
+    // v2.3: size without itself, flags, padding size and the CRC
+    // of the frames without the padding
+    let frame = b"TIT2\x00\x00\x00\x06\x00\x00\x00Title";
+    let mut tag = b"ID3\x03\x00\x40\x00\x00\x00\x00".to_vec();
+    tag.extend_from_slice(b"\x00\x00\x00\x0A\x80\x00\x00\x00\x00\x10");
+    tag.extend_from_slice(&crate::tools::encode_int_be_u32(checksum_ieee(frame)));
+    tag.extend_from_slice(frame);
+    tag.resize(tag.len() + 16, 0);
+    tag[9] = tag.len() as u8 - 10;
+    let v23_tags = super::get(&mut Cursor::new(&tag)).unwrap();
+    assert_eq!(v23_tags.title, TagOption::Some("Title".to_string()));
+
+    // v2.4 restrictions: 64 frames in 128 KB, UTF-8, 30 characters,
+    // PNG or JPEG of exactly 64x64 pixels
+    let mut tag = b"ID3\x04\x00\x40\x00\x00\x00\x00".to_vec();
+    tag.extend_from_slice(b"\x00\x00\x00\x08\x01\x10\x01\x7F");
+    tag.extend_from_slice(b"TIT2\x00\x00\x00\x06\x00\x00\x03Title");
+    tag[9] = tag.len() as u8 - 10;
+    let ideal = Id3v2Restrictions {
+        max_frames: 64,
+        max_size: 128 * 1024,
+        latin1_or_utf8: true,
+        max_text_length: Some(30),
+        png_or_jpeg: true,
+        max_image_size: Some(64),
+        exact_image_size: true,
+    };
+    assert_eq!(
+        super::restrictions(&mut Cursor::new(&tag)).unwrap(),
+        Some(ideal)
+    );
+    let v24_tags = super::get(&mut Cursor::new(&tag)).unwrap();
+    assert_eq!(v24_tags.title, TagOption::Some("Title".to_string()));
+    assert_eq!(super::crc_matches(&mut Cursor::new(&tag)).unwrap(), None);
+}
+
 #[test]
 fn id3v22_read_test() {
     let (tags, image) = read_data_in("id3v22-utf16le-jpeg");
@@ -113,6 +231,7 @@ fn id3v24_read_test() {
         date: DateTime::from_iso_8601("2017").into(),
         track_number: TagOption::Some(5),
         genre: TagOption::Some("Anime".to_string()),
+        copyright: TagOption::Some("(P)FlyingDog, Inc.".to_string()),
         front_cover: TagOption::Some(image),
         ..Default::default()
     };
@@ -150,6 +269,7 @@ fn id3v24_unsynchronized_read_test() {
         genre: TagOption::Some("Classical".to_string()),
 
         comment: TagOption::Some("Test Comments".to_string()),
+        copyright: TagOption::Some("Test Copyright".to_string()),
 
         sort_title: TagOption::Some("Test Title Sort Order".to_string()),
         sort_album: TagOption::Some("Test Album Sort Order".to_string()),
@@ -0,0 +1,19 @@
+use crate::id3v2::regex::*;
+
+#[test]
+fn genre_test() {
+    assert_eq!(get_genre("Anime"), "Anime");
+    assert_eq!(get_genre("(17)"), "Rock");
+    assert_eq!(get_genre("17"), "Rock");
+    assert_eq!(get_genre("(17)Rock"), "Rock");
+    assert_eq!(get_genre("(4)Eurodisco"), "Eurodisco");
+    assert_eq!(get_genre("(RX)"), "Remix");
+    assert_eq!(get_genre("(CR)"), "Cover");
+    assert_eq!(get_genre("(145)"), "Anime");
+    assert_eq!(get_genre("(17)(18)"), "Rock / Techno");
+    assert_eq!(get_genre("17 / Anime"), "Rock / Anime");
+    assert_eq!(get_genre("((Fake)"), "(Fake)");
+    // unknown references are left alone
+    assert_eq!(get_genre("(200)"), "(200)");
+    assert_eq!(get_genre("200"), "200");
+}
@@ -0,0 +1,253 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::Error;
+
+use crate::tools::decode_int_be_u64;
+use crate::tools::decode_int_le_u64;
+use crate::tools::encode_int_be_u64;
+use crate::tools::encode_int_le_u64;
+use crate::tools::encoding::decode_iso_8859_1;
+use crate::tools::tag_error;
+
+// how a flavour of IFF writes its sizes: RIFF in little endian,
+// AIFF in big endian, and DSDIFF in 64 bits
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub big_endian: bool,
+    // bytes in a size field, 4 or 8
+    pub size_width: usize,
+}
+
+pub const RIFF: Layout = Layout {
+    big_endian: false,
+    size_width: 4,
+};
+pub const AIFF: Layout = Layout {
+    big_endian: true,
+    size_width: 4,
+};
+pub const DSDIFF: Layout = Layout {
+    big_endian: true,
+    size_width: 8,
+};
+
+pub struct Chunk {
+    pub id: [u8; 4],
+    // start of the chunk header
+    pub start: u64,
+    // size of the contents, without the padding byte
+    pub size: u64,
+    // id and size
+    header: u64,
+}
+impl Chunk {
+    pub fn body(&self) -> u64 {
+        self.start + self.header
+    }
+    // chunks are padded to an even length
+    pub fn end(&self) -> u64 {
+        self.body() + self.size + self.size % 2
+    }
+    pub fn is_id3(&self) -> bool {
+        &self.id == b"ID3 " || &self.id == b"id3 "
+    }
+}
+
+pub struct Form {
+    // RIFF, FORM and the like
+    pub id: [u8; 4],
+    // WAVE, AIFF and the like
+    pub kind: [u8; 4],
+    // end of the container, anything after it is left alone
+    pub end: u64,
+    pub length: u64,
+    pub chunks: Vec<Chunk>,
+}
+
+impl Layout {
+    fn decode_size(&self, vec: &[u8]) -> u64 {
+        match self.big_endian {
+            true => decode_int_be_u64(vec),
+            false => decode_int_le_u64(vec),
+        }
+    }
+    fn encode_size(&self, size: u64) -> Vec<u8> {
+        match self.big_endian {
+            true => encode_int_be_u64(size)[8 - self.size_width..].to_vec(),
+            false => encode_int_le_u64(size)[..self.size_width].to_vec(),
+        }
+    }
+    fn header(&self) -> usize {
+        4 + self.size_width
+    }
+
+    // the container header, without any chunks yet
+    pub fn read_form<T: Read + Seek>(&self, input: &mut T) -> Result<Form, Error> {
+        let length = input.seek(SeekFrom::End(0))?;
+        input.seek(SeekFrom::Start(0))?;
+
+        let mut vec = vec![0; self.header() + 4];
+        input.read_exact(&mut vec)?;
+
+        let mut form = Form {
+            id: [0; 4],
+            kind: [0; 4],
+            end: 0,
+            length,
+            chunks: Vec::new(),
+        };
+        form.id.copy_from_slice(&vec[0..4]);
+        form.kind.copy_from_slice(&vec[self.header()..]);
+        let size = self.decode_size(&vec[4..self.header()]);
+        form.end = std::cmp::min((self.header() as u64).saturating_add(size), length);
+        Ok(form)
+    }
+
+    // walk the chunks of a container; check sees each chunk header with the
+    // input at its contents, and may correct its size or the container's end
+    pub fn read_chunks<T, F>(
+        &self,
+        input: &mut T,
+        form: &mut Form,
+        mut check: F,
+    ) -> Result<(), Error>
+    where
+        T: Read + Seek,
+        F: FnMut(&mut T, &mut Chunk, &mut u64) -> Result<(), Error>,
+    {
+        let mut header = vec![0; self.header()];
+        let mut pos = self.header() as u64 + 4;
+        while pos + self.header() as u64 <= form.end {
+            input.seek(SeekFrom::Start(pos))?;
+            input.read_exact(&mut header)?;
+
+            let mut chunk = Chunk {
+                id: [0; 4],
+                start: pos,
+                size: self.decode_size(&header[4..]),
+                header: self.header() as u64,
+            };
+            chunk.id.copy_from_slice(&header[0..4]);
+            check(input, &mut chunk, &mut form.end)?;
+
+            // the padding byte may be missing at the very end
+            match chunk.body().checked_add(chunk.size) {
+                Some(end) if end <= form.end => (),
+                _ => return Err(tag_error("Invalid chunk size")),
+            }
+            pos = chunk.end();
+            form.chunks.push(chunk);
+        }
+        Ok(())
+    }
+
+    // the chunks inside a chunk, starting at pos
+    pub fn collect(&self, vec: &[u8], mut pos: usize) -> Vec<([u8; 4], Vec<u8>)> {
+        let mut items = Vec::new();
+        while pos + self.header() <= vec.len() {
+            let mut id: [u8; 4] = [0; 4];
+            id.copy_from_slice(&vec[pos..pos + 4]);
+            let size = self.decode_size(&vec[pos + 4..pos + self.header()]) as usize;
+            let end = std::cmp::min((pos + self.header()).saturating_add(size), vec.len());
+            items.push((id, vec[pos + self.header()..end].to_vec()));
+            pos = end + size % 2;
+        }
+        items
+    }
+
+    // a chunk with its header and padding
+    pub fn new_chunk(&self, id: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut vec = id.to_vec();
+        vec.append(&mut self.encode_size(data.len() as u64));
+        vec.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            vec.push(0);
+        }
+        vec
+    }
+
+    // the container header for contents of the given size
+    pub fn form_header(&self, form: &Form, size: u64) -> Result<Vec<u8>, Error> {
+        if self.size_width == 4 && size > 0xFFFF_FFFF {
+            return Err(tag_error(&format!(
+                "File too large for {}",
+                decode_iso_8859_1(&form.kind).trim_end()
+            )));
+        }
+        let mut vec = form.id.to_vec();
+        vec.append(&mut self.encode_size(size));
+        vec.extend_from_slice(&form.kind);
+        Ok(vec)
+    }
+}
+
+pub fn read_chunk<T: Read + Seek>(input: &mut T, chunk: &Chunk) -> Result<Vec<u8>, Error> {
+    let mut vec = Vec::new();
+    input.seek(SeekFrom::Start(chunk.body()))?;
+    input.take(chunk.size).read_to_end(&mut vec)?;
+    Ok(vec)
+}
+
+pub enum Piece {
+    Copy(u64, u64),
+    Data(Vec<u8>),
+}
+
+// the contents of a new container: chunks for which replace gives something
+// are replaced by it (and dropped if it's empty), everything else is copied
+pub fn pieces<F>(form: &Form, mut replace: F) -> Result<Vec<Piece>, Error>
+where
+    F: FnMut(&Chunk) -> Result<Option<Vec<u8>>, Error>,
+{
+    let mut pieces = Vec::new();
+    for chunk in form.chunks.iter() {
+        match replace(chunk)? {
+            Some(vec) => pieces.push(Piece::Data(vec)),
+            None => {
+                // the last chunk might miss its padding
+                let end = std::cmp::min(chunk.end(), form.end);
+                pieces.push(Piece::Copy(chunk.start, end - chunk.start));
+                if (end - chunk.start) % 2 == 1 {
+                    pieces.push(Piece::Data(vec![0]));
+                }
+            }
+        }
+    }
+    Ok(pieces)
+}
+
+// the size of a container with these contents, its kind included
+pub fn size(pieces: &[Piece]) -> u64 {
+    pieces.iter().fold(4, |size, p| match p {
+        Piece::Copy(_, length) => size + length,
+        Piece::Data(vec) => size + vec.len() as u64,
+    })
+}
+
+// write the contents after the container header,
+// and then whatever trailed the container
+pub fn write_pieces<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    form: &Form,
+    mut pieces: Vec<Piece>,
+) -> Result<(), Error> {
+    pieces.push(Piece::Copy(form.end, form.length - form.end));
+
+    for piece in pieces.iter() {
+        match piece {
+            Piece::Copy(start, length) => {
+                input.seek(SeekFrom::Start(*start))?;
+                std::io::copy(&mut input.by_ref().take(*length), output)?;
+            }
+            Piece::Data(vec) => output.write_all(vec)?,
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
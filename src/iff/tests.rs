@@ -0,0 +1,83 @@
+use std::io::Cursor;
+
+#[test]
+fn layout_test() {
+    // the same chunk in each flavour, padded to an even length
+    assert_eq!(
+        super::RIFF.new_chunk(b"abcd", b"xyz"),
+        b"abcd\x03\x00\x00\x00xyz\x00"
+    );
+    assert_eq!(
+        super::AIFF.new_chunk(b"abcd", b"xyz"),
+        b"abcd\x00\x00\x00\x03xyz\x00"
+    );
+    assert_eq!(
+        super::DSDIFF.new_chunk(b"abcd", b"xyz"),
+        b"abcd\x00\x00\x00\x00\x00\x00\x00\x03xyz\x00"
+    );
+
+    let vec = [
+        &b"LIST"[..],
+        &super::RIFF.new_chunk(b"abcd", b"xyz"),
+        &super::RIFF.new_chunk(b"efgh", b"uv"),
+    ]
+    .concat();
+    let items = super::RIFF.collect(&vec, 4);
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0], (*b"abcd", b"xyz".to_vec()));
+    assert_eq!(items[1], (*b"efgh", b"uv".to_vec()));
+}
+
+#[test]
+fn form_test() {
+    // a last chunk without its padding, then something after the container
+    let mut vec = b"FORM\x00\x00\x00\x21TEST".to_vec();
+    vec.append(&mut super::AIFF.new_chunk(b"abcd", b"xyz"));
+    vec.extend_from_slice(b"efgh\x00\x00\x00\x09123456789tail");
+
+    let mut cursor = Cursor::new(&vec);
+    let mut form = super::AIFF.read_form(&mut cursor).unwrap();
+    super::AIFF
+        .read_chunks(&mut cursor, &mut form, |_, _, _| Ok(()))
+        .unwrap();
+    assert_eq!(&form.kind, b"TEST");
+    assert_eq!(form.end, 41);
+    let ids: Vec<[u8; 4]> = form.chunks.iter().map(|c| c.id).collect();
+    assert_eq!(ids, vec![*b"abcd", *b"efgh"]);
+
+    // the last chunk gets its padding, the tail stays
+    let pieces = super::pieces(&form, |_| Ok(None)).unwrap();
+    let size = super::size(&pieces);
+    let mut output = super::AIFF.form_header(&form, size).unwrap();
+    super::write_pieces(&mut cursor, &mut output, &form, pieces).unwrap();
+    assert_eq!(output.len(), vec.len() + 1);
+    assert_eq!(&output[4..8], &super::AIFF.encode_size(size)[..]);
+    assert_eq!(size, 4 + 12 + 17 + 1);
+
+    // a chunk running past the end of the container
+    vec[31] = 0x0A;
+    let mut cursor = Cursor::new(&vec);
+    let mut form = super::AIFF.read_form(&mut cursor).unwrap();
+    assert!(super::AIFF
+        .read_chunks(&mut cursor, &mut form, |_, _, _| Ok(()))
+        .is_err());
+
+    let form = super::RIFF
+        .read_form(&mut Cursor::new(b"RIFF\xFF\xFF\xFF\xFFWAVE"))
+        .unwrap();
+    assert!(super::RIFF.form_header(&form, 0x1_0000_0000).is_err());
+}
+
+#[test]
+fn oversized_chunk_test() {
+    // a DSDIFF chunk size that overflows when added to its position
+    let mut vec = b"FRM8\x00\x00\x00\x00\x00\x00\x00\x1CDSD ".to_vec();
+    vec.extend_from_slice(b"abcd\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xF0");
+    vec.extend_from_slice(b"12345678");
+
+    let mut cursor = Cursor::new(&vec);
+    let mut form = super::DSDIFF.read_form(&mut cursor).unwrap();
+    assert!(super::DSDIFF
+        .read_chunks(&mut cursor, &mut form, |_, _, _| Ok(()))
+        .is_err());
+}
@@ -2,15 +2,38 @@
 extern crate lazy_static;
 
 mod types;
+pub use crate::types::CueIndex;
+pub use crate::types::CueSheet;
+pub use crate::types::CueTrack;
 use crate::types::DateTime;
+pub use crate::types::Id3v2Restrictions;
 use crate::types::Image;
+pub use crate::types::Properties;
 use crate::types::TagOption;
+pub use crate::types::TagTypes;
 use crate::types::Tags;
+pub use crate::types::WriteOptions;
 
+mod adts;
+mod aiff;
+mod ape;
+mod asf;
+mod cue;
+mod dff;
+mod dsf;
 mod flac;
 mod id3v1;
 mod id3v2;
+mod iff;
+mod lyrics3;
 mod m4a;
+mod matroska;
+mod monkeys_audio;
+mod mpeg;
+mod musepack;
+mod ogg;
+mod wav;
+mod wavpack;
 
 mod vorbis;
 
@@ -20,9 +43,29 @@ mod tools;
 #[cfg(test)]
 mod tests;
 
+pub use crate::flac::metadata::FlacBlock;
+pub use crate::flac::metadata::FlacCueIndex;
+pub use crate::flac::metadata::FlacCueSheet;
+pub use crate::flac::metadata::FlacCueTrack;
+pub use crate::flac::metadata::FlacMetadata;
+pub use crate::flac::metadata::FlacPicture;
+pub use crate::flac::metadata::SeekPoint;
+pub use crate::flac::metadata::StreamInfo;
+pub use crate::flac::metadata::VorbisComment;
+
+pub use crate::dispatch::check_id3v2_crc;
+pub use crate::dispatch::copy_with_tags;
+pub use crate::dispatch::get_cue_sheet;
+pub use crate::dispatch::get_flac_metadata;
 pub use crate::dispatch::get_front_cover;
+pub use crate::dispatch::get_id3v2_restrictions;
+pub use crate::dispatch::get_properties;
 pub use crate::dispatch::get_tags;
+pub use crate::dispatch::remove_tags;
+pub use crate::dispatch::set_cue_sheet;
+pub use crate::dispatch::set_flac_metadata;
 pub use crate::dispatch::set_tags;
+pub use crate::dispatch::set_tags_with_options;
 
 use std::io;
 
@@ -0,0 +1,160 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::Error;
+use crate::TagOption;
+use crate::Tags;
+
+use crate::id3v1;
+use crate::tools::encoding::decode_iso_8859_1;
+
+extern crate regex;
+use self::regex::Regex;
+
+// Lyrics3v1 tags are at most this long between LYRICSBEGIN and LYRICSEND
+const V1_MAX_LYRICS: u64 = 5100;
+
+// locate a Lyrics3 tag ending at `end` (right before ID3v1) as (start, length)
+pub fn locate<T: Read + Seek>(input: &mut T, end: u64) -> Result<Option<(u64, u64)>, Error> {
+    if end < 15 + 11 {
+        return Ok(None);
+    }
+
+    // v2: "LYRICSBEGIN" fields, six digit size and "LYRICS200"
+    let mut arr: [u8; 15] = [0; 15];
+    input.seek(SeekFrom::Start(end - 15))?;
+    input.read_exact(&mut arr)?;
+
+    if &arr[6..] == b"LYRICS200" {
+        let size = match std::str::from_utf8(&arr[..6])
+            .ok()
+            .and_then(|x| x.parse::<u64>().ok())
+        {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        if size + 15 > end {
+            return Ok(None);
+        }
+        let start = end - 15 - size;
+        return Ok(match is_begin(input, start)? {
+            true => Some((start, size + 15)),
+            false => None,
+        });
+    }
+
+    // v1: "LYRICSBEGIN" lyrics "LYRICSEND", we have to search for the start
+    if &arr[6..] == b"LYRICSEND" {
+        let length = std::cmp::min(end - 9, V1_MAX_LYRICS + 11);
+        let mut vec = vec![0; length as usize];
+        input.seek(SeekFrom::Start(end - 9 - length))?;
+        input.read_exact(&mut vec)?;
+
+        if let Some(i) = vec.windows(11).position(|w| w == b"LYRICSBEGIN") {
+            let start = end - 9 - length + i as u64;
+            return Ok(Some((start, end - start)));
+        }
+    }
+
+    Ok(None)
+}
+
+fn is_begin<T: Read + Seek>(input: &mut T, start: u64) -> Result<bool, Error> {
+    let mut arr: [u8; 11] = [0; 11];
+    input.seek(SeekFrom::Start(start))?;
+    input.read_exact(&mut arr)?;
+    Ok(&arr == b"LYRICSBEGIN")
+}
+
+// the fields of a Lyrics3v2 tag: three letter id, five digit size and the data
+fn fields(mut vec: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut fields = Vec::new();
+    while vec.len() >= 8 {
+        let size = match std::str::from_utf8(&vec[3..8])
+            .ok()
+            .and_then(|x| x.parse::<usize>().ok())
+        {
+            Some(x) if x <= vec.len() - 8 => x,
+            _ => break,
+        };
+        fields.push((&vec[0..3], &vec[8..8 + size]));
+        vec = &vec[8 + size..];
+    }
+    fields
+}
+
+fn text(vec: &[u8]) -> TagOption<String> {
+    match decode_iso_8859_1(vec).replace("\r\n", "\n") {
+        ref x if x.is_empty() => TagOption::None,
+        x => TagOption::Some(x),
+    }
+}
+
+// the lyrics of a Lyrics3 tag in front of ID3v1, along with the extended
+// title, album and artist of v2 (which go beyond ID3v1's 30 characters)
+pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    let mut tags = Tags::none();
+    if !id3v1::has_id3v1(input) {
+        return Ok(tags);
+    }
+    let end = input.seek(SeekFrom::End(-128))?;
+    let (start, length) = match locate(input, end)? {
+        Some(x) => x,
+        None => return Ok(tags),
+    };
+
+    let mut vec = vec![0; length as usize];
+    input.seek(SeekFrom::Start(start))?;
+    input.read_exact(&mut vec)?;
+
+    // v1 is nothing but lyrics
+    if vec.ends_with(b"LYRICSEND") {
+        tags.lyrics = text(&vec[11..vec.len() - 9]);
+        return Ok(tags);
+    }
+
+    let fields = fields(&vec[11..vec.len() - 15]);
+    for &(id, value) in fields.iter() {
+        match id {
+            b"LYR" => tags.lyrics = text(value),
+            b"ETT" => tags.title = text(value),
+            b"EAL" => tags.album = text(value),
+            b"EAR" => tags.artist = text(value),
+            _ => (),
+        }
+    }
+
+    // the second indication says whether there are [mm:ss] time stamps
+    let timestamps = fields
+        .iter()
+        .any(|&(id, value)| id == b"IND" && value.get(1) == Some(&b'1'));
+    if let (true, TagOption::Some(ref mut lyrics)) = (timestamps, &mut tags.lyrics) {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"\[\d\d:\d\d\]").unwrap();
+        }
+        *lyrics = RE.replace_all(lyrics, "").into_owned();
+    }
+
+    Ok(tags)
+}
+
+// ID3v1 fields with the longer Lyrics3v2 versions they were cut from;
+// one that doesn't start the same way is out of date, and ID3v1 wins
+pub fn extend(mut id3v1: Tags, lyrics3: Tags) -> Tags {
+    for (field, extended) in [
+        (&mut id3v1.title, lyrics3.title),
+        (&mut id3v1.album, lyrics3.album),
+        (&mut id3v1.artist, lyrics3.artist),
+    ] {
+        if let (TagOption::Some(x), TagOption::Some(y)) = (&*field, &extended) {
+            if y.starts_with(x.trim_end()) {
+                *field = extended;
+            }
+        }
+    }
+    id3v1.lyrics = lyrics3.lyrics;
+    id3v1
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,95 @@
+use crate::TagOption;
+use crate::Tags;
+use std::io::Cursor;
+
+// a Lyrics3v2 tag from its fields
+fn v2(fields: &[(&str, &str)]) -> Vec<u8> {
+    let mut vec = b"LYRICSBEGIN".to_vec();
+    for (id, value) in fields {
+        vec.extend_from_slice(format!("{}{:05}{}", id, value.len(), value).as_bytes());
+    }
+    let size = vec.len();
+    vec.extend_from_slice(format!("{:06}LYRICS200", size).as_bytes());
+    vec
+}
+
+fn file(lyrics3: &[u8]) -> Vec<u8> {
+    let mut vec = vec![0xFF; 100];
+    vec.extend_from_slice(lyrics3);
+    let id3v1 = Tags {
+        title: TagOption::Some("A Title That Is Too Long For ".to_string()),
+        ..Tags::none()
+    };
+    vec.extend_from_slice(&crate::id3v1::render(&id3v1));
+    vec
+}
+
+#[test]
+fn lyrics3v2_test() {
+    let tag = v2(&[
+        ("IND", "11"),
+        ("LYR", "[00:01]First line\r\n[00:05]Second line"),
+        ("ETT", "A Title That Is Too Long For ID3v1"),
+        ("EAR", "Artist"),
+    ]);
+    let mut cursor = Cursor::new(file(&tag));
+    assert_eq!(
+        super::locate(&mut cursor, 100 + tag.len() as u64).unwrap(),
+        Some((100, tag.len() as u64))
+    );
+
+    let tags = super::get(&mut cursor).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("A Title That Is Too Long For ID3v1".to_string()),
+        artist: TagOption::Some("Artist".to_string()),
+        lyrics: TagOption::Some("First line\nSecond line".to_string()),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+
+    // without time stamps, brackets stay
+    let tag = v2(&[("IND", "10"), ("LYR", "[00:01] stays")]);
+    let tags = super::get(&mut Cursor::new(file(&tag))).unwrap();
+    assert_eq!(tags.lyrics, TagOption::Some("[00:01] stays".to_string()));
+}
+
+#[test]
+fn lyrics3v1_test() {
+    let tag = b"LYRICSBEGINSome lyrics\r\nMore lyricsLYRICSEND";
+    let tags = super::get(&mut Cursor::new(file(tag))).unwrap();
+    assert_eq!(
+        tags.lyrics,
+        TagOption::Some("Some lyrics\nMore lyrics".to_string())
+    );
+
+    assert_eq!(
+        super::get(&mut Cursor::new(file(b""))).unwrap(),
+        Tags::none()
+    );
+    assert_eq!(
+        super::get(&mut Cursor::new(vec![0; 200])).unwrap(),
+        Tags::none()
+    );
+}
+
+#[test]
+fn extend_test() {
+    let id3v1 = Tags {
+        title: TagOption::Some("A Title That Is Too Long For".to_string()),
+        album: TagOption::Some("Changed Album".to_string()),
+        ..Tags::none()
+    };
+    let lyrics3 = Tags {
+        title: TagOption::Some("A Title That Is Too Long For ID3v1".to_string()),
+        album: TagOption::Some("Old Album".to_string()),
+        lyrics: TagOption::Some("Lyrics".to_string()),
+        ..Tags::none()
+    };
+    let ideal = Tags {
+        title: TagOption::Some("A Title That Is Too Long For ID3v1".to_string()),
+        album: TagOption::Some("Changed Album".to_string()),
+        lyrics: TagOption::Some("Lyrics".to_string()),
+        ..Tags::none()
+    };
+    assert_eq!(super::extend(id3v1, lyrics3), ideal);
+}
@@ -1,18 +1,25 @@
 use std;
 
 use std::io::prelude::*;
+use std::io::SeekFrom;
 
 use crate::DateTime;
 use crate::Image;
 use crate::TagOption;
 use crate::Tags;
+use crate::WriteOptions;
 
 use crate::Error;
 
+use std::collections::BTreeMap;
+
 use crate::tools::decode_int_be_u32;
+use crate::tools::decode_int_be_u64;
 use crate::tools::encode_int_be_u16;
 use crate::tools::encode_int_be_u32;
+use crate::tools::encode_int_be_u64;
 use crate::tools::encoding::decode_iso_8859_1;
+use crate::tools::encoding::decode_utf8;
 use crate::tools::encoding::encode_iso_8859_1;
 use crate::tools::tag_error;
 use crate::tools::tags;
@@ -23,17 +30,29 @@ struct Atom {
     size: u64,
     start: u64,
     end: u64,
+    // 8 bytes, or 16 with a 64-bit extended size
+    header: u64,
 }
 impl Atom {
+    // size 1 means a 64-bit size follows the name, if we were given those bytes
+    // size 0 (atom runs to the end of its parent) is left for the caller to resolve
     fn new(input: &[u8], start: u64) -> Atom {
-        let size = decode_int_be_u32(&input[0..4]) as u64;
+        let (size, header) = match decode_int_be_u32(&input[0..4]) {
+            1 if input.len() >= 16 => (decode_int_be_u64(&input[8..16]), 16),
+            x => (x as u64, 8),
+        };
         Atom {
             name: decode_iso_8859_1(&input[4..8]),
             size: size,
             start: start,
             end: start + size,
+            header,
         }
     }
+    // start of the atom contents
+    fn body(&self) -> u64 {
+        self.start + self.header
+    }
     #[allow(dead_code)]
     fn print(&self) {
         println!(
@@ -43,39 +62,169 @@ impl Atom {
     }
 }
 
-pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
-    let mut buf: [u8; 8] = [0; 8];
+// read the atom header at pos, returning None on EOF
+// the input is left at the start of the atom contents
+fn read_atom<T: Read + Seek>(input: &mut T, pos: u64) -> Result<Option<(Atom, Vec<u8>)>, Error> {
+    let mut header = vec![0; 8];
 
-    let mut pos = 0;
+    let read = input.read(&mut header)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    input.read_exact(&mut header[read..])?;
 
-    let ilst;
+    // 64-bit extended size
+    if decode_int_be_u32(&header[0..4]) == 1 {
+        header.resize(16, 0);
+        input.read_exact(&mut header[8..])?;
+    }
 
-    // locate moov.udta.meta.ilst
-    loop {
-        if input.read(&mut buf)? == 0 {
-            // if we hit EOF without locating the tags atom
-            return Ok(Tags::none());
-        }
-        let atom = Atom::new(&buf, pos);
-        pos += buf.len() as u64;
+    let mut atom = Atom::new(&header, pos);
+
+    // atom extends to the end of the file
+    if atom.size == 0 {
+        let end = input.seek(SeekFrom::End(0))?;
+        input.seek(SeekFrom::Start(atom.body()))?;
+        atom.size = end - pos;
+        atom.end = end;
+    }
+
+    if atom.size < atom.header {
+        return Err(tag_error("Found invalid atom while parsing m4a structure"));
+    }
 
-        if atom.size < 8 && atom.size > 1 {
-            return Err(tag_error("Found invalid atom while parsing m4a structure"));
+    Ok(Some((atom, header)))
+}
+
+// read a whole atom into memory, header included
+// an atom running to the end of the file gets a real size, so it can be moved
+fn read_atom_data<T: Read>(input: &mut T, atom: &Atom, header: &[u8]) -> Result<Vec<u8>, Error> {
+    let header = match decode_int_be_u32(&header[0..4]) {
+        0 if atom.size <= 0xFFFF_FFFF => {
+            [&encode_int_be_u32(atom.size as u32)[..], &header[4..8]].concat()
         }
+        0 => [
+            &encode_int_be_u32(1)[..],
+            &header[4..8],
+            &encode_int_be_u64(atom.size + 8),
+        ]
+        .concat(),
+        _ => header.to_vec(),
+    };
+    let mut vec = vec![0; (atom.size - atom.header) as usize + header.len()];
+    vec[..header.len()].copy_from_slice(&header);
+    input.read_exact(&mut vec[header.len()..])?;
+    Ok(vec)
+}
 
-        match atom.name.as_str() {
-            "moov" | "udta" => (),
-            // skip the four bytes at the start of meta
-            "meta" => pos = input.seek(std::io::SeekFrom::Current(4))?,
-            "ilst" => {
-                ilst = atom;
-                break;
+// the atom filling a whole buffer, such as an in-memory moov
+fn root(vec: &[u8]) -> Atom {
+    let mut atom = Atom::new(vec, 0);
+    if atom.size == 0 {
+        atom.size = vec.len() as u64;
+        atom.end = atom.size;
+    }
+    atom
+}
+
+// make sure we're looking at an MPEG-4 family file
+fn check_ftyp<T: Read + Seek>(input: &mut T) -> Result<(), Error> {
+    input.seek(SeekFrom::Start(0))?;
+
+    let result = match read_atom(input, 0)? {
+        Some((ref atom, _)) if atom.name.as_str() == "ftyp" => {
+            let mut vec = vec![0; std::cmp::min(atom.size - atom.header, 0x1000) as usize];
+            input.read_exact(&mut vec)?;
+
+            // major brand, minor version, then compatible brands
+            let mut brands = vec
+                .chunks(4)
+                .enumerate()
+                .filter(|&(i, b)| i != 1 && b.len() == 4)
+                .map(|(_, b)| b);
+
+            match brands.any(is_known_brand) {
+                true => Ok(()),
+                false => Err(tag_error("Unsupported MPEG-4 brand in ftyp atom")),
             }
-            _ => pos = input.seek(std::io::SeekFrom::Current(atom.size as i64 - 8))?,
         }
+        // older QuickTime files don't have to start with ftyp
+        Some((ref atom, _)) => match atom.name.as_str() {
+            "moov" | "mdat" | "free" | "skip" | "wide" | "pnot" => Ok(()),
+            _ => Err(tag_error(
+                "ftyp atom not found (is this a valid MPEG-4 file?)",
+            )),
+        },
+        None => Err(tag_error(
+            "ftyp atom not found (is this a valid MPEG-4 file?)",
+        )),
+    };
+
+    input.seek(SeekFrom::Start(0))?;
+    result
+}
+
+fn is_known_brand(brand: &[u8]) -> bool {
+    match brand {
+        b"M4A " | b"M4B " | b"M4P " | b"M4V " | b"M4VH" | b"M4VP" | b"mp41" | b"mp42" | b"mp71"
+        | b"isom" | b"iso2" | b"iso3" | b"iso4" | b"iso5" | b"iso6" | b"avc1" | b"f4v "
+        | b"f4a " | b"f4b " | b"MSNV" | b"NDAS" | b"dash" | b"qt  " => true,
+        // 3GPP and 3GPP2
+        _ => brand.starts_with(b"3g"),
     }
+}
 
-    collect_tags(input, &mut pos, &ilst)
+pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    check_ftyp(input)?;
+
+    // locate moov and read it into memory
+    let mut pos = 0;
+    let moov = loop {
+        match read_atom(input, pos)? {
+            Some((atom, header)) => {
+                if atom.name.as_str() == "moov" {
+                    input.seek(SeekFrom::Start(atom.body()))?;
+                    break read_atom_data(input, &atom, &header)?;
+                }
+                pos = input.seek(SeekFrom::Start(atom.end))?;
+            }
+            // if we hit EOF without locating the tags atom
+            None => return Ok(Tags::none()),
+        }
+    };
+
+    // iTunes style moov.udta.meta.ilst first
+    let mut tags = match find_ilst(&moov) {
+        Some(ilst) => {
+            let mut cursor = std::io::Cursor::new(&moov[..]);
+            let mut pos = cursor.seek(SeekFrom::Start(ilst.body()))?;
+            collect_tags(&mut cursor, &mut pos, &ilst)?
+        }
+        None => Tags::none(),
+    };
+
+    // then fill in the gaps from QuickTime style moov.meta
+    collect_mdta(&moov, &mut tags);
+
+    Ok(tags)
+}
+
+// moov.udta.meta.ilst in an in-memory moov atom
+fn find_ilst(moov: &[u8]) -> Option<Atom> {
+    let root = root(moov);
+    let udta = find_atom(moov, root.body(), root.end, "udta")?;
+    let meta = find_atom(moov, udta.body(), udta.end, "meta")?;
+    find_atom(moov, meta_children(moov, &meta), meta.end, "ilst")
+}
+
+// iTunes meta atoms have four bytes of version and flags before their children,
+// QuickTime ones start with the handler straight away
+fn meta_children(vec: &[u8], meta: &Atom) -> u64 {
+    let body = meta.body() as usize;
+    match vec.get(body + 4..body + 8) {
+        Some(x) if x == b"hdlr" => meta.body(),
+        _ => meta.body() + 4,
+    }
 }
 
 macro_rules! write_data {
@@ -129,233 +278,617 @@ macro_rules! overwrite_u32 {
     }};
 }
 
-pub fn set<R: Read + Seek, W: Write>(
+pub fn set_with_options<R: Read + Seek, W: Write>(
     input: &mut R,
     output: &mut W,
     new: &Tags,
+    options: &WriteOptions,
 ) -> Result<(), Error> {
-    let mut buf: [u8; 8] = [0; 8];
+    rewrite(input, output, options.faststart, |moov| {
+        set_ilst(moov, new)?;
+        set_mdta(moov, new)
+    })
+}
+
+// copy the file, modifying the moov atom with f on the way
+// and fixing up the chunk offsets of every track afterwards
+fn rewrite<R, W, F>(input: &mut R, output: &mut W, faststart: bool, f: F) -> Result<(), Error>
+where
+    R: Read + Seek,
+    W: Write,
+    F: FnOnce(&mut Vec<u8>) -> Result<(), Error>,
+{
+    check_ftyp(input)?;
+
+    // collect the top level layout
+    let mut atoms = Vec::new();
+    let mut pos = 0;
+    while let Some((atom, header)) = read_atom(input, pos)? {
+        pos = input.seek(SeekFrom::Start(atom.end))?;
+        atoms.push((atom, header));
+    }
 
-    // construct new file in memory
-    let mut vec: Vec<u8> = Vec::new();
+    let moov_index = match atoms.iter().position(|(a, _)| a.name.as_str() == "moov") {
+        Some(i) => i,
+        None => return Err(tag_error("Could not find moov atom")),
+    };
+
+    let mut moov = {
+        let (ref atom, ref header) = atoms[moov_index];
+        input.seek(SeekFrom::Start(atom.body()))?;
+        read_atom_data(input, atom, header)?
+    };
+    f(&mut moov)?;
+
+    // output order - optionally move moov in front of the media data
+    let mut order: Vec<usize> = (0..atoms.len()).collect();
+    if faststart {
+        if let Some(mdat) = atoms.iter().position(|(a, _)| a.name.as_str() == "mdat") {
+            if mdat < moov_index {
+                order.remove(moov_index);
+                order.insert(mdat, moov_index);
+            }
+        }
+    }
 
+    // work out how far each atom moves
+    let mut shifts = Vec::with_capacity(atoms.len());
     let mut pos = 0;
+    for &i in order.iter() {
+        let atom = &atoms[i].0;
+        shifts.push((atom.start, atom.end, pos as i64 - atom.start as i64));
+        pos += match i == moov_index {
+            true => moov.len() as u64,
+            false => atom.size,
+        };
+    }
 
-    // to adjust sizes
-    let mut moov_offset = 0;
-    let mut udta_offset = 0;
-    let mut meta_offset = 0;
+    patch_chunk_offsets(&mut moov, |offset| {
+        shifts
+            .iter()
+            .find(|&&(start, end, _)| start <= offset && offset < end)
+            .map_or(0, |&(_, _, shift)| shift)
+    })?;
+
+    for &i in order.iter() {
+        if i == moov_index {
+            output.write_all(&moov)?;
+        } else {
+            let (ref atom, ref header) = atoms[i];
+            input.seek(SeekFrom::Start(atom.body()))?;
+            output.write_all(header)?;
+            std::io::copy(&mut input.by_ref().take(atom.size - atom.header), output)?;
+        }
+    }
 
-    // to fix media data offsets
-    let mut stco_offset = 0;
+    output.flush()?;
+    Ok(())
+}
 
-    // locate moov.udta.meta.ilst
-    // and moov.trak.mdia.minf.stbl.stco
-    // at the same time, copy all other data
-    loop {
-        input.read(&mut buf)?;
+// replace the contents of moov.udta.meta.ilst in an in-memory moov atom
+fn set_ilst(moov: &mut Vec<u8>, new: &Tags) -> Result<(), Error> {
+    create_ilst(moov);
+
+    let root = root(moov);
+
+    let udta = match find_atom(moov, root.body(), root.end, "udta") {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    // meta has four bytes of version and flags before its children
+    let meta = match find_atom(moov, udta.body(), udta.end, "meta") {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let ilst = match find_atom(moov, meta_children(moov, &meta), meta.end, "ilst") {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+
+    let old = {
+        let mut cursor = std::io::Cursor::new(&moov[..]);
+        let mut pos = cursor.seek(SeekFrom::Start(ilst.body()))?;
+        collect_tags(&mut cursor, &mut pos, &ilst)?
+    };
+    let vec = get_ilst_data(&tags::delta(&old, new))?;
+
+    let delta = vec.len() as i64 - (ilst.size - ilst.header) as i64;
+    moov.splice(ilst.body() as usize..ilst.end as usize, vec);
+
+    for parent in [&ilst, &meta, &udta, &root].iter() {
+        adjust_size(moov, parent.start as usize, delta);
+    }
 
-        let atom = Atom::new(&buf, pos);
-        pos += buf.len() as u64;
+    Ok(())
+}
 
-        if atom.size < 8 && atom.size > 1 {
-            return Err(tag_error("Found invalid atom while parsing m4a structure"));
-        }
+// QuickTime metadata keys and the text fields they map onto,
+// com.apple.quicktime.creationdate is handled separately as a date
+const MDTA_KEYS: [&str; 7] = [
+    "com.apple.quicktime.title",
+    "com.apple.quicktime.artist",
+    "com.apple.quicktime.album",
+    "com.apple.quicktime.composer",
+    "com.apple.quicktime.genre",
+    "com.apple.quicktime.comment",
+    "com.apple.quicktime.description",
+];
+const MDTA_DATE: &str = "com.apple.quicktime.creationdate";
+
+fn mdta_field<'a>(tags: &'a mut Tags, key: &str) -> Option<&'a mut TagOption<String>> {
+    match key {
+        "com.apple.quicktime.title" => Some(&mut tags.title),
+        "com.apple.quicktime.artist" => Some(&mut tags.artist),
+        "com.apple.quicktime.album" => Some(&mut tags.album),
+        "com.apple.quicktime.composer" => Some(&mut tags.composer),
+        "com.apple.quicktime.genre" => Some(&mut tags.genre),
+        "com.apple.quicktime.comment" => Some(&mut tags.comment),
+        "com.apple.quicktime.description" => Some(&mut tags.description),
+        _ => None,
+    }
+}
 
-        match atom.name.as_str() {
-            "moov" => {
-                // remember location of moov atom start
-                moov_offset = vec.len();
-                // and add the atom marker
-                vec.extend_from_slice(&buf);
-            }
+// moov.meta with an mdta handler, and where its children start
+fn find_mdta_meta(moov: &[u8]) -> Option<(Atom, u64)> {
+    let root = root(moov);
+    let meta = find_atom(moov, root.body(), root.end, "meta")?;
+    let start = meta_children(moov, &meta);
+
+    // version, flags and pre-defined come before the handler type
+    let hdlr = find_atom(moov, start, meta.end, "hdlr")?;
+    let body = hdlr.body() as usize;
+    match moov.get(body + 8..body + 12) {
+        Some(x) if x == b"mdta" => Some((meta, start)),
+        _ => None,
+    }
+}
 
-            // stco route - write the atom start, but then traverse the atom
-            "trak" | "mdia" | "minf" | "stbl" => {
-                vec.extend_from_slice(&buf);
-            }
-            // finally, stco itself
-            "stco" => {
-                stco_offset = vec.len();
-                vec.extend_from_slice(&buf);
-                let s = vec.len();
-                vec.resize(s + atom.size as usize - 8, 0);
-                pos += input.read(&mut vec[s..])? as u64;
-            }
+// the key names from a keys atom, item n of the ilst refers to key n - 1
+fn collect_keys(vec: &[u8], keys: &Atom) -> Vec<String> {
+    // skip version, flags and the entry count
+    children(vec, keys.body() + 8, keys.end)
+        .iter()
+        .map(|a| decode_utf8(&vec[a.body() as usize..a.end as usize]))
+        .collect()
+}
 
-            // conclusion
-            "mdat" => {
-                // if we hit mdat and we have no stco, don't write
-                if stco_offset == 0 {
-                    return Err(tag_error("Could not find stco atom"));
-                }
-                let mdat_original = input.seek(std::io::SeekFrom::Current(0))? as i64;
-                let mdat_offset = vec.len() as i64;
-                let mdat_delta = (mdat_offset - mdat_original) as i32;
-
-                // we're sitting on top of the data.
-                // finalize the new structure and write
-
-                // fix stco with the mdat offset we calculated
-                let stco_count = decode_int_be_u32(&vec[stco_offset + 12..stco_offset + 12 + 4]);
-                for i in 0..stco_count {
-                    let offset = stco_offset + 12 + 4 + (4 * i) as usize;
-                    overwrite_u32!(
-                        &encode_int_be_u32(
-                            (decode_int_be_u32(&vec[offset..offset + 4]) as i64 + mdat_delta as i64)
-                                as u32
-                        ),
-                        &mut vec[offset..offset + 4]
-                    );
-                }
-                // write the thing
-                output.write(&vec)?;
-                std::io::copy(input, output)?;
-                break;
-            }
+// the keys and ilst items of a QuickTime meta atom as (key, item contents) pairs
+fn collect_mdta_items(moov: &[u8], meta: &Atom, start: u64) -> Vec<(String, Vec<u8>)> {
+    let keys = match find_atom(moov, start, meta.end, "keys") {
+        Some(x) => collect_keys(moov, &x),
+        None => return Vec::new(),
+    };
+    let ilst = match find_atom(moov, start, meta.end, "ilst") {
+        Some(x) => x,
+        None => return Vec::new(),
+    };
+
+    let mut items = Vec::new();
+    for item in children(moov, ilst.body(), ilst.end) {
+        // the atom name is the 1-based key index
+        let index = decode_int_be_u32(&moov[item.start as usize + 4..item.start as usize + 8]);
+        if let Some(key) = keys.get((index as usize).wrapping_sub(1)) {
+            items.push((
+                key.clone(),
+                moov[item.body() as usize..item.end as usize].to_vec(),
+            ));
+        }
+    }
+    items
+}
 
-            // ilst route
-            "udta" => {
-                // etc for all ancestors of ilst
-                udta_offset = vec.len();
-                vec.extend_from_slice(&buf);
+// fill the fields that are still None from QuickTime style metadata
+fn collect_mdta(moov: &[u8], tags: &mut Tags) {
+    let (meta, start) = match find_mdta_meta(moov) {
+        Some(x) => x,
+        None => return,
+    };
+
+    for (key, data) in collect_mdta_items(moov, &meta, start) {
+        let text = match collect_data_text(&data) {
+            Some(x) => x,
+            None => continue,
+        };
+        if key.as_str() == MDTA_DATE {
+            if tags.date.is_none() {
+                tags.date = DateTime::from_iso_8601(text.as_str()).into();
             }
-            "meta" => {
-                meta_offset = vec.len();
-                vec.extend_from_slice(&buf);
-                vec.extend_from_slice(&[0, 0, 0, 0]);
-                pos = input.seek(std::io::SeekFrom::Current(4))?;
+        } else if let Some(field) = mdta_field(tags, key.as_str()) {
+            if field.is_none() {
+                *field = TagOption::Some(text);
             }
-            "ilst" => {
-                let ilst_offset = vec.len();
-
-                vec.extend_from_slice(&buf);
-                let ilst_size_orig = decode_int_be_u32(&buf[0..4]);
-
-                let tags = tags::delta(&collect_tags(input, &mut pos, &atom)?, new);
-
-                // write tags
-                write_text!(vec, tags.title, "©nam");
-                write_text!(vec, tags.album, "©alb");
-                write_text!(vec, tags.artist, "©art");
-                write_text!(vec, tags.album_artist, "aART");
-                write_text!(vec, tags.composer, "©wrt");
-                write_text!(vec, tags.grouping, "©grp");
-                write_text!(vec, tags.genre, "©gen");
-                if let TagOption::Some(ref t) = tags.date {
-                    let text = t.to_iso_8601();
-                    if text.as_str() != "" {
-                        write_data!(
-                            vec,
-                            text.as_str().as_bytes(),
-                            "©day",
-                            &[0, 0, 0, 1, 0, 0, 0, 0]
-                        );
-                    }
-                }
+        }
+    }
+}
 
-                // track + track total
-                vec.extend_from_slice(b"\x00\x00\x00\x1Etrkn");
-                vec.extend_from_slice(b"\x00\x00\x00\x16data");
-                vec.extend_from_slice(b"\x00\x00\x00\x00\x00\x00\x00\x00");
-                vec.extend_from_slice(b"\x00\x00");
-                vec.extend_from_slice(&tag_to_u16_encoded!(tags.track_number));
-                vec.extend_from_slice(&tag_to_u16_encoded!(tags.track_total));
-
-                // disc + disc total
-                vec.extend_from_slice(b"\x00\x00\x00\x1Edisk");
-                vec.extend_from_slice(b"\x00\x00\x00\x16data");
-                vec.extend_from_slice(b"\x00\x00\x00\x00\x00\x00\x00\x00");
-                vec.extend_from_slice(b"\x00\x00");
-                vec.extend_from_slice(&tag_to_u16_encoded!(tags.disc_number));
-                vec.extend_from_slice(&tag_to_u16_encoded!(tags.disc_total));
+// update the mapped keys of QuickTime style metadata, if the file has any
+fn set_mdta(moov: &mut Vec<u8>, new: &Tags) -> Result<(), Error> {
+    let (meta, start) = match find_mdta_meta(moov) {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+
+    let mut items = collect_mdta_items(moov, &meta, start);
+    let old = {
+        let mut tags = Tags::none();
+        collect_mdta(moov, &mut tags);
+        tags
+    };
+    let mut tags = tags::delta(&old, new);
+
+    // drop the mapped items and add back whatever is set
+    let mut values = Vec::new();
+    for key in MDTA_KEYS.iter() {
+        if let Some(TagOption::Some(x)) = mdta_field(&mut tags, key) {
+            values.push((*key, x.clone()));
+        }
+    }
+    if let TagOption::Some(ref x) = tags.date {
+        values.push((MDTA_DATE, x.to_iso_8601()));
+    }
+    items.retain(|(k, _)| k.as_str() != MDTA_DATE && mdta_field(&mut Tags::none(), k).is_none());
+    for (key, value) in values {
+        if value.as_str() != "" {
+            let data = [&[0, 0, 0, 1, 0, 0, 0, 0], value.as_bytes()].concat();
+            items.push((key.to_string(), new_atom("data", &data)));
+        }
+    }
 
-                write_data!(
-                    vec,
-                    &tag_to_u16_encoded!(tags.bpm),
-                    "tmpo",
-                    b"\x00\x00\x00\x15\x00\x00\x00\x00"
-                );
+    // rebuild keys and ilst, every remaining item gets its own key
+    let mut keys = vec![0; 4]; // version and flags
+    keys.extend_from_slice(&encode_int_be_u32(items.len() as u32));
+    let mut ilst = Vec::new();
+    for (i, (key, data)) in items.iter().enumerate() {
+        keys.append(&mut new_atom("mdta", key.as_bytes()));
 
-                if let TagOption::Some(true) = tags.is_compilation {
-                    write_data!(vec, &[0x01], "cpil", b"\x00\x00\x00\x15\x00\x00\x00\x00");
-                }
+        ilst.extend_from_slice(&encode_int_be_u32(data.len() as u32 + 8));
+        ilst.extend_from_slice(&encode_int_be_u32(i as u32 + 1));
+        ilst.extend_from_slice(data);
+    }
 
-                write_text!(vec, tags.comment, "©cmt");
-                write_text!(vec, tags.sort_title, "sonm");
-                write_text!(vec, tags.sort_album, "soal");
-                write_text!(vec, tags.sort_artist, "soar");
-                write_text!(vec, tags.sort_album_artist, "soaa");
-                write_text!(vec, tags.sort_composer, "soco");
-
-                // if we have image
-                if let TagOption::Some(ref i) = tags.front_cover {
-                    if i.is_some() {
-                        write_data!(
-                            vec,
-                            &match i {
-                                Image::PNG(ref x) | Image::JPEG(ref x) => x,
-                                _ => return Err(tag_error("Expected an image")),
-                            },
-                            "covr",
-                            // flag jpeg or png
-                            &[
-                                0,
-                                0,
-                                0,
-                                match i {
-                                    Image::JPEG(_) => 13,
-                                    Image::PNG(_) => 14,
-                                    _ => 0,
-                                },
-                                0,
-                                0,
-                                0,
-                                0
-                            ]
-                        );
-                    }
-                }
+    // the new meta keeps everything else in place
+    let mut vec = moov[meta.body() as usize..start as usize].to_vec();
+    for atom in children(moov, start, meta.end) {
+        match atom.name.as_str() {
+            "keys" | "ilst" => (),
+            _ => vec.extend_from_slice(&moov[atom.start as usize..atom.end as usize]),
+        }
+    }
+    vec.append(&mut new_atom("keys", &keys));
+    vec.append(&mut new_atom("ilst", &ilst));
+    let vec = new_atom("meta", &vec);
 
-                let ilst_delta =
-                    ((vec.len() as i64 - ilst_offset as i64) - ilst_size_orig as i64) as i32;
+    let delta = vec.len() as i64 - meta.size as i64;
+    moov.splice(meta.start as usize..meta.end as usize, vec);
+    adjust_size(moov, 0, delta);
 
-                overwrite_u32!(
-                    &encode_int_be_u32(
-                        (decode_int_be_u32(&vec[moov_offset..moov_offset + 4]) as i64
-                            + ilst_delta as i64) as u32
-                    ),
-                    &mut vec[moov_offset..moov_offset + 4]
-                );
-                overwrite_u32!(
-                    &encode_int_be_u32(
-                        (decode_int_be_u32(&vec[udta_offset..udta_offset + 4]) as i64
-                            + ilst_delta as i64) as u32
-                    ),
-                    &mut vec[udta_offset..udta_offset + 4]
-                );
-                overwrite_u32!(
-                    &encode_int_be_u32(
-                        (decode_int_be_u32(&vec[meta_offset..meta_offset + 4]) as i64
-                            + ilst_delta as i64) as u32
-                    ),
-                    &mut vec[meta_offset..meta_offset + 4]
-                );
-                overwrite_u32!(
-                    &encode_int_be_u32(
-                        (decode_int_be_u32(&vec[ilst_offset..ilst_offset + 4]) as i64
-                            + ilst_delta as i64) as u32
-                    ),
-                    &mut vec[ilst_offset..ilst_offset + 4]
-                );
+    Ok(())
+}
+
+// create whatever part of moov.udta.meta.ilst is missing
+fn create_ilst(moov: &mut Vec<u8>) {
+    let root = root(moov);
+
+    let udta = match find_atom(moov, root.body(), root.end, "udta") {
+        Some(x) => x,
+        None => return insert_atom(moov, &[root], new_atom("udta", &new_meta())),
+    };
+    let meta = match find_atom(moov, udta.body(), udta.end, "meta") {
+        Some(x) => x,
+        None => return insert_atom(moov, &[root, udta], new_meta()),
+    };
+    if find_atom(moov, meta_children(moov, &meta), meta.end, "ilst").is_none() {
+        insert_atom(moov, &[root, udta, meta], new_atom("ilst", &[]));
+    }
+}
+
+fn new_atom(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut vec = encode_int_be_u32(data.len() as u32 + 8);
+    vec.extend_from_slice(&encode_iso_8859_1(name));
+    vec.extend_from_slice(data);
+    vec
+}
+
+// an iTunes style meta atom with a metadata handler and an empty ilst
+fn new_meta() -> Vec<u8> {
+    let mut vec = vec![0; 4]; // version and flags
+
+    let mut hdlr = vec![0; 8]; // version, flags and pre-defined
+    hdlr.extend_from_slice(b"mdirappl");
+    hdlr.extend_from_slice(&[0; 9]); // reserved and empty name
+    vec.append(&mut new_atom("hdlr", &hdlr));
+
+    vec.append(&mut new_atom("ilst", &[]));
+    new_atom("meta", &vec)
+}
+
+// append an atom to the last atom in path, growing all of its ancestors
+fn insert_atom(vec: &mut Vec<u8>, path: &[Atom], atom: Vec<u8>) {
+    let parent = match path.last() {
+        Some(x) => x,
+        None => return,
+    };
+    let delta = atom.len() as i64;
+    vec.splice(parent.end as usize..parent.end as usize, atom);
+    for p in path.iter() {
+        adjust_size(vec, p.start as usize, delta);
+    }
+}
+
+// the contents of an ilst atom
+fn get_ilst_data(tags: &Tags) -> Result<Vec<u8>, Error> {
+    let mut vec = Vec::new();
+
+    // write tags
+    write_text!(vec, tags.title, "©nam");
+    write_text!(vec, tags.album, "©alb");
+    write_text!(vec, tags.artist, "©art");
+    write_text!(vec, tags.album_artist, "aART");
+    write_text!(vec, tags.composer, "©wrt");
+    write_text!(vec, tags.grouping, "©grp");
+    write_text!(vec, tags.genre, "©gen");
+    if let TagOption::Some(ref t) = tags.date {
+        let text = t.to_iso_8601();
+        if text.as_str() != "" {
+            write_data!(
+                vec,
+                text.as_str().as_bytes(),
+                "©day",
+                &[0, 0, 0, 1, 0, 0, 0, 0]
+            );
+        }
+    }
+
+    // track + track total
+    vec.extend_from_slice(b"\x00\x00\x00\x1Etrkn");
+    vec.extend_from_slice(b"\x00\x00\x00\x16data");
+    vec.extend_from_slice(b"\x00\x00\x00\x00\x00\x00\x00\x00");
+    vec.extend_from_slice(b"\x00\x00");
+    vec.extend_from_slice(&tag_to_u16_encoded!(tags.track_number));
+    vec.extend_from_slice(&tag_to_u16_encoded!(tags.track_total));
+
+    // disc + disc total
+    vec.extend_from_slice(b"\x00\x00\x00\x1Edisk");
+    vec.extend_from_slice(b"\x00\x00\x00\x16data");
+    vec.extend_from_slice(b"\x00\x00\x00\x00\x00\x00\x00\x00");
+    vec.extend_from_slice(b"\x00\x00");
+    vec.extend_from_slice(&tag_to_u16_encoded!(tags.disc_number));
+    vec.extend_from_slice(&tag_to_u16_encoded!(tags.disc_total));
+
+    write_data!(
+        vec,
+        &tag_to_u16_encoded!(tags.bpm),
+        "tmpo",
+        b"\x00\x00\x00\x15\x00\x00\x00\x00"
+    );
+
+    if let TagOption::Some(true) = tags.is_compilation {
+        write_data!(vec, &[0x01], "cpil", b"\x00\x00\x00\x15\x00\x00\x00\x00");
+    }
+
+    write_text!(vec, tags.comment, "©cmt");
+    write_text!(vec, tags.copyright, "cprt");
+    write_text!(vec, tags.sort_title, "sonm");
+    write_text!(vec, tags.sort_album, "soal");
+    write_text!(vec, tags.sort_artist, "soar");
+    write_text!(vec, tags.sort_album_artist, "soaa");
+    write_text!(vec, tags.sort_composer, "soco");
+
+    // freeform atoms, keys without a mean go into the iTunes namespace
+    if let TagOption::Some(ref x) = tags.custom {
+        for (key, value) in x.iter() {
+            let (mean, name) = match key.find(':') {
+                Some(i) => (&key[..i], &key[i + 1..]),
+                None => ("com.apple.iTunes", key.as_str()),
+            };
+
+            let mut atom = Vec::new();
+            atom.append(&mut new_atom(
+                "mean",
+                &[&[0, 0, 0, 0], mean.as_bytes()].concat(),
+            ));
+            atom.append(&mut new_atom(
+                "name",
+                &[&[0, 0, 0, 0], name.as_bytes()].concat(),
+            ));
+            atom.append(&mut new_atom(
+                "data",
+                &[&[0, 0, 0, 1, 0, 0, 0, 0], value.as_bytes()].concat(),
+            ));
+            vec.append(&mut new_atom("----", &atom));
+        }
+    }
+
+    // audiobook and video atoms
+    if let TagOption::Some(x) = tags.media_kind {
+        write_data!(vec, &[x], "stik", b"\x00\x00\x00\x15\x00\x00\x00\x00");
+    }
+    write_text!(vec, tags.tv_show, "tvsh");
+    if let TagOption::Some(x) = tags.tv_season {
+        write_data!(
+            vec,
+            &encode_int_be_u32(x as u32),
+            "tvsn",
+            b"\x00\x00\x00\x15\x00\x00\x00\x00"
+        );
+    }
+    if let TagOption::Some(x) = tags.tv_episode {
+        write_data!(
+            vec,
+            &encode_int_be_u32(x as u32),
+            "tves",
+            b"\x00\x00\x00\x15\x00\x00\x00\x00"
+        );
+    }
+    write_text!(vec, tags.description, "desc");
+    write_text!(vec, tags.long_description, "ldes");
+    if let TagOption::Some(x) = tags.is_hd_video {
+        write_data!(vec, &[x as u8], "hdvd", b"\x00\x00\x00\x15\x00\x00\x00\x00");
+    }
+
+    // if we have image
+    if let TagOption::Some(ref i) = tags.front_cover {
+        if i.is_some() {
+            write_data!(
+                vec,
+                &match i {
+                    Image::PNG(ref x) | Image::JPEG(ref x) => x,
+                    _ => return Err(tag_error("Expected an image")),
+                },
+                "covr",
+                // flag jpeg or png
+                &[
+                    0,
+                    0,
+                    0,
+                    match i {
+                        Image::JPEG(_) => 13,
+                        Image::PNG(_) => 14,
+                        _ => 0,
+                    },
+                    0,
+                    0,
+                    0,
+                    0
+                ]
+            );
+        }
+    }
+
+    Ok(vec)
+}
+
+// copy the file without the moov.udta.meta hierarchy
+pub fn remove<R: Read + Seek, W: Write>(input: &mut R, output: &mut W) -> Result<(), Error> {
+    rewrite(input, output, false, |moov| {
+        remove_meta(moov);
+        Ok(())
+    })
+}
+
+// cut moov.udta.meta and QuickTime style moov.meta out of an in-memory moov atom
+fn remove_meta(moov: &mut Vec<u8>) {
+    if let Some((meta, _)) = find_mdta_meta(moov) {
+        moov.drain(meta.start as usize..meta.end as usize);
+        adjust_size(moov, 0, -(meta.size as i64));
+    }
+
+    let root = root(moov);
+
+    let udta = match find_atom(moov, root.body(), root.end, "udta") {
+        Some(x) => x,
+        None => return,
+    };
+    let meta = match find_atom(moov, udta.body(), udta.end, "meta") {
+        Some(x) => x,
+        None => return,
+    };
+
+    moov.drain(meta.start as usize..meta.end as usize);
+
+    let delta = -(meta.size as i64);
+    adjust_size(moov, udta.start as usize, delta);
+    adjust_size(moov, root.start as usize, delta);
+}
+
+// list the child atoms in vec[start..end]
+fn children(vec: &[u8], start: u64, end: u64) -> Vec<Atom> {
+    let mut atoms = Vec::new();
+    let mut pos = start;
+    while pos + 8 <= end {
+        let header = &vec[pos as usize..std::cmp::min(pos + 16, end) as usize];
+        let mut atom = Atom::new(header, pos);
+        // runs to the end of the parent
+        if atom.size == 0 {
+            atom.size = end - pos;
+            atom.end = end;
+        }
+        if atom.size < atom.header || atom.end > end {
+            break;
+        }
+        pos = atom.end;
+        atoms.push(atom);
+    }
+    atoms
+}
+
+fn find_atom(vec: &[u8], start: u64, end: u64, name: &str) -> Option<Atom> {
+    children(vec, start, end)
+        .into_iter()
+        .find(|a| a.name.as_str() == name)
+}
+
+// add delta to the size of the atom starting at offset
+fn adjust_size(vec: &mut [u8], offset: usize, delta: i64) {
+    match decode_int_be_u32(&vec[offset..offset + 4]) {
+        // 64-bit extended size
+        1 => {
+            let size = decode_int_be_u64(&vec[offset + 8..offset + 16]) as i64 + delta;
+            vec[offset + 8..offset + 16].copy_from_slice(&encode_int_be_u64(size as u64));
+        }
+        // to the end of the file, nothing to adjust
+        0 => (),
+        x => {
+            let size = x as i64 + delta;
+            overwrite_u32!(
+                &encode_int_be_u32(size as u32),
+                &mut vec[offset..offset + 4]
+            );
+        }
+    }
+}
+
+// shift the chunk offsets (stco and co64) of every track in an in-memory moov atom
+// shift returns how far the data at a given offset has moved
+fn patch_chunk_offsets<F: Fn(u64) -> i64>(moov: &mut [u8], shift: F) -> Result<(), Error> {
+    let root = root(moov);
+
+    let mut tables = Vec::new();
+    for trak in children(moov, root.body(), root.end) {
+        if trak.name.as_str() != "trak" {
+            continue;
+        }
+        let mut parent = Some(trak);
+        for name in ["mdia", "minf", "stbl"].iter() {
+            parent = parent.and_then(|p| find_atom(moov, p.body(), p.end, name));
+        }
+        if let Some(stbl) = parent {
+            tables.append(&mut children(moov, stbl.body(), stbl.end));
+        }
+    }
+
+    for table in tables {
+        // version and flags, then entry count
+        let start = table.body() as usize + 8;
+        if start > table.end as usize {
+            continue;
+        }
+        let count = decode_int_be_u32(&moov[start - 4..start]) as usize;
+
+        let width = match table.name.as_str() {
+            "stco" => 4,
+            "co64" => 8,
+            _ => continue,
+        };
+
+        for i in 0..count {
+            let offset = start + width * i;
+            if offset + width > table.end as usize {
+                break;
             }
-            // copy unknown atoms
-            _ => {
-                // read in the atom
-                vec.extend_from_slice(&buf);
-                let s = vec.len();
-                vec.resize(s + atom.size as usize - 8, 0);
-                pos += input.read(&mut vec[s..])? as u64;
+            let entry = &mut moov[offset..offset + width];
+            let value = decode_int_be_u64(entry);
+            let value = value as i64 + shift(value);
+
+            if width == 4 {
+                if !(0..=0xFFFFFFFF).contains(&value) {
+                    return Err(tag_error(
+                        "Chunk offset does not fit into stco after resizing the metadata",
+                    ));
+                }
+                entry.copy_from_slice(&encode_int_be_u32(value as u32));
+            } else {
+                entry.copy_from_slice(&encode_int_be_u64(value as u64));
             }
         }
     }
@@ -384,7 +917,8 @@ fn collect_tags<T: Read + Seek>(input: &mut T, pos: &mut u64, ilst: &Atom) -> Re
         *pos += buf.len() as u64;
 
         let atom = Atom::new(&buf, *pos);
-        if atom.size == 0 {
+        // a broken size would take us backwards, or out of ilst
+        if atom.size < 8 || *pos - 8 + atom.size > ilst.end {
             break;
         }
 
@@ -400,9 +934,12 @@ fn collect_tags<T: Read + Seek>(input: &mut T, pos: &mut u64, ilst: &Atom) -> Re
             "©gen" => if_let_text!(tags.genre, input, pos),
             "gnre" => {
                 if tags.genre == TagOption::None {
+                    // gnre is the ID3v1 genre + 1, with 0 meaning no genre
                     if let Ok(i) = collect_atom_num(input, pos) {
                         use crate::id3v1::get_genre;
-                        tags.genre = TagOption::Some(get_genre(i as u8 - 1));
+                        if (1..=0x100).contains(&i) && !get_genre((i - 1) as u8).is_empty() {
+                            tags.genre = TagOption::Some(get_genre((i - 1) as u8));
+                        }
                     }
                 }
             }
@@ -455,6 +992,7 @@ fn collect_tags<T: Read + Seek>(input: &mut T, pos: &mut u64, ilst: &Atom) -> Re
             }
 
             "©cmt" => if_let_text!(tags.comment, input, pos),
+            "cprt" => if_let_text!(tags.copyright, input, pos),
 
             "sonm" => if_let_text!(tags.sort_title, input, pos),
             "soal" => if_let_text!(tags.sort_album, input, pos),
@@ -462,6 +1000,30 @@ fn collect_tags<T: Read + Seek>(input: &mut T, pos: &mut u64, ilst: &Atom) -> Re
             "soaa" => if_let_text!(tags.sort_album_artist, input, pos),
             "soco" => if_let_text!(tags.sort_composer, input, pos),
 
+            "stik" => {
+                if let Ok(i) = collect_atom_num(input, pos) {
+                    tags.media_kind = TagOption::Some(i as u8);
+                }
+            }
+            "tvsh" => if_let_text!(tags.tv_show, input, pos),
+            "tvsn" => {
+                if let Ok(i) = collect_atom_num(input, pos) {
+                    tags.tv_season = TagOption::Some(i as i64);
+                }
+            }
+            "tves" => {
+                if let Ok(i) = collect_atom_num(input, pos) {
+                    tags.tv_episode = TagOption::Some(i as i64);
+                }
+            }
+            "desc" => if_let_text!(tags.description, input, pos),
+            "ldes" => if_let_text!(tags.long_description, input, pos),
+            "hdvd" => {
+                if let Ok(i) = collect_atom_num(input, pos) {
+                    tags.is_hd_video = TagOption::Some(i != 0);
+                }
+            }
+
             // collect the first image only
             "covr" => {
                 if tags.front_cover == TagOption::None {
@@ -487,9 +1049,21 @@ fn collect_tags<T: Read + Seek>(input: &mut T, pos: &mut u64, ilst: &Atom) -> Re
                 }
             }
 
-            // skip secret itunes metadata
-            // maybe collect it later
-            "----" => *pos = input.seek(std::io::SeekFrom::Current(atom.size as i64 - 8))?,
+            // freeform atoms, keyed by mean:name
+            "----" => {
+                let mut vec = vec![0; atom.size as usize - 8];
+                input.read_exact(&mut vec)?;
+                *pos += vec.len() as u64;
+
+                if let Some((key, value)) = collect_freeform(&vec) {
+                    if let TagOption::None = tags.custom {
+                        tags.custom = TagOption::Some(BTreeMap::new());
+                    }
+                    if let TagOption::Some(ref mut x) = tags.custom {
+                        x.insert(key, value);
+                    }
+                }
+            }
             // skip unknown atoms
             _ => *pos = input.seek(std::io::SeekFrom::Current(atom.size as i64 - 8))?,
         }
@@ -498,8 +1072,43 @@ fn collect_tags<T: Read + Seek>(input: &mut T, pos: &mut u64, ilst: &Atom) -> Re
     Ok(tags)
 }
 
+// collect the mean, name and first text data atom of a freeform atom
+fn collect_freeform(vec: &[u8]) -> Option<(String, String)> {
+    let mut mean = None;
+    let mut name = None;
+    let mut value = None;
+
+    for atom in children(vec, 0, vec.len() as u64) {
+        let data = &vec[atom.body() as usize..atom.end as usize];
+        // all three have four bytes of version/flags or type first
+        if data.len() < 4 {
+            continue;
+        }
+        match atom.name.as_str() {
+            "mean" => mean = Some(decode_utf8(&data[4..])),
+            "name" => name = Some(decode_utf8(&data[4..])),
+            "data" if value.is_none() => {
+                value = collect_data_text(&vec[atom.start as usize..atom.end as usize])
+            }
+            _ => (),
+        }
+    }
+
+    Some((format!("{}:{}", mean?, name?), value?))
+}
+
+// the value of the first text data atom in vec
+fn collect_data_text(vec: &[u8]) -> Option<String> {
+    children(vec, 0, vec.len() as u64)
+        .iter()
+        .filter(|a| a.name.as_str() == "data")
+        .map(|a| &vec[a.body() as usize..a.end as usize])
+        // type and locale, only take text
+        .find(|x| x.len() >= 8 && x[3] == 1)
+        .map(|x| decode_utf8(&x[8..]))
+}
+
 fn collect_atom_text<T: Read + Seek>(input: &mut T, pos: &mut u64) -> Result<String, Error> {
-    use crate::tools::encoding::decode_utf8;
     let vec = collect_atom_data(input, pos)?;
 
     if vec.len() < 9 {
@@ -1,5 +1,6 @@
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::Cursor;
 
 use crate::DateTime;
 use crate::Image;
@@ -9,7 +10,27 @@ use crate::Tags;
 #[test]
 fn aac_test() {
     let mut file = File::open("testfiles/m4a-aac.m4a").unwrap();
-    let tags = super::get(&mut file).unwrap();
+    let mut tags = super::get(&mut file).unwrap();
+
+    // freeform atoms, the plist in iTunMOVI is too long to compare here
+    match std::mem::replace(&mut tags.custom, TagOption::None) {
+        TagOption::Some(custom) => {
+            assert_eq!(
+                custom.keys().collect::<Vec<_>>(),
+                vec![
+                    "com.apple.iTunes:iTunMOVI",
+                    "com.apple.iTunes:iTunNORM",
+                    "com.apple.iTunes:iTunSMPB"
+                ]
+            );
+            assert_eq!(
+                custom["com.apple.iTunes:iTunNORM"],
+                " 000000F5 00000127 000005EE 00000738 00003158 00003158 00003B2E 000043D8 0000116A 00000E83"
+            );
+        }
+        _ => panic!("no freeform atoms"),
+    }
+
     let ideal = Tags {
         title: TagOption::Some("(Segue) - Nathan Adler, Pt. 2".to_string()),
         artist: TagOption::Some("David Bowie".to_string()),
@@ -25,6 +46,12 @@ fn aac_test() {
         disc_number: TagOption::Some(1),
         disc_total: TagOption::Some(1),
 
+        copyright: TagOption::Some(
+            "℗ 1995, 2004 ISO Records under license to Sony Music Entertainment Inc.".to_string(),
+        ),
+
+        media_kind: TagOption::Some(1),
+
         front_cover: TagOption::Some({
             let mut vec = Vec::new();
             let mut file = File::open("testfiles/m4a-aac-cover.jpg").unwrap();
@@ -70,3 +97,549 @@ fn alac_test() {
 
     assert_eq!(tags, ideal);
 }
+
+// first chunk offset of the first track
+fn first_chunk_offset(vec: &[u8]) -> usize {
+    let mut atom = super::find_atom(vec, 0, vec.len() as u64, "moov").unwrap();
+    for name in ["trak", "mdia", "minf", "stbl"].iter() {
+        atom = super::find_atom(vec, atom.body(), atom.end, name).unwrap();
+    }
+    let table = super::children(vec, atom.body(), atom.end)
+        .into_iter()
+        .find(|a| a.name.as_str() == "stco" || a.name.as_str() == "co64")
+        .unwrap();
+    let start = table.body() as usize + 8;
+    match table.name.as_str() {
+        "stco" => crate::tools::decode_int_be_u32(&vec[start..start + 4]) as usize,
+        _ => crate::tools::decode_int_be_u64(&vec[start..start + 8]) as usize,
+    }
+}
+
+// rebuild a file the way large files are laid out:
+// co64 instead of stco, and mdat with a 64-bit size (or size 0)
+fn large_layout(vec: &[u8], mdat_to_end: bool) -> Vec<u8> {
+    use crate::tools::{decode_int_be_u32, encode_int_be_u32, encode_int_be_u64};
+
+    let moov_atom = super::find_atom(vec, 0, vec.len() as u64, "moov").unwrap();
+    let mdat_atom = super::find_atom(vec, 0, vec.len() as u64, "mdat").unwrap();
+    let mut moov = vec[moov_atom.start as usize..moov_atom.end as usize].to_vec();
+
+    let mut path = vec![super::Atom::new(&moov, 0)];
+    for name in ["trak", "mdia", "minf", "stbl", "stco"].iter() {
+        let parent = path.last().unwrap();
+        let atom = super::find_atom(&moov, parent.body(), parent.end, name).unwrap();
+        path.push(atom);
+    }
+    let stco = path.pop().unwrap();
+
+    let count = decode_int_be_u32(&moov[stco.start as usize + 12..stco.start as usize + 16]);
+    let mut co64 = encode_int_be_u32(16 + 8 * count);
+    co64.extend_from_slice(b"co64");
+    co64.extend_from_slice(&moov[stco.start as usize + 8..stco.start as usize + 16]);
+    for i in 0..count as usize {
+        let offset = stco.start as usize + 16 + 4 * i;
+        let entry = decode_int_be_u32(&moov[offset..offset + 4]);
+        co64.extend_from_slice(&encode_int_be_u64(entry as u64));
+    }
+
+    let delta = co64.len() as i64 - stco.size as i64;
+    moov.splice(stco.start as usize..stco.end as usize, co64);
+    for atom in path.iter() {
+        super::adjust_size(&mut moov, atom.start as usize, delta);
+    }
+
+    let mdat_header = if mdat_to_end {
+        b"\x00\x00\x00\x00mdat".to_vec()
+    } else {
+        let mut h = b"\x00\x00\x00\x01mdat".to_vec();
+        h.extend_from_slice(&encode_int_be_u64(mdat_atom.size + 8));
+        h
+    };
+    let header_delta = mdat_header.len() as i64 - 8;
+    super::patch_chunk_offsets(&mut moov, |_| delta + header_delta).unwrap();
+
+    let mut out = vec[..moov_atom.start as usize].to_vec();
+    out.extend_from_slice(&moov);
+    out.extend_from_slice(&vec[moov_atom.end as usize..mdat_atom.start as usize]);
+    out.extend_from_slice(&mdat_header);
+    out.extend_from_slice(&vec[mdat_atom.start as usize + 8..]);
+    out
+}
+
+#[test]
+fn large_layout_test() {
+    let mut vec = Vec::new();
+    File::open("testfiles/m4a-aac.m4a")
+        .unwrap()
+        .read_to_end(&mut vec)
+        .unwrap();
+    let ideal = super::get(&mut Cursor::new(&vec)).unwrap();
+    let chunk = first_chunk_offset(&vec);
+
+    for mdat_to_end in [false, true].iter() {
+        let large = large_layout(&vec, *mdat_to_end);
+        assert_eq!(super::get(&mut Cursor::new(&large)).unwrap(), ideal);
+
+        let tags = Tags {
+            title: TagOption::Some("A much longer title than the one we had before".to_string()),
+            ..Tags::mixed()
+        };
+        let mut output = Vec::new();
+        super::set_with_options(
+            &mut Cursor::new(&large),
+            &mut output,
+            &tags,
+            &Default::default(),
+        )
+        .unwrap();
+
+        let written = super::get(&mut Cursor::new(&output)).unwrap();
+        assert_eq!(written.title, tags.title);
+        assert_eq!(written.album, ideal.album);
+
+        let new = first_chunk_offset(&output);
+        assert_eq!(&vec[chunk..chunk + 64], &output[new..new + 64]);
+    }
+}
+
+#[test]
+fn remove_test() {
+    let mut vec = Vec::new();
+    File::open("testfiles/m4a-aac.m4a")
+        .unwrap()
+        .read_to_end(&mut vec)
+        .unwrap();
+
+    // both stco and co64 chunk offsets must be patched
+    for input in [vec.clone(), large_layout(&vec, false)].iter() {
+        let mut output = Vec::new();
+        super::remove(&mut Cursor::new(input), &mut output).unwrap();
+
+        assert!(output.len() < input.len());
+        assert_eq!(super::get(&mut Cursor::new(&output)).unwrap(), Tags::none());
+
+        // media data must still be where the chunk offsets say it is
+        let old = first_chunk_offset(input);
+        let new = first_chunk_offset(&output);
+        assert_eq!(&input[old..old + 64], &output[new..new + 64]);
+    }
+}
+
+// rebuild a file with moov at the end and a second copy of the first track
+fn moov_at_end_layout(vec: &[u8]) -> Vec<u8> {
+    let moov_atom = super::find_atom(vec, 0, vec.len() as u64, "moov").unwrap();
+    let mut moov = vec[moov_atom.start as usize..moov_atom.end as usize].to_vec();
+
+    let root = super::Atom::new(&moov, 0);
+    let trak = super::find_atom(&moov, root.body(), root.end, "trak").unwrap();
+    let copy = moov[trak.start as usize..trak.end as usize].to_vec();
+    moov.splice(trak.end as usize..trak.end as usize, copy);
+    super::adjust_size(&mut moov, 0, trak.size as i64);
+
+    // everything after moov moves up
+    let delta = -(moov_atom.size as i64);
+    super::patch_chunk_offsets(&mut moov, |_| delta).unwrap();
+
+    let mut out = vec[..moov_atom.start as usize].to_vec();
+    out.extend_from_slice(&vec[moov_atom.end as usize..]);
+    out.extend_from_slice(&moov);
+    out
+}
+
+// first chunk offset of every track
+fn chunk_offsets(vec: &[u8]) -> Vec<usize> {
+    let moov = super::find_atom(vec, 0, vec.len() as u64, "moov").unwrap();
+    super::children(vec, moov.body(), moov.end)
+        .into_iter()
+        .filter(|a| a.name.as_str() == "trak")
+        .map(|trak| {
+            let mut atom = trak;
+            for name in ["mdia", "minf", "stbl", "stco"].iter() {
+                atom = super::find_atom(vec, atom.body(), atom.end, name).unwrap();
+            }
+            let start = atom.body() as usize + 8;
+            crate::tools::decode_int_be_u32(&vec[start..start + 4]) as usize
+        })
+        .collect()
+}
+
+#[test]
+fn moov_at_end_test() {
+    let mut vec = Vec::new();
+    File::open("testfiles/m4a-aac.m4a")
+        .unwrap()
+        .read_to_end(&mut vec)
+        .unwrap();
+    let chunk = first_chunk_offset(&vec);
+
+    let input = moov_at_end_layout(&vec);
+    let tags = Tags {
+        title: TagOption::Some("A much longer title than the one we had before".to_string()),
+        ..Tags::mixed()
+    };
+
+    for faststart in [false, true].iter() {
+        let options = crate::WriteOptions {
+            faststart: *faststart,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        super::set_with_options(&mut Cursor::new(&input), &mut output, &tags, &options).unwrap();
+
+        assert_eq!(
+            super::get(&mut Cursor::new(&output)).unwrap().title,
+            tags.title
+        );
+
+        let atoms: Vec<String> = super::children(&output, 0, output.len() as u64)
+            .into_iter()
+            .map(|a| a.name)
+            .collect();
+        match faststart {
+            true => assert_eq!(atoms, vec!["ftyp", "free", "moov", "mdat"]),
+            false => assert_eq!(atoms, vec!["ftyp", "free", "mdat", "moov"]),
+        }
+
+        // both tracks still point at the media data
+        let offsets = chunk_offsets(&output);
+        assert_eq!(offsets.len(), 2);
+        for offset in offsets {
+            assert_eq!(&vec[chunk..chunk + 64], &output[offset..offset + 64]);
+        }
+    }
+}
+
+#[test]
+fn moov_size_zero_test() {
+    let mut vec = Vec::new();
+    File::open("testfiles/m4a-aac.m4a")
+        .unwrap()
+        .read_to_end(&mut vec)
+        .unwrap();
+    let chunk = first_chunk_offset(&vec);
+    let ideal = super::get(&mut Cursor::new(&vec)).unwrap();
+
+    // a trailing moov that runs to the end of the file
+    let mut input = moov_at_end_layout(&vec);
+    let moov = super::find_atom(&input, 0, input.len() as u64, "moov").unwrap();
+    input[moov.start as usize..moov.start as usize + 4].copy_from_slice(&[0; 4]);
+
+    assert_eq!(super::get(&mut Cursor::new(&input)).unwrap(), ideal);
+
+    let tags = Tags {
+        title: TagOption::Some("A much longer title than the one we had before".to_string()),
+        ..Tags::mixed()
+    };
+    for faststart in [false, true].iter() {
+        let options = crate::WriteOptions {
+            faststart: *faststart,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        super::set_with_options(&mut Cursor::new(&input), &mut output, &tags, &options).unwrap();
+
+        let written = super::get(&mut Cursor::new(&output)).unwrap();
+        assert_eq!(written.title, tags.title);
+        assert_eq!(written.album, ideal.album);
+
+        // moov gets a real size wherever it ends up
+        let atoms = super::children(&output, 0, output.len() as u64);
+        let moov = atoms.iter().find(|a| a.name.as_str() == "moov").unwrap();
+        let size = &output[moov.start as usize..moov.start as usize + 4];
+        assert_ne!(crate::tools::decode_int_be_u32(size), 0);
+        match faststart {
+            true => assert_eq!(atoms.last().unwrap().name.as_str(), "mdat"),
+            false => assert_eq!(atoms.last().unwrap().name.as_str(), "moov"),
+        }
+
+        for offset in chunk_offsets(&output) {
+            assert_eq!(&vec[chunk..chunk + 64], &output[offset..offset + 64]);
+        }
+    }
+
+    let mut output = Vec::new();
+    super::remove(&mut Cursor::new(&input), &mut output).unwrap();
+    assert_eq!(super::get(&mut Cursor::new(&output)).unwrap(), Tags::none());
+    for offset in chunk_offsets(&output) {
+        assert_eq!(&vec[chunk..chunk + 64], &output[offset..offset + 64]);
+    }
+}
+
+#[test]
+fn create_ilst_test() {
+    let mut vec = Vec::new();
+    File::open("testfiles/m4a-aac.m4a")
+        .unwrap()
+        .read_to_end(&mut vec)
+        .unwrap();
+    let chunk = first_chunk_offset(&vec);
+
+    // no meta in udta
+    let mut no_meta = Vec::new();
+    super::remove(&mut Cursor::new(&vec), &mut no_meta).unwrap();
+
+    // no udta at all
+    let no_udta = {
+        let moov_atom = super::find_atom(&no_meta, 0, no_meta.len() as u64, "moov").unwrap();
+        let mut moov = no_meta[moov_atom.start as usize..moov_atom.end as usize].to_vec();
+        let root = super::Atom::new(&moov, 0);
+        let udta = super::find_atom(&moov, root.body(), root.end, "udta").unwrap();
+        moov.drain(udta.start as usize..udta.end as usize);
+        super::adjust_size(&mut moov, 0, -(udta.size as i64));
+        super::patch_chunk_offsets(&mut moov, |_| -(udta.size as i64)).unwrap();
+
+        let mut out = no_meta[..moov_atom.start as usize].to_vec();
+        out.extend_from_slice(&moov);
+        out.extend_from_slice(&no_meta[moov_atom.end as usize..]);
+        out
+    };
+
+    let tags = Tags {
+        title: TagOption::Some("New Title".to_string()),
+        artist: TagOption::Some("New Artist".to_string()),
+        ..Tags::mixed()
+    };
+
+    for input in [no_meta, no_udta].iter() {
+        assert_eq!(super::get(&mut Cursor::new(input)).unwrap(), Tags::none());
+
+        let mut output = Vec::new();
+        super::set_with_options(
+            &mut Cursor::new(input),
+            &mut output,
+            &tags,
+            &Default::default(),
+        )
+        .unwrap();
+
+        let written = super::get(&mut Cursor::new(&output)).unwrap();
+        assert_eq!(written.title, tags.title);
+        assert_eq!(written.artist, tags.artist);
+
+        let new = first_chunk_offset(&output);
+        assert_eq!(&vec[chunk..chunk + 64], &output[new..new + 64]);
+    }
+}
+
+#[test]
+fn video_atoms_test() {
+    let mut vec = Vec::new();
+    File::open("testfiles/m4a-aac.m4a")
+        .unwrap()
+        .read_to_end(&mut vec)
+        .unwrap();
+
+    let tags = Tags {
+        media_kind: TagOption::Some(10),
+        tv_show: TagOption::Some("Show".to_string()),
+        tv_season: TagOption::Some(2),
+        tv_episode: TagOption::Some(13),
+        description: TagOption::Some("Short description".to_string()),
+        long_description: TagOption::Some("A much longer description".to_string()),
+        is_hd_video: TagOption::Some(true),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set_with_options(
+        &mut Cursor::new(&vec),
+        &mut output,
+        &tags,
+        &Default::default(),
+    )
+    .unwrap();
+
+    let ideal = Tags {
+        media_kind: TagOption::Some(10),
+        tv_show: TagOption::Some("Show".to_string()),
+        tv_season: TagOption::Some(2),
+        tv_episode: TagOption::Some(13),
+        description: TagOption::Some("Short description".to_string()),
+        long_description: TagOption::Some("A much longer description".to_string()),
+        is_hd_video: TagOption::Some(true),
+        ..super::get(&mut Cursor::new(&vec)).unwrap()
+    };
+    assert_eq!(super::get(&mut Cursor::new(&output)).unwrap(), ideal);
+}
+
+#[test]
+fn ftyp_test() {
+    let mut vec = b"\x00\x00\x00\x14ftypabcd\x00\x00\x00\x00efgh".to_vec();
+    vec.extend_from_slice(b"\x00\x00\x00\x08moov");
+    assert!(super::get(&mut Cursor::new(&vec)).is_err());
+
+    // compatible brands count too
+    vec[16..20].copy_from_slice(b"M4B ");
+    assert_eq!(super::get(&mut Cursor::new(&vec)).unwrap(), Tags::none());
+
+    // QuickTime movies
+    vec[8..12].copy_from_slice(b"qt  ");
+    vec[16..20].copy_from_slice(b"efgh");
+    assert_eq!(super::get(&mut Cursor::new(&vec)).unwrap(), Tags::none());
+
+    assert!(super::get(&mut Cursor::new(b"\x1A\x45\xDF\xA3\x00\x00\x00\x00")).is_err());
+}
+
+#[test]
+fn freeform_test() {
+    let mut input = File::open("testfiles/m4a-aac.m4a").unwrap();
+    let mut custom = std::collections::BTreeMap::new();
+    custom.insert(
+        "com.apple.iTunes:MusicBrainz Track Id".to_string(),
+        "abc".to_string(),
+    );
+    custom.insert("org.example:Mood".to_string(), "Calm".to_string());
+    custom.insert("CATALOGNUMBER".to_string(), "XYZ-1".to_string());
+    let new = Tags {
+        custom: TagOption::Some(custom),
+        ..Tags::mixed()
+    };
+
+    let mut output = Cursor::new(Vec::new());
+    super::set_with_options(&mut input, &mut output, &new, &Default::default()).unwrap();
+    output.set_position(0);
+
+    let tags = super::get(&mut output).unwrap();
+    let mut ideal = std::collections::BTreeMap::new();
+    ideal.insert(
+        "com.apple.iTunes:MusicBrainz Track Id".to_string(),
+        "abc".to_string(),
+    );
+    ideal.insert("org.example:Mood".to_string(), "Calm".to_string());
+    ideal.insert(
+        "com.apple.iTunes:CATALOGNUMBER".to_string(),
+        "XYZ-1".to_string(),
+    );
+    assert_eq!(tags.custom, TagOption::Some(ideal));
+    assert_eq!(tags.artist, TagOption::Some("David Bowie".to_string()));
+}
+
+#[test]
+fn broken_freeform_test() {
+    // a freeform atom too small to hold its own header, then one running past ilst
+    for size in [4u32, 0x7FFF_FFFF].iter() {
+        let mut vec = b"\x00\x00\x00\x00ilst".to_vec();
+        vec.extend_from_slice(&crate::tools::encode_int_be_u32(*size));
+        vec.extend_from_slice(b"----");
+        vec.resize(32, 0);
+        vec[3] = 32;
+
+        let ilst = super::Atom::new(&vec, 0);
+        let mut cursor = Cursor::new(&vec);
+        let mut pos = cursor.seek(std::io::SeekFrom::Start(8)).unwrap();
+        let tags = super::collect_tags(&mut cursor, &mut pos, &ilst).unwrap();
+        assert_eq!(tags, Tags::none());
+    }
+}
+
+// append a QuickTime style moov.meta with an mdta handler
+fn add_mdta(vec: &mut Vec<u8>, items: &[(&str, &str)]) {
+    let mut hdlr = vec![0; 8];
+    hdlr.extend_from_slice(b"mdta");
+    hdlr.extend_from_slice(&[0; 13]);
+
+    let mut keys = vec![0, 0, 0, 0, 0, 0, 0, items.len() as u8];
+    let mut ilst = Vec::new();
+    for (i, (key, value)) in items.iter().enumerate() {
+        keys.append(&mut super::new_atom("mdta", key.as_bytes()));
+
+        let data = super::new_atom(
+            "data",
+            &[&[0, 0, 0, 1, 0, 0, 0, 0], value.as_bytes()].concat(),
+        );
+        ilst.extend_from_slice(&[0, 0, 0, data.len() as u8 + 8, 0, 0, 0, i as u8 + 1]);
+        ilst.extend_from_slice(&data);
+    }
+
+    let mut meta = super::new_atom("hdlr", &hdlr);
+    meta.append(&mut super::new_atom("keys", &keys));
+    meta.append(&mut super::new_atom("ilst", &ilst));
+
+    let moov = super::find_atom(vec, 0, vec.len() as u64, "moov").unwrap();
+    super::insert_atom(vec, &[moov], super::new_atom("meta", &meta));
+}
+
+#[test]
+fn mdta_test() {
+    let mut vec = Vec::new();
+    File::open("testfiles/m4a-aac.m4a")
+        .unwrap()
+        .read_to_end(&mut vec)
+        .unwrap();
+    let mut input = Vec::new();
+    super::remove(&mut Cursor::new(&vec), &mut input).unwrap();
+    add_mdta(
+        &mut input,
+        &[
+            ("com.apple.quicktime.make", "Apple"),
+            ("com.apple.quicktime.title", "Holiday"),
+            ("com.apple.quicktime.artist", "Someone"),
+            (
+                "com.apple.quicktime.creationdate",
+                "2019-06-01T10:20:30+0200",
+            ),
+        ],
+    );
+
+    let tags = super::get(&mut Cursor::new(&input)).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("Holiday".to_string()),
+        artist: TagOption::Some("Someone".to_string()),
+        date: DateTime::from_iso_8601("2019-06-01T10:20:30").into(),
+        ..Default::default()
+    };
+    assert_eq!(tags, ideal);
+
+    // mapped keys follow the new tags, anything else is kept
+    let new = Tags {
+        title: TagOption::Some("Beach".to_string()),
+        artist: TagOption::None,
+        album: TagOption::Some("Summer".to_string()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set_with_options(
+        &mut Cursor::new(&input),
+        &mut output,
+        &new,
+        &Default::default(),
+    )
+    .unwrap();
+
+    let moov = super::find_atom(&output, 0, output.len() as u64, "moov").unwrap();
+    let moov = &output[moov.start as usize..moov.end as usize];
+    let (meta, start) = super::find_mdta_meta(moov).unwrap();
+    let mut items: Vec<(String, Option<String>)> = super::collect_mdta_items(moov, &meta, start)
+        .into_iter()
+        .map(|(k, v)| (k, super::collect_data_text(&v)))
+        .collect();
+    items.sort();
+    assert_eq!(
+        items,
+        vec![
+            (
+                "com.apple.quicktime.album".to_string(),
+                Some("Summer".to_string())
+            ),
+            (
+                "com.apple.quicktime.creationdate".to_string(),
+                Some("2019-06-01T10:20:30".to_string())
+            ),
+            (
+                "com.apple.quicktime.make".to_string(),
+                Some("Apple".to_string())
+            ),
+            (
+                "com.apple.quicktime.title".to_string(),
+                Some("Beach".to_string())
+            ),
+        ]
+    );
+
+    // and removing tags takes the QuickTime metadata with it
+    let mut removed = Vec::new();
+    super::remove(&mut Cursor::new(&output), &mut removed).unwrap();
+    assert_eq!(
+        super::get(&mut Cursor::new(&removed)).unwrap(),
+        Tags::none()
+    );
+}
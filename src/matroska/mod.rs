@@ -0,0 +1,908 @@
+use std::collections::BTreeMap;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::DateTime;
+use crate::Error;
+use crate::Image;
+use crate::TagOption;
+use crate::Tags;
+
+use crate::tools::decode_int_be_u64;
+use crate::tools::tag_error;
+use crate::tools::tags;
+use crate::tools::tags::delta;
+use crate::tools::tags::fill;
+
+use crc::crc32::checksum_ieee;
+
+const EBML: u32 = 0x1A45_DFA3;
+const DOC_TYPE: u32 = 0x4282;
+const SEGMENT: u32 = 0x1853_8067;
+const VOID: u32 = 0xEC;
+
+const SEEKHEAD: u32 = 0x114D_9B74;
+const SEEK: u32 = 0x4DBB;
+const SEEK_ID: u32 = 0x53AB;
+const SEEK_POSITION: u32 = 0x53AC;
+
+const ATTACHMENTS: u32 = 0x1941_A469;
+const ATTACHED_FILE: u32 = 0x61A7;
+const FILE_NAME: u32 = 0x466E;
+const FILE_MIME_TYPE: u32 = 0x4660;
+const FILE_DATA: u32 = 0x465C;
+const FILE_UID: u32 = 0x46AE;
+
+const TAGS: u32 = 0x1254_C367;
+const TAG: u32 = 0x7373;
+const TARGETS: u32 = 0x63C0;
+const TARGET_TYPE_VALUE: u32 = 0x68CA;
+const SIMPLE_TAG: u32 = 0x67C8;
+const TAG_NAME: u32 = 0x45A3;
+const TAG_STRING: u32 = 0x4487;
+
+// tags that only apply to a certain track, edition, chapter or attachment
+const TARGET_UIDS: [u32; 4] = [0x63C5, 0x63C9, 0x63C4, 0x63C6];
+
+// children of the segment, used to find the end of elements of unknown size
+const LEVEL_1: [u32; 8] = [
+    SEEKHEAD,
+    0x1549_A966, // Info
+    0x1654_AE6B, // Tracks
+    0x1F43_B675, // Cluster
+    0x1C53_BB6B, // Cues
+    ATTACHMENTS,
+    0x1043_A770, // Chapters
+    TAGS,
+];
+
+// target levels
+const TRACK: u64 = 30;
+const ALBUM: u64 = 50;
+const DISC: u64 = 60;
+
+// album level tags which we read as if they were set on the track
+const ALBUM_FALLBACK: [&str; 5] = ["COMPOSER", "GENRE", "DATE_RELEASED", "COMMENT", "COPYRIGHT"];
+
+// EBML variable size integer, the length marker removed
+fn read_vint(vec: &[u8]) -> Option<(u64, usize)> {
+    let first = *vec.first()?;
+    if first == 0 {
+        return None;
+    }
+    let length = first.leading_zeros() as usize + 1;
+    if vec.len() < length {
+        return None;
+    }
+    let value = vec[1..length]
+        .iter()
+        .fold(first as u64 & (0xFF >> length), |value, b| {
+            (value << 8) | *b as u64
+        });
+    Some((value, length))
+}
+
+// element ids keep their length marker
+fn read_id(vec: &[u8]) -> Option<(u32, usize)> {
+    let first = *vec.first()?;
+    let length = first.leading_zeros() as usize + 1;
+    if first == 0 || length > 4 || vec.len() < length {
+        return None;
+    }
+    let id = vec[..length].iter().fold(0, |id, b| (id << 8) | *b as u32);
+    Some((id, length))
+}
+
+// id, size (None if unknown) and the length of the element header
+fn read_header(vec: &[u8]) -> Option<(u32, Option<u64>, usize)> {
+    let (id, id_length) = read_id(vec)?;
+    let (size, size_length) = read_vint(&vec[id_length..])?;
+    let size = match size == (1 << (7 * size_length)) - 1 {
+        true => None,
+        false => Some(size),
+    };
+    Some((id, size, id_length + size_length))
+}
+
+// the children of an element in memory
+fn children(vec: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut items = Vec::new();
+    let mut pos = 0;
+    while let Some((id, size, length)) = read_header(&vec[pos..]) {
+        let body = pos + length;
+        let end = match size {
+            Some(x) => std::cmp::min(body.saturating_add(x as usize), vec.len()),
+            None => vec.len(),
+        };
+        items.push((id, &vec[body..end]));
+        pos = end;
+    }
+    items
+}
+
+fn decode_uint(value: &[u8]) -> u64 {
+    decode_int_be_u64(&value[value.len().saturating_sub(8)..])
+}
+
+fn decode_string(value: &[u8]) -> String {
+    // strings may be padded with zero bytes
+    let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+    String::from_utf8_lossy(&value[..end]).to_string()
+}
+
+fn encode_id(id: u32) -> Vec<u8> {
+    let bytes = id.to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(3);
+    bytes[start..].to_vec()
+}
+
+fn size_length(size: u64) -> usize {
+    (1..8).find(|l| size < (1 << (7 * l)) - 1).unwrap_or(8)
+}
+
+fn encode_size(size: u64, length: usize) -> Vec<u8> {
+    let value = size | 1 << (7 * length);
+    value.to_be_bytes()[8 - length..].to_vec()
+}
+
+fn new_element(id: u32, body: &[u8]) -> Vec<u8> {
+    let mut vec = encode_id(id);
+    vec.append(&mut encode_size(
+        body.len() as u64,
+        size_length(body.len() as u64),
+    ));
+    vec.extend_from_slice(body);
+    vec
+}
+
+fn new_uint(id: u32, value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(7);
+    new_element(id, &bytes[start..])
+}
+
+fn new_string(id: u32, value: &str) -> Vec<u8> {
+    new_element(id, value.as_bytes())
+}
+
+// a Void element of exactly `length` bytes, which has to be at least 2
+fn void(length: u64) -> Vec<u8> {
+    let size_length = (1..9)
+        .find(|&l| length - 1 - l as u64 <= (1 << (7 * l)) - 2)
+        .unwrap_or(8);
+    let size = length - 1 - size_length as u64;
+    let mut vec = encode_id(VOID);
+    vec.append(&mut encode_size(size, size_length));
+    vec.resize(length as usize, 0);
+    vec
+}
+
+// the element followed by a Void filling up the rest of `slot` bytes,
+// if it fits; a longer size field makes up for a single leftover byte
+fn fit(id: u32, body: &[u8], slot: u64) -> Option<Vec<u8>> {
+    let header = encode_id(id).len() as u64;
+    let body_length = body.len() as u64;
+    for length in size_length(body_length)..9 {
+        let total = header + length as u64 + body_length;
+        if total == slot || total + 2 <= slot {
+            let mut vec = encode_id(id);
+            vec.append(&mut encode_size(body_length, length));
+            vec.extend_from_slice(body);
+            if total < slot {
+                vec.append(&mut void(slot - total));
+            }
+            return Some(vec);
+        }
+    }
+    None
+}
+
+struct Element {
+    id: u32,
+    start: u64,
+    body: u64,
+    end: u64,
+}
+
+struct Segment {
+    // WebM doesn't allow attachments
+    webm: bool,
+    // start of the segment contents, which seek positions are relative to
+    body: u64,
+    // where the segment size is stored, and in how many bytes (unless unknown)
+    size_field: Option<(u64, usize)>,
+    end: u64,
+    length: u64,
+    children: Vec<Element>,
+}
+
+fn read_element<T: Read + Seek>(
+    input: &mut T,
+    pos: u64,
+    end: u64,
+) -> Result<Option<(u32, Option<u64>, u64)>, Error> {
+    let mut vec = Vec::new();
+    input.seek(SeekFrom::Start(pos))?;
+    input
+        .take(std::cmp::min(12, end.saturating_sub(pos)))
+        .read_to_end(&mut vec)?;
+    Ok(read_header(&vec).map(|(id, size, length)| (id, size, pos + length as u64)))
+}
+
+// elements of unknown size (like clusters written live) end where the next
+// element that can't be one of their children starts
+fn find_end<T: Read + Seek>(input: &mut T, body: u64, end: u64) -> Result<u64, Error> {
+    let mut pos = body;
+    while pos < end {
+        match read_element(input, pos, end)? {
+            Some((id, Some(size), body)) if !LEVEL_1.contains(&id) && id != EBML => {
+                pos = body + size;
+            }
+            Some((id, _, _)) if LEVEL_1.contains(&id) || id == EBML => return Ok(pos),
+            _ => return Ok(end),
+        }
+    }
+    Ok(end)
+}
+
+// the EBML header, then the segment and its children
+fn read_segment<T: Read + Seek>(input: &mut T) -> Result<Segment, Error> {
+    let length = input.seek(SeekFrom::End(0))?;
+
+    let (size, body) = match read_element(input, 0, length)? {
+        Some((EBML, Some(size), body)) => (size, body),
+        _ => return Err(tag_error("Not an EBML file")),
+    };
+    let mut vec = Vec::new();
+    input.seek(SeekFrom::Start(body))?;
+    input.take(size).read_to_end(&mut vec)?;
+    let doc_type = children(&vec)
+        .into_iter()
+        .find(|&(id, _)| id == DOC_TYPE)
+        .map(|(_, value)| decode_string(value));
+    let webm = match doc_type.as_deref() {
+        Some("matroska") => false,
+        Some("webm") => true,
+        _ => return Err(tag_error("Not a Matroska file")),
+    };
+
+    // skip anything up to the segment
+    let mut pos = body + size;
+    let (start, size, body) = loop {
+        match read_element(input, pos, length)? {
+            Some((SEGMENT, size, body)) => break (pos, size, body),
+            Some((_, Some(size), body)) => pos = body + size,
+            _ => return Err(tag_error("Matroska segment not found")),
+        }
+    };
+    let end = match size {
+        Some(x) => std::cmp::min(body + x, length),
+        None => length,
+    };
+    let size_field = size.map(|_| (start + 4, (body - start - 4) as usize));
+
+    let mut children = Vec::new();
+    let mut pos = body;
+    while pos < end {
+        let (id, size, body) = match read_element(input, pos, end)? {
+            Some(x) => x,
+            None => break,
+        };
+        let child_end = match size {
+            Some(x) => std::cmp::min(body + x, end),
+            None => find_end(input, body, end)?,
+        };
+        children.push(Element {
+            id,
+            start: pos,
+            body,
+            end: child_end,
+        });
+        pos = child_end;
+    }
+
+    Ok(Segment {
+        webm,
+        body,
+        size_field,
+        end,
+        length,
+        children,
+    })
+}
+
+fn read_body<T: Read + Seek>(input: &mut T, element: &Element) -> Result<Vec<u8>, Error> {
+    let mut vec = Vec::new();
+    input.seek(SeekFrom::Start(element.body))?;
+    input
+        .take(element.end - element.body)
+        .read_to_end(&mut vec)?;
+    Ok(vec)
+}
+
+struct SimpleTag {
+    name: String,
+    value: Option<String>,
+    // the nested SORT_WITH tag
+    sort: Option<String>,
+    raw: Vec<u8>,
+}
+
+struct Tag {
+    level: u64,
+    // tags for a certain track or chapter are left alone
+    targeted: bool,
+    targets: Vec<u8>,
+    simple: Vec<SimpleTag>,
+    raw: Vec<u8>,
+}
+
+fn read_simple(body: &[u8]) -> SimpleTag {
+    let mut simple = SimpleTag {
+        name: String::new(),
+        value: None,
+        sort: None,
+        raw: new_element(SIMPLE_TAG, body),
+    };
+    for (id, value) in children(body) {
+        match id {
+            TAG_NAME => simple.name = decode_string(value),
+            TAG_STRING => simple.value = Some(decode_string(value)),
+            SIMPLE_TAG => {
+                let nested = read_simple(value);
+                if nested.name.eq_ignore_ascii_case("SORT_WITH") {
+                    simple.sort = nested.value;
+                }
+            }
+            _ => (),
+        }
+    }
+    simple
+}
+
+fn read_tags(body: &[u8]) -> Vec<Tag> {
+    let mut list = Vec::new();
+    for (_, value) in children(body).into_iter().filter(|&(id, _)| id == TAG) {
+        let mut tag = Tag {
+            // the default target level is the album
+            level: ALBUM,
+            targeted: false,
+            targets: Vec::new(),
+            simple: Vec::new(),
+            raw: new_element(TAG, value),
+        };
+        for (id, value) in children(value) {
+            match id {
+                TARGETS => {
+                    tag.targets = new_element(TARGETS, value);
+                    for (id, value) in children(value) {
+                        if id == TARGET_TYPE_VALUE {
+                            tag.level = decode_uint(value);
+                        } else if TARGET_UIDS.contains(&id) && decode_uint(value) != 0 {
+                            tag.targeted = true;
+                        }
+                    }
+                }
+                SIMPLE_TAG => tag.simple.push(read_simple(value)),
+                _ => (),
+            }
+        }
+        list.push(tag);
+    }
+    list
+}
+
+struct Attachment {
+    name: String,
+    mime: String,
+    data: Vec<u8>,
+    raw: Vec<u8>,
+}
+
+fn read_attachments(body: &[u8]) -> Vec<Attachment> {
+    let mut files = Vec::new();
+    for (_, value) in children(body)
+        .into_iter()
+        .filter(|&(id, _)| id == ATTACHED_FILE)
+    {
+        let mut file = Attachment {
+            name: String::new(),
+            mime: String::new(),
+            data: Vec::new(),
+            raw: new_element(ATTACHED_FILE, value),
+        };
+        for (id, value) in children(value) {
+            match id {
+                FILE_NAME => file.name = decode_string(value),
+                FILE_MIME_TYPE => file.mime = decode_string(value),
+                FILE_DATA => file.data = value.to_vec(),
+                _ => (),
+            }
+        }
+        files.push(file);
+    }
+    files
+}
+
+// by convention the front cover is called cover.jpg or cover.png,
+// otherwise we take the first image
+fn find_cover(files: &[Attachment]) -> Option<usize> {
+    files
+        .iter()
+        .position(|f| f.name.to_lowercase().starts_with("cover."))
+        .or_else(|| {
+            files
+                .iter()
+                .position(|f| f.mime == "image/jpeg" || f.mime == "image/png")
+        })
+}
+
+// all Tag elements and attachments in the segment
+fn read_all<T: Read + Seek>(
+    input: &mut T,
+    segment: &Segment,
+) -> Result<(Vec<Tag>, Vec<Attachment>), Error> {
+    let mut list = Vec::new();
+    let mut files = Vec::new();
+    for element in segment.children.iter() {
+        match element.id {
+            TAGS => list.append(&mut read_tags(&read_body(input, element)?)),
+            ATTACHMENTS => files.append(&mut read_attachments(&read_body(input, element)?)),
+            _ => (),
+        }
+    }
+    Ok((list, files))
+}
+
+// some album level tags are read as if they were set on the track
+fn field_level(level: u64, name: &str) -> u64 {
+    match level == ALBUM && ALBUM_FALLBACK.contains(&name) {
+        true => TRACK,
+        false => level,
+    }
+}
+
+// map a SimpleTag onto tags, returns false if we don't know it
+fn read_field(tags: &mut Tags, level: u64, simple: &SimpleTag) -> bool {
+    let name = simple.name.to_uppercase();
+    let value = match simple.value {
+        Some(ref x) => x.clone(),
+        None => return true,
+    };
+    let sort = simple.sort.clone().into();
+
+    match (field_level(level, &name), name.as_str()) {
+        (TRACK, "TITLE") => {
+            fill(&mut tags.title, TagOption::Some(value));
+            fill(&mut tags.sort_title, sort);
+        }
+        (TRACK, "ARTIST") => {
+            fill(&mut tags.artist, TagOption::Some(value));
+            fill(&mut tags.sort_artist, sort);
+        }
+        (TRACK, "COMPOSER") => {
+            fill(&mut tags.composer, TagOption::Some(value));
+            fill(&mut tags.sort_composer, sort);
+        }
+        (TRACK, "GENRE") => fill(&mut tags.genre, TagOption::Some(value)),
+        (TRACK, "DATE_RELEASED") => fill(&mut tags.date, DateTime::from_iso_8601(&value).into()),
+        (TRACK, "PART_NUMBER") => fill(&mut tags.track_number, tags::number(&value)),
+        (TRACK, "COMMENT") => fill(&mut tags.comment, TagOption::Some(value)),
+        (TRACK, "COPYRIGHT") => fill(&mut tags.copyright, TagOption::Some(value)),
+        (TRACK, "BPM") => fill(&mut tags.bpm, tags::number(&value)),
+
+        (ALBUM, "TITLE") => {
+            fill(&mut tags.album, TagOption::Some(value));
+            fill(&mut tags.sort_album, sort);
+        }
+        (ALBUM, "ARTIST") => {
+            fill(&mut tags.album_artist, TagOption::Some(value));
+            fill(&mut tags.sort_album_artist, sort);
+        }
+        (ALBUM, "TOTAL_PARTS") => fill(&mut tags.track_total, tags::number(&value)),
+
+        (DISC, "PART_NUMBER") => fill(&mut tags.disc_number, tags::number(&value)),
+        (DISC, "TOTAL_PARTS") => fill(&mut tags.disc_total, tags::number(&value)),
+
+        _ => return false,
+    }
+    true
+}
+
+fn collect_tags(list: &[Tag], files: &[Attachment]) -> Tags {
+    let mut tags = Tags::none();
+    let mut custom = BTreeMap::new();
+
+    // the most specific level wins
+    for &level in [TRACK, ALBUM, DISC].iter() {
+        for tag in list.iter().filter(|t| !t.targeted && t.level == level) {
+            for simple in tag.simple.iter() {
+                if !read_field(&mut tags, level, simple) {
+                    let value = simple.value.clone().unwrap_or_default();
+                    custom.entry(simple.name.clone()).or_insert(value);
+                }
+            }
+        }
+    }
+
+    if !custom.is_empty() {
+        tags.custom = TagOption::Some(custom);
+    }
+    if let Some(i) = find_cover(files) {
+        tags.front_cover = TagOption::Some(Image::from_bytes(&files[i].data));
+    }
+    tags
+}
+
+pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    let segment = read_segment(input)?;
+    let (list, files) = read_all(input, &segment)?;
+    Ok(collect_tags(&list, &files))
+}
+
+fn text(value: &TagOption<String>) -> Option<String> {
+    match value {
+        TagOption::Some(ref x) if x.as_str() != "" => Some(x.clone()),
+        _ => None,
+    }
+}
+
+fn number(value: &TagOption<i64>) -> Option<String> {
+    match value {
+        TagOption::Some(x) => Some(x.to_string()),
+        _ => None,
+    }
+}
+
+// level, name, value and sort value of everything we write
+fn fields(tags: &Tags) -> Vec<(u64, &'static str, Option<String>, Option<String>)> {
+    let date = match tags.date {
+        TagOption::Some(ref x) => Some(x.to_iso_8601()),
+        _ => None,
+    };
+    vec![
+        (ALBUM, "TITLE", text(&tags.album), text(&tags.sort_album)),
+        (
+            ALBUM,
+            "ARTIST",
+            text(&tags.album_artist),
+            text(&tags.sort_album_artist),
+        ),
+        (ALBUM, "TOTAL_PARTS", number(&tags.track_total), None),
+        (TRACK, "TITLE", text(&tags.title), text(&tags.sort_title)),
+        (TRACK, "ARTIST", text(&tags.artist), text(&tags.sort_artist)),
+        (
+            TRACK,
+            "COMPOSER",
+            text(&tags.composer),
+            text(&tags.sort_composer),
+        ),
+        (TRACK, "GENRE", text(&tags.genre), None),
+        (TRACK, "DATE_RELEASED", date, None),
+        (TRACK, "PART_NUMBER", number(&tags.track_number), None),
+        (TRACK, "COMMENT", text(&tags.comment), None),
+        (TRACK, "COPYRIGHT", text(&tags.copyright), None),
+        (TRACK, "BPM", number(&tags.bpm), None),
+        (DISC, "PART_NUMBER", number(&tags.disc_number), None),
+        (DISC, "TOTAL_PARTS", number(&tags.disc_total), None),
+    ]
+}
+
+fn new_simple(name: &str, value: &str, sort: Option<&String>) -> Vec<u8> {
+    let mut body = new_string(TAG_NAME, name);
+    body.append(&mut new_string(TAG_STRING, value));
+    if let Some(x) = sort {
+        body.append(&mut new_simple("SORT_WITH", x, None));
+    }
+    new_element(SIMPLE_TAG, &body)
+}
+
+// a Tag element to write: the Targets and SimpleTags of a level we manage,
+// or one we leave alone
+struct NewTag {
+    level: Option<u64>,
+    head: Vec<u8>,
+    simple: Vec<Vec<u8>>,
+}
+
+// the contents of the Tags element, empty if there is nothing to write;
+// tags we don't know about are kept where they were, new ones go to the album
+fn render_tags(tags: &Tags, list: Vec<Tag>) -> Vec<u8> {
+    let fields = fields(tags);
+    let mut custom = match tags.custom {
+        TagOption::Some(ref x) => x.clone(),
+        _ => BTreeMap::new(),
+    };
+
+    let mut new: Vec<NewTag> = Vec::new();
+    for tag in list {
+        if tag.targeted || ![TRACK, ALBUM, DISC].contains(&tag.level) {
+            new.push(NewTag {
+                level: None,
+                head: tag.raw,
+                simple: Vec::new(),
+            });
+            continue;
+        }
+
+        let mut simple = Vec::new();
+        for old in tag.simple {
+            let name = old.name.to_uppercase();
+            let level = field_level(tag.level, &name);
+            if fields.iter().any(|f| f.0 == level && f.1 == name) {
+                continue;
+            }
+            // binary values aren't in custom, so they stay as they are
+            if old.value.is_none() {
+                simple.push(old.raw);
+                continue;
+            }
+            match custom.remove(&old.name) {
+                Some(ref x) if Some(x) == old.value.as_ref() => simple.push(old.raw),
+                Some(ref x) => simple.push(new_simple(&old.name, x, None)),
+                None => (),
+            }
+        }
+        new.push(NewTag {
+            level: Some(tag.level),
+            head: tag.targets,
+            simple,
+        });
+    }
+
+    for &level in [ALBUM, TRACK, DISC].iter() {
+        let i = match new.iter().position(|t| t.level == Some(level)) {
+            Some(i) => i,
+            None => {
+                new.push(NewTag {
+                    level: Some(level),
+                    head: new_element(TARGETS, &new_uint(TARGET_TYPE_VALUE, level)),
+                    simple: Vec::new(),
+                });
+                new.len() - 1
+            }
+        };
+        let mut simple = Vec::new();
+        for (_, name, value, sort) in fields.iter().filter(|f| f.0 == level) {
+            if let Some(x) = value {
+                simple.push(new_simple(name, x, sort.as_ref()));
+            }
+        }
+        if level == ALBUM {
+            for (name, value) in custom.iter() {
+                simple.push(new_simple(name, value, None));
+            }
+        }
+        // the mapped fields go first
+        simple.append(&mut new[i].simple);
+        new[i].simple = simple;
+    }
+
+    let mut vec = Vec::new();
+    for tag in new {
+        match tag.level {
+            None => vec.extend_from_slice(&tag.head),
+            Some(_) if tag.simple.is_empty() => (),
+            Some(_) => vec.append(&mut new_element(
+                TAG,
+                &[tag.head, tag.simple.concat()].concat(),
+            )),
+        }
+    }
+    vec
+}
+
+// the contents of the Attachments element, with the cover replaced
+fn render_attachments(
+    cover: &TagOption<Image>,
+    files: Vec<Attachment>,
+    old_cover: Option<usize>,
+) -> Vec<u8> {
+    let mut vec = Vec::new();
+    for (i, file) in files.into_iter().enumerate() {
+        if Some(i) != old_cover {
+            vec.extend_from_slice(&file.raw);
+        }
+    }
+
+    if let TagOption::Some(ref image) = cover {
+        let (name, data) = match image {
+            Image::JPEG(ref x) => ("cover.jpg", x),
+            Image::PNG(ref x) => ("cover.png", x),
+            Image::None => return vec,
+        };
+        let mut body = new_string(FILE_NAME, name);
+        body.append(&mut new_string(FILE_MIME_TYPE, &image.mime()));
+        body.append(&mut new_element(FILE_DATA, data));
+        // any unique number will do
+        body.append(&mut new_uint(FILE_UID, checksum_ieee(data) as u64 + 1));
+        vec.append(&mut new_element(ATTACHED_FILE, &body));
+    }
+    vec
+}
+
+pub fn set<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    new: &Tags,
+) -> Result<(), Error> {
+    let segment = read_segment(input)?;
+    let (list, files) = read_all(input, &segment)?;
+    let old = collect_tags(&list, &files);
+    let tags = delta(&old, new);
+
+    let mut replace = vec![(TAGS, render_tags(&tags, list))];
+    if !segment.webm && tags.front_cover != old.front_cover {
+        let cover = find_cover(&files);
+        replace.push((
+            ATTACHMENTS,
+            render_attachments(&tags.front_cover, files, cover),
+        ));
+    }
+
+    rewrite(input, output, &segment, replace)
+}
+
+// copy the file without the Tags element and the cover attachment
+pub fn remove<R: Read + Seek, W: Write>(input: &mut R, output: &mut W) -> Result<(), Error> {
+    let segment = read_segment(input)?;
+    let (_, files) = read_all(input, &segment)?;
+
+    let mut replace = vec![(TAGS, Vec::new())];
+    if let Some(cover) = find_cover(&files) {
+        replace.push((
+            ATTACHMENTS,
+            render_attachments(&TagOption::None, files, Some(cover)),
+        ));
+    }
+
+    rewrite(input, output, &segment, replace)
+}
+
+// the entries of a SeekHead as element id and position
+fn read_seeks(body: &[u8]) -> Vec<(Vec<u8>, u64)> {
+    let mut seeks = Vec::new();
+    for (_, value) in children(body).into_iter().filter(|&(id, _)| id == SEEK) {
+        let mut id = Vec::new();
+        let mut position = None;
+        for (child, value) in children(value) {
+            match child {
+                SEEK_ID => id = value.to_vec(),
+                SEEK_POSITION => position = Some(decode_uint(value)),
+                _ => (),
+            }
+        }
+        if let Some(x) = position {
+            seeks.push((id, x));
+        }
+    }
+    seeks
+}
+
+fn render_seeks(seeks: &[(Vec<u8>, u64)]) -> Vec<u8> {
+    let mut vec = Vec::new();
+    for (id, position) in seeks.iter() {
+        let mut body = new_element(SEEK_ID, id);
+        body.append(&mut new_uint(SEEK_POSITION, *position));
+        vec.append(&mut new_element(SEEK, &body));
+    }
+    vec
+}
+
+// the end of an element and any unused Void elements right after it
+fn slot(children: &[Element], used: &mut [bool], i: usize) -> u64 {
+    let mut end = children[i].end;
+    for (j, child) in children.iter().enumerate().skip(i + 1) {
+        if child.id != VOID || used[j] {
+            break;
+        }
+        used[j] = true;
+        end = child.end;
+    }
+    end - children[i].start
+}
+
+// copy the file, replacing the contents of the given elements (empty to remove
+// them); elements that don't fit where they were are moved to the end of the
+// segment, so that nothing else has to move, and the SeekHead is updated
+fn rewrite<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    segment: &Segment,
+    replace: Vec<(u32, Vec<u8>)>,
+) -> Result<(), Error> {
+    let children = &segment.children;
+    let mut used = vec![false; children.len()];
+    let mut edits: Vec<(u64, u64, Vec<u8>)> = Vec::new();
+    let mut appended = Vec::new();
+    let mut positions = Vec::new();
+
+    for (id, body) in replace.iter() {
+        let mut position = None;
+        for (i, child) in children.iter().enumerate() {
+            if child.id != *id || used[i] {
+                continue;
+            }
+            used[i] = true;
+
+            // the first one is overwritten if there is room, the others are voided
+            if position.is_none() && !body.is_empty() {
+                let mut reserved = used.clone();
+                let length = slot(children, &mut reserved, i);
+                if let Some(vec) = fit(*id, body, length) {
+                    used = reserved;
+                    edits.push((child.start, length, vec));
+                    position = Some(child.start);
+                    continue;
+                }
+            }
+            edits.push((
+                child.start,
+                child.end - child.start,
+                void(child.end - child.start),
+            ));
+        }
+
+        if position.is_none() && !body.is_empty() {
+            position = Some(segment.end + appended.len() as u64);
+            appended.append(&mut new_element(*id, body));
+        }
+        positions.push((encode_id(*id), position));
+    }
+
+    if let Some(i) = children.iter().position(|c| c.id == SEEKHEAD) {
+        let old = read_seeks(&read_body(input, &children[i])?);
+        // without room for the new entries, the stale ones are dropped at least
+        let pruned: Vec<_> = old
+            .into_iter()
+            .filter(|(id, _)| positions.iter().all(|(x, _)| x != id))
+            .collect();
+        let mut updated = pruned.clone();
+        for (id, position) in positions.iter() {
+            if let Some(x) = position {
+                updated.push((id.clone(), x - segment.body));
+            }
+        }
+
+        let length = slot(children, &mut used, i);
+        let vec = fit(SEEKHEAD, &render_seeks(&updated), length)
+            .or_else(|| fit(SEEKHEAD, &render_seeks(&pruned), length));
+        match vec {
+            Some(vec) => edits.push((children[i].start, length, vec)),
+            None => return Err(tag_error("Could not update the Matroska SeekHead")),
+        }
+    }
+
+    if let Some((pos, length)) = segment.size_field {
+        let size = segment.end - segment.body + appended.len() as u64;
+        if size >= (1 << (7 * length)) - 1 {
+            return Err(tag_error("Matroska segment size doesn't fit"));
+        }
+        edits.push((pos, length as u64, encode_size(size, length)));
+    }
+
+    edits.sort_by_key(|e| e.0);
+    let mut pos = 0;
+    for (start, length, vec) in edits.iter() {
+        input.seek(SeekFrom::Start(pos))?;
+        std::io::copy(&mut input.by_ref().take(start - pos), output)?;
+        output.write_all(vec)?;
+        pos = start + length;
+    }
+    input.seek(SeekFrom::Start(pos))?;
+    std::io::copy(&mut input.by_ref().take(segment.end - pos), output)?;
+    output.write_all(&appended)?;
+
+    // whatever trails the segment stays where it is
+    input.seek(SeekFrom::Start(segment.end))?;
+    std::io::copy(
+        &mut input.by_ref().take(segment.length - segment.end),
+        output,
+    )?;
+
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,267 @@
+use crate::DateTime;
+use crate::Image;
+use crate::TagOption;
+use crate::Tags;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+use super::new_element;
+use super::new_simple;
+use super::new_uint;
+
+fn tag(level: u64, simple: &[Vec<u8>]) -> Vec<u8> {
+    let targets = new_element(super::TARGETS, &new_uint(super::TARGET_TYPE_VALUE, level));
+    new_element(super::TAG, &[targets, simple.concat()].concat())
+}
+
+// the EBML header and a segment with an 8 byte size, like mkvmerge writes
+fn file(doc_type: &str, children: &[Vec<u8>], known: bool) -> Vec<u8> {
+    let mut vec = new_element(super::EBML, &super::new_string(super::DOC_TYPE, doc_type));
+    let body = children.concat();
+    vec.extend_from_slice(&super::encode_id(super::SEGMENT));
+    match known {
+        true => vec.append(&mut super::encode_size(body.len() as u64, 8)),
+        false => vec.push(0xFF),
+    }
+    vec.extend_from_slice(&body);
+    vec
+}
+
+fn cluster(known: bool) -> Vec<u8> {
+    let body = [
+        new_uint(0xE7, 0),
+        new_element(0xA3, &[0x81, 0, 0, 0x80, 1, 2, 3, 4]),
+    ]
+    .concat();
+    match known {
+        true => new_element(0x1F43_B675, &body),
+        false => [&b"\x1F\x43\xB6\x75\xFF"[..], &body].concat(),
+    }
+}
+
+fn mka() -> Vec<u8> {
+    let info = new_element(0x1549_A966, &new_uint(0x2A_D7B1, 1_000_000));
+    let tracks = new_element(0x1654_AE6B, &new_element(0xAE, &new_uint(0xD7, 1)));
+    let tags = new_element(
+        super::TAGS,
+        &[
+            tag(
+                50,
+                &[
+                    new_simple("TITLE", "Album", None),
+                    new_simple("ARTIST", "Album Artist", None),
+                    new_simple("TOTAL_PARTS", "12", None),
+                    new_simple("GENRE", "Album Genre", None),
+                    new_simple("ENCODER", "Encoder", None),
+                ],
+            ),
+            tag(
+                30,
+                &[
+                    new_simple("TITLE", "Title", Some(&"Title, The".to_string())),
+                    new_simple("PART_NUMBER", "3", None),
+                    new_simple("DATE_RELEASED", "2019-03-04", None),
+                    new_simple("GENRE", "Track Genre", None),
+                ],
+            ),
+            tag(60, &[new_simple("PART_NUMBER", "1", None)]),
+            // only about the track with this uid, left alone
+            new_element(
+                super::TAG,
+                &[
+                    new_element(super::TARGETS, &new_uint(0x63C5, 1234)),
+                    new_simple("TITLE", "Track Specific", None),
+                ]
+                .concat(),
+            ),
+        ]
+        .concat(),
+    );
+
+    // seek positions are relative to the segment contents
+    let seek = |id: u32, position: usize| {
+        new_element(
+            super::SEEK,
+            &[
+                new_element(super::SEEK_ID, &super::encode_id(id)),
+                new_uint(super::SEEK_POSITION, position as u64),
+            ]
+            .concat(),
+        )
+    };
+    let cluster = cluster(true);
+    let seekhead = [
+        seek(0x1549_A966, 100),
+        seek(super::TAGS, 100 + info.len() + tracks.len() + cluster.len()),
+    ]
+    .concat();
+    let seekhead = super::fit(super::SEEKHEAD, &seekhead, 100).unwrap();
+
+    file("matroska", &[seekhead, info, tracks, cluster, tags], true)
+}
+
+fn segment(vec: &[u8]) -> super::Segment {
+    super::read_segment(&mut Cursor::new(vec)).unwrap()
+}
+
+// every SeekHead entry points at the element it says
+fn check_seeks(vec: &[u8]) {
+    let segment = segment(vec);
+    let seekhead = segment.children.iter().find(|c| c.id == super::SEEKHEAD);
+    let body = super::read_body(&mut Cursor::new(vec), seekhead.unwrap()).unwrap();
+    for (id, position) in super::read_seeks(&body) {
+        let (found, _) = super::read_id(&vec[(segment.body + position) as usize..]).unwrap();
+        assert_eq!(super::encode_id(found), id);
+    }
+}
+
+#[test]
+fn matroska_get_test() {
+    let tags = super::get(&mut Cursor::new(mka())).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("Title".to_string()),
+        sort_title: TagOption::Some("Title, The".to_string()),
+        album: TagOption::Some("Album".to_string()),
+        album_artist: TagOption::Some("Album Artist".to_string()),
+        genre: TagOption::Some("Track Genre".to_string()),
+        date: DateTime::from_iso_8601("2019-03-04").into(),
+        track_number: TagOption::Some(3),
+        track_total: TagOption::Some(12),
+        disc_number: TagOption::Some(1),
+        custom: TagOption::Some({
+            let mut map = BTreeMap::new();
+            map.insert("ENCODER".to_string(), "Encoder".to_string());
+            map
+        }),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+}
+
+#[test]
+fn matroska_set_test() {
+    let original = mka();
+
+    // a shorter title fits where the old tags were
+    let new = Tags {
+        title: TagOption::Some("T".to_string()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&original), &mut output, &new).unwrap();
+    assert_eq!(output.len(), original.len());
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("T".to_string()));
+    assert_eq!(tags.genre, TagOption::Some("Track Genre".to_string()));
+
+    // more than that goes to the end of the segment, along with the cover
+    let new = Tags {
+        title: TagOption::Some("A much longer title than before".to_string()),
+        artist: TagOption::Some("Artist".to_string()),
+        front_cover: TagOption::Some(Image::PNG(b"\x89PNG not really".to_vec())),
+        ..Tags::mixed()
+    };
+    let mut moved = Vec::new();
+    super::set(&mut Cursor::new(&output), &mut moved, &new).unwrap();
+    assert!(moved.len() > output.len());
+    // nothing between the SeekHead and the old tags moved
+    let old = segment(&output);
+    let start = old
+        .children
+        .iter()
+        .find(|c| c.id == 0x1549_A966)
+        .unwrap()
+        .start as usize;
+    let end = old
+        .children
+        .iter()
+        .find(|c| c.id == super::TAGS)
+        .unwrap()
+        .start as usize;
+    assert_eq!(&moved[start..end], &output[start..end]);
+
+    let segment = segment(&moved);
+    assert_eq!(segment.end, moved.len() as u64);
+    let ids: Vec<u32> = segment
+        .children
+        .iter()
+        .map(|c| c.id)
+        .filter(|&id| id != super::VOID)
+        .collect();
+    assert_eq!(
+        &ids[ids.len() - 2..],
+        &[super::TAGS, super::ATTACHMENTS][..]
+    );
+    check_seeks(&moved);
+
+    let tags = super::get(&mut Cursor::new(&moved)).unwrap();
+    assert_eq!(
+        tags.title,
+        TagOption::Some("A much longer title than before".to_string())
+    );
+    assert_eq!(tags.artist, TagOption::Some("Artist".to_string()));
+    assert_eq!(tags.album, TagOption::Some("Album".to_string()));
+    assert_eq!(tags.disc_number, TagOption::Some(1));
+    assert_eq!(
+        tags.front_cover,
+        TagOption::Some(Image::PNG(b"\x89PNG not really".to_vec()))
+    );
+
+    // the track specific tag is still there
+    let (list, _) = super::read_all(&mut Cursor::new(&moved), &segment).unwrap();
+    assert!(list
+        .iter()
+        .any(|t| t.targeted && t.simple[0].value.as_deref() == Some("Track Specific")));
+
+    let mut bare = Vec::new();
+    super::remove(&mut Cursor::new(&moved), &mut bare).unwrap();
+    assert_eq!(bare.len(), moved.len());
+    assert_eq!(super::get(&mut Cursor::new(&bare)).unwrap(), Tags::none());
+    check_seeks(&bare);
+}
+
+#[test]
+fn matroska_live_test() {
+    // how browsers record: unknown sizes, no SeekHead and no tags
+    let info = new_element(0x1549_A966, &new_uint(0x2A_D7B1, 1_000_000));
+    let original = file("webm", &[info, cluster(false), cluster(false)], false);
+    assert_eq!(
+        super::get(&mut Cursor::new(&original)).unwrap(),
+        Tags::none()
+    );
+    assert_eq!(segment(&original).children.len(), 3);
+
+    let new = Tags {
+        title: TagOption::Some("Recording".to_string()),
+        front_cover: TagOption::Some(Image::JPEG(vec![0xFF, 0xD8, 0xFF])),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&original), &mut output, &new).unwrap();
+
+    assert_eq!(&output[..original.len()], &original[..]);
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    // WebM has no attachments
+    let ideal = Tags {
+        title: TagOption::Some("Recording".to_string()),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+}
+
+#[test]
+fn void_test() {
+    for length in [2, 3, 128, 129, 130, 20000].iter() {
+        let vec = super::void(*length);
+        assert_eq!(vec.len() as u64, *length);
+        let (id, size, header) = super::read_header(&vec).unwrap();
+        assert_eq!(id, super::VOID);
+        assert_eq!(size.unwrap() + header as u64, *length);
+    }
+    assert!(super::fit(super::TAGS, &[0; 10], 14).is_none());
+    assert_eq!(super::fit(super::TAGS, &[0; 10], 15).unwrap().len(), 15);
+    // a single byte left over goes into the size field
+    let vec = super::fit(super::TAGS, &[0; 10], 16).unwrap();
+    assert_eq!(super::read_header(&vec), Some((super::TAGS, Some(10), 6)));
+    assert_eq!(super::fit(super::TAGS, &[0; 10], 17).unwrap().len(), 17);
+}
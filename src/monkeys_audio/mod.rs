@@ -0,0 +1,82 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::Error;
+use crate::Properties;
+
+use crate::ape;
+use crate::tools::decode_int_le_u32;
+use crate::tools::tag_error;
+
+// format flags
+const EIGHT_BIT: u32 = 0x1;
+const TWENTY_FOUR_BIT: u32 = 0x8;
+
+// audio properties from the MAC header, which moved behind a descriptor in 3.98
+pub fn get_properties<T: Read + Seek>(input: &mut T) -> Result<Properties, Error> {
+    let mut arr: [u8; 32] = [0; 32];
+    input.seek(SeekFrom::Start(0))?;
+    input.read_exact(&mut arr)?;
+
+    if &arr[0..4] != b"MAC " {
+        return Err(tag_error("Monkey's Audio header not found"));
+    }
+    let version = decode_int_le_u32(&arr[4..6]);
+
+    let (blocks_per_frame, final_frame_blocks, total_frames, bits, channels, sample_rate) =
+        if version >= 3980 {
+            let descriptor = decode_int_le_u32(&arr[8..12]);
+            let mut header: [u8; 24] = [0; 24];
+            input.seek(SeekFrom::Start(descriptor as u64))?;
+            input.read_exact(&mut header)?;
+            (
+                decode_int_le_u32(&header[4..8]),
+                decode_int_le_u32(&header[8..12]),
+                decode_int_le_u32(&header[12..16]),
+                decode_int_le_u32(&header[16..18]),
+                decode_int_le_u32(&header[18..20]),
+                decode_int_le_u32(&header[20..24]),
+            )
+        } else {
+            let compression = decode_int_le_u32(&arr[6..8]);
+            let flags = decode_int_le_u32(&arr[8..10]);
+            let blocks_per_frame = if version >= 3950 {
+                73728 * 4
+            } else if version >= 3900 || (version >= 3800 && compression == 4000) {
+                73728
+            } else {
+                9216
+            };
+            let bits = if flags & EIGHT_BIT != 0 {
+                8
+            } else if flags & TWENTY_FOUR_BIT != 0 {
+                24
+            } else {
+                16
+            };
+            (
+                blocks_per_frame,
+                decode_int_le_u32(&arr[28..32]),
+                decode_int_le_u32(&arr[24..28]),
+                bits,
+                decode_int_le_u32(&arr[10..12]),
+                decode_int_le_u32(&arr[12..16]),
+            )
+        };
+
+    let samples = match total_frames {
+        0 => 0,
+        x => (x as u64 - 1) * blocks_per_frame as u64 + final_frame_blocks as u64,
+    };
+
+    Ok(Properties::from_samples(
+        Some(samples),
+        sample_rate,
+        channels as u8,
+        Some(bits as u8),
+        ape::audio_end(input)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests;
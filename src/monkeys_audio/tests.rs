@@ -0,0 +1,55 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use crate::tools::encode_int_le_u32;
+
+fn le16(x: u16) -> Vec<u8> {
+    vec![x as u8, (x >> 8) as u8]
+}
+
+#[test]
+fn monkeys_audio_new_test() {
+    // 52 byte descriptor, then the header
+    let mut file = b"MAC ".to_vec();
+    file.extend_from_slice(&le16(3990));
+    file.extend_from_slice(&le16(0));
+    file.extend_from_slice(&encode_int_le_u32(52));
+    file.resize(52, 0);
+    file.extend_from_slice(&le16(2000));
+    file.extend_from_slice(&le16(0));
+    file.extend_from_slice(&encode_int_le_u32(73728));
+    file.extend_from_slice(&encode_int_le_u32(14976));
+    file.extend_from_slice(&encode_int_le_u32(3));
+    file.extend_from_slice(&le16(24));
+    file.extend_from_slice(&le16(1));
+    file.extend_from_slice(&encode_int_le_u32(48000));
+    file.resize(4000, 0);
+
+    let properties = super::get_properties(&mut Cursor::new(file)).unwrap();
+    assert_eq!(properties.duration, Some(Duration::from_millis(3384)));
+    assert_eq!(properties.sample_rate, Some(48000));
+    assert_eq!(properties.channels, Some(1));
+    assert_eq!(properties.bits_per_sample, Some(24));
+    assert_eq!(properties.bitrate, Some(9));
+}
+
+#[test]
+fn monkeys_audio_old_test() {
+    let mut file = b"MAC ".to_vec();
+    file.extend_from_slice(&le16(3970));
+    file.extend_from_slice(&le16(2000));
+    file.extend_from_slice(&le16(0x8));
+    file.extend_from_slice(&le16(2));
+    file.extend_from_slice(&encode_int_le_u32(44100));
+    file.extend_from_slice(&[0; 8]);
+    file.extend_from_slice(&encode_int_le_u32(1));
+    file.extend_from_slice(&encode_int_le_u32(88200));
+    file.resize(1000, 0);
+
+    let properties = super::get_properties(&mut Cursor::new(file)).unwrap();
+    assert_eq!(properties.duration, Some(Duration::from_secs(2)));
+    assert_eq!(properties.channels, Some(2));
+    assert_eq!(properties.bits_per_sample, Some(24));
+
+    assert!(super::get_properties(&mut Cursor::new(b"fLaC".repeat(10))).is_err());
+}
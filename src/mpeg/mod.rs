@@ -0,0 +1,143 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::Error;
+use crate::Properties;
+
+use crate::ape;
+use crate::id3v2;
+use crate::tools::tag_error;
+
+// kbit/s by bitrate index, for MPEG-1 Layer I and II, then MPEG-2/2.5 Layer I and II
+const BITRATES: [[u32; 15]; 4] = [
+    [
+        0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+    ],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+// for MPEG-1, MPEG-2 and MPEG-2.5
+const SAMPLE_RATES: [[u32; 3]; 3] = [
+    [44100, 48000, 32000],
+    [22050, 24000, 16000],
+    [11025, 12000, 8000],
+];
+
+// how far into the audio we look for the first frame
+const MAX_SCAN: u64 = 0x10000;
+
+pub struct Frame {
+    pub length: u64,
+    pub samples: u64,
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+// an MPEG audio Layer I or II frame header
+fn parse_frame(arr: &[u8]) -> Option<Frame> {
+    if arr.len() < 4 || arr[0] != 0xFF || arr[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+    let version = match (arr[1] >> 3) & 3 {
+        3 => 0,
+        2 => 1,
+        0 => 2,
+        _ => return None,
+    };
+    // Layer III is 1, and is left to the mp3 code
+    let layer_1 = match (arr[1] >> 1) & 3 {
+        3 => true,
+        2 => false,
+        _ => return None,
+    };
+    // free format (0) isn't supported
+    let bitrate = match arr[2] >> 4 {
+        0 | 15 => return None,
+        x => BITRATES[(version > 0) as usize * 2 + !layer_1 as usize][x as usize] * 1000,
+    };
+    let sample_rate = match (arr[2] >> 2) & 3 {
+        3 => return None,
+        x => SAMPLE_RATES[version][x as usize],
+    };
+    let padding = ((arr[2] >> 1) & 1) as u64;
+
+    let (length, samples) = match layer_1 {
+        true => (
+            (12 * bitrate as u64 / sample_rate as u64 + padding) * 4,
+            384,
+        ),
+        false => (144 * bitrate as u64 / sample_rate as u64 + padding, 1152),
+    };
+
+    Some(Frame {
+        length,
+        samples,
+        sample_rate,
+        channels: if arr[3] >> 6 == 3 { 1 } else { 2 },
+    })
+}
+
+// the first frame that is followed by another one (or the end of the audio),
+// so that some random 0xFF doesn't throw us off
+pub fn find_frame<T, F>(input: &mut T, start: u64, end: u64, parse: F) -> Result<u64, Error>
+where
+    T: Read + Seek,
+    F: Fn(&[u8]) -> Option<u64>,
+{
+    let mut vec = Vec::new();
+    input.seek(SeekFrom::Start(start))?;
+    input
+        .take(std::cmp::min(MAX_SCAN, end.saturating_sub(start)))
+        .read_to_end(&mut vec)?;
+
+    for i in 0..vec.len() {
+        if let Some(length) = parse(&vec[i..]) {
+            let next = i + length as usize;
+            if start + next as u64 >= end || next >= vec.len() || parse(&vec[next..]).is_some() {
+                return Ok(start + i as u64);
+            }
+        }
+    }
+    Err(tag_error("No audio frames found"))
+}
+
+// audio properties from walking the frames of an MPEG Layer I or II stream
+pub fn get_properties<T: Read + Seek>(input: &mut T) -> Result<Properties, Error> {
+    let end = ape::audio_end(input)?;
+    let start = id3v2::skip(input)?;
+    let mut pos = find_frame(input, start, end, |x| parse_frame(x).map(|f| f.length))?;
+
+    let mut first = None;
+    let mut samples = 0;
+    let mut bytes = 0;
+    let mut arr: [u8; 4] = [0; 4];
+    while pos + 4 <= end {
+        input.seek(SeekFrom::Start(pos))?;
+        input.read_exact(&mut arr)?;
+        let frame = match parse_frame(&arr) {
+            Some(x) => x,
+            None => break,
+        };
+        samples += frame.samples;
+        bytes += frame.length;
+        pos += frame.length;
+        first = first.or(Some(frame));
+    }
+
+    let first = first.ok_or_else(|| tag_error("No audio frames found"))?;
+    Ok(Properties::from_samples(
+        Some(samples),
+        first.sample_rate,
+        first.channels,
+        None,
+        std::cmp::min(bytes, end - start),
+    ))
+}
+
+#[cfg(test)]
+mod tests;
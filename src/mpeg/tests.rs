@@ -0,0 +1,45 @@
+use crate::Properties;
+use std::io::Cursor;
+use std::time::Duration;
+
+// MPEG-1 Layer II at 192 kbit/s and 48000 Hz, 576 bytes and 24 ms a frame
+fn frame(mono: bool) -> Vec<u8> {
+    let mut vec = vec![0xFF, 0xFD, 0xA4, if mono { 0xC0 } else { 0 }];
+    vec.resize(576, 0x55);
+    vec
+}
+
+#[test]
+fn mpeg_properties_test() {
+    // an ID3v2 tag, a bit of junk with a false sync, then 50 frames
+    let mut file = b"ID3\x03\x00\x00\x00\x00\x00\x05\0\0\0\0\0".to_vec();
+    file.extend_from_slice(&[0xFF, 0xFD, 0xA4, 0x00, 0x12]);
+    for _ in 0..50 {
+        file.append(&mut frame(false));
+    }
+    assert_eq!(crate::id3v2::skip(&mut Cursor::new(&file)).unwrap(), 15);
+
+    let properties = super::get_properties(&mut Cursor::new(&file)).unwrap();
+    let ideal = Properties {
+        duration: Some(Duration::from_millis(1200)),
+        sample_rate: Some(48000),
+        channels: Some(2),
+        bits_per_sample: None,
+        bitrate: Some(192),
+    };
+    assert_eq!(properties, ideal);
+
+    // an id3v1 tag at the end stops the walk
+    let mut file = frame(true);
+    file.append(&mut frame(true));
+    file.extend_from_slice(b"TAG");
+    file.resize(file.len() + 125, 0);
+    let properties = super::get_properties(&mut Cursor::new(&file)).unwrap();
+    assert_eq!(properties.channels, Some(1));
+    assert_eq!(properties.duration, Some(Duration::from_millis(48)));
+
+    // Layer III is not ours
+    let mut file = frame(false);
+    file[1] = 0xFB;
+    assert!(super::get_properties(&mut Cursor::new(file)).is_err());
+}
@@ -0,0 +1,107 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::Error;
+use crate::Properties;
+
+use crate::ape;
+use crate::tools::decode_int_le_u32;
+use crate::tools::tag_error;
+
+const SAMPLE_RATES: [u32; 4] = [44100, 48000, 37800, 32000];
+const FRAME_SAMPLES: u64 = 1152;
+
+// SV8 packets before the stream header we're willing to skip
+const MAX_PACKETS: usize = 16;
+
+// audio properties from the SV7 or SV8 stream header
+pub fn get_properties<T: Read + Seek>(input: &mut T) -> Result<Properties, Error> {
+    let mut arr: [u8; 4] = [0; 4];
+    input.seek(SeekFrom::Start(0))?;
+    input.read_exact(&mut arr)?;
+
+    let (samples, sample_rate, channels) = if &arr == b"MPCK" {
+        read_sv8(input)?
+    } else if &arr[0..3] == b"MP+" && arr[3] & 0xF == 7 {
+        read_sv7(input)?
+    } else {
+        return Err(tag_error("Musepack SV7/SV8 header not found"));
+    };
+
+    Ok(Properties::from_samples(
+        Some(samples),
+        sample_rate,
+        channels,
+        None,
+        ape::audio_end(input)?,
+    ))
+}
+
+fn read_sv7<T: Read + Seek>(input: &mut T) -> Result<(u64, u32, u8), Error> {
+    let mut arr: [u8; 24] = [0; 24];
+    input.seek(SeekFrom::Start(0))?;
+    input.read_exact(&mut arr)?;
+
+    let frames = decode_int_le_u32(&arr[4..8]) as u64;
+    let sample_rate = SAMPLE_RATES[(decode_int_le_u32(&arr[8..12]) >> 16) as usize & 3];
+
+    // without gapless info the last frame is assumed to be half empty
+    let gapless = decode_int_le_u32(&arr[20..24]);
+    let unused = match gapless >> 31 {
+        1 => FRAME_SAMPLES - ((gapless >> 20) & 0x7FF) as u64,
+        _ => FRAME_SAMPLES / 2,
+    };
+
+    Ok((
+        (frames * FRAME_SAMPLES).saturating_sub(unused),
+        sample_rate,
+        2,
+    ))
+}
+
+// sizes and counts in SV8 are big endian, 7 bits per byte
+fn read_varint<T: Read>(input: &mut T) -> Result<(u64, u64), Error> {
+    let mut value = 0;
+    for length in 1..10 {
+        let mut byte: [u8; 1] = [0];
+        input.read_exact(&mut byte)?;
+        value = (value << 7) | (byte[0] & 0x7F) as u64;
+        if byte[0] & 0x80 == 0 {
+            return Ok((value, length));
+        }
+    }
+    Err(tag_error("Invalid Musepack varint"))
+}
+
+fn read_sv8<T: Read + Seek>(input: &mut T) -> Result<(u64, u32, u8), Error> {
+    let mut pos = 4;
+    for _ in 0..MAX_PACKETS {
+        let mut key: [u8; 2] = [0; 2];
+        input.seek(SeekFrom::Start(pos))?;
+        input.read_exact(&mut key)?;
+        // the size includes the key and the size itself
+        let (size, _) = read_varint(input)?;
+
+        if &key == b"SH" {
+            // CRC and stream version
+            input.seek(SeekFrom::Current(5))?;
+            let (samples, _) = read_varint(input)?;
+            let (silence, _) = read_varint(input)?;
+            let mut arr: [u8; 2] = [0; 2];
+            input.read_exact(&mut arr)?;
+            return Ok((
+                samples.saturating_sub(silence),
+                SAMPLE_RATES[(arr[0] >> 5) as usize & 3],
+                (arr[1] >> 4) + 1,
+            ));
+        }
+        if size == 0 {
+            break;
+        }
+        pos += size;
+    }
+    Err(tag_error("Musepack stream header not found"))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,43 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use crate::tools::encode_int_le_u32;
+
+#[test]
+fn musepack_sv7_test() {
+    let mut file = b"MP+\x17".to_vec();
+    file.extend_from_slice(&encode_int_le_u32(100));
+    // 48000 Hz
+    file.extend_from_slice(&encode_int_le_u32(1 << 16));
+    file.extend_from_slice(&[0; 8]);
+    // gapless, with 1152 - 960 samples in the last frame
+    file.extend_from_slice(&encode_int_le_u32(1 << 31 | 192 << 20));
+    file.resize(1000, 0);
+
+    let properties = super::get_properties(&mut Cursor::new(file)).unwrap();
+    assert_eq!(properties.sample_rate, Some(48000));
+    assert_eq!(properties.channels, Some(2));
+    assert_eq!(properties.bits_per_sample, None);
+    assert_eq!(properties.duration, Some(Duration::from_millis(2380)));
+}
+
+#[test]
+fn musepack_sv8_test() {
+    let mut file = b"MPCK".to_vec();
+    // an unknown packet to skip, then the stream header
+    file.extend_from_slice(b"XX\x05\0\0");
+    file.extend_from_slice(b"SH\x0F");
+    file.extend_from_slice(&[0, 0, 0, 0, 8]);
+    // 88200 + 1000 samples, with 1000 of beginning silence
+    file.extend_from_slice(&[0x85, 0xB8, 0x70]);
+    file.extend_from_slice(&[0x87, 0x68]);
+    file.extend_from_slice(&[0x00, 0x10]);
+    file.resize(1000, 0);
+
+    let properties = super::get_properties(&mut Cursor::new(file)).unwrap();
+    assert_eq!(properties.sample_rate, Some(44100));
+    assert_eq!(properties.channels, Some(2));
+    assert_eq!(properties.duration, Some(Duration::from_secs(2)));
+
+    assert!(super::get_properties(&mut Cursor::new(vec![0; 100])).is_err());
+}
@@ -0,0 +1,393 @@
+use std::io::prelude::*;
+use std::io::Cursor;
+use std::io::SeekFrom;
+
+use crate::Error;
+use crate::Image;
+use crate::TagOption;
+use crate::Tags;
+
+use crate::flac;
+use crate::vorbis;
+
+use crate::tools::decode_int_le_u32;
+use crate::tools::decode_int_le_u64;
+use crate::tools::encode_int_be_u32;
+use crate::tools::encode_int_le_u32;
+use crate::tools::encode_int_le_u64;
+use crate::tools::tag_error;
+use crate::tools::tags::delta;
+
+// page header flags
+const CONTINUED: u8 = 0x01;
+const FIRST_PAGE: u8 = 0x02;
+
+// granule position of a page on which no packet ends
+const NO_GRANULE: u64 = 0xFFFF_FFFF_FFFF_FFFF;
+
+lazy_static! {
+    // CRC-32 with polynomial 0x04C11DB7, unreflected and starting at zero
+    static ref CRC_TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        for (i, x) in table.iter_mut().enumerate() {
+            let mut r = (i as u32) << 24;
+            for _ in 0..8 {
+                r = match r & 0x8000_0000 {
+                    0 => r << 1,
+                    _ => (r << 1) ^ 0x04C1_1DB7,
+                };
+            }
+            *x = r;
+        }
+        table
+    };
+}
+
+fn crc(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, &b| {
+        (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ b) as usize]
+    })
+}
+
+pub struct Page {
+    pub flags: u8,
+    pub granule: u64,
+    pub serial: u32,
+    pub sequence: u32,
+    // the lacing values, a packet ends at each one below 255
+    pub segments: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+impl Page {
+    pub fn read<T: Read>(input: &mut T) -> Result<Page, Error> {
+        let mut arr: [u8; 27] = [0; 27];
+        input.read_exact(&mut arr)?;
+
+        if &arr[0..4] != b"OggS" || arr[4] != 0 {
+            return Err(tag_error("Ogg page not found"));
+        }
+
+        let mut segments = vec![0; arr[26] as usize];
+        input.read_exact(&mut segments)?;
+        let mut data = vec![0; segments.iter().map(|&x| x as usize).sum()];
+        input.read_exact(&mut data)?;
+
+        let page = Page {
+            flags: arr[5],
+            granule: decode_int_le_u64(&arr[6..14]),
+            serial: decode_int_le_u32(&arr[14..18]),
+            sequence: decode_int_le_u32(&arr[18..22]),
+            segments,
+            data,
+        };
+        if page.render()[22..26] != arr[22..26] {
+            return Err(tag_error("Ogg page checksum mismatch"));
+        }
+        Ok(page)
+    }
+
+    pub fn render(&self) -> Vec<u8> {
+        let mut vec = b"OggS\x00".to_vec();
+        vec.push(self.flags);
+        vec.extend_from_slice(&encode_int_le_u64(self.granule));
+        vec.extend_from_slice(&encode_int_le_u32(self.serial));
+        vec.extend_from_slice(&encode_int_le_u32(self.sequence));
+        // checksum goes here
+        vec.extend_from_slice(&[0; 4]);
+        vec.push(self.segments.len() as u8);
+        vec.extend_from_slice(&self.segments);
+        vec.extend_from_slice(&self.data);
+
+        let checksum = encode_int_le_u32(crc(&vec));
+        vec[22..26].copy_from_slice(&checksum);
+        vec
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Codec {
+    Vorbis,
+    Flac,
+    Speex,
+}
+
+struct Stream {
+    codec: Codec,
+    serial: u32,
+    // the header packets, comments among them
+    packets: Vec<Vec<u8>>,
+    // where the first page after the headers starts
+    end: u64,
+    // how many pages the headers took
+    pages: u32,
+}
+
+// how many header packets a stream has, once we know enough of them
+fn header_count(codec: Codec, packets: &[Vec<u8>]) -> Option<usize> {
+    match codec {
+        Codec::Vorbis => Some(3),
+        // the extra headers field of the first packet
+        Codec::Speex => Some(2 + decode_int_le_u32(&packets[0][68..72]) as usize),
+        // a block count in the first packet, or 0 and the last block flag
+        Codec::Flac => match (packets[0][7] as usize) << 8 | packets[0][8] as usize {
+            0 if packets.len() > 1 && packets[packets.len() - 1][0] & 0x80 != 0 => {
+                Some(packets.len())
+            }
+            0 => None,
+            x => Some(1 + x),
+        },
+    }
+}
+
+fn read_stream<T: Read + Seek>(input: &mut T) -> Result<Stream, Error> {
+    input.seek(SeekFrom::Start(0))?;
+    let first = Page::read(input)?;
+    if first.flags & FIRST_PAGE == 0 || first.segments.len() != 1 || first.segments[0] == 255 {
+        return Err(tag_error("Ogg stream does not start with a header packet"));
+    }
+
+    let packet = &first.data;
+    let codec = if packet.starts_with(b"\x01vorbis") && packet.len() >= 30 {
+        Codec::Vorbis
+    } else if packet.starts_with(b"\x7FFLAC") && packet.len() >= 51 {
+        Codec::Flac
+    } else if packet.starts_with(b"Speex   ") && packet.len() >= 80 {
+        Codec::Speex
+    } else {
+        return Err(tag_error("Unsupported Ogg codec"));
+    };
+
+    let mut stream = Stream {
+        codec,
+        serial: first.serial,
+        packets: vec![first.data],
+        end: 0,
+        pages: 1,
+    };
+
+    let mut packet = Vec::new();
+    while !matches!(header_count(codec, &stream.packets), Some(x) if stream.packets.len() >= x) {
+        let page = Page::read(input)?;
+        if page.serial != stream.serial {
+            return Err(tag_error("Multiplexed Ogg streams are not supported"));
+        }
+        stream.pages += 1;
+
+        let mut pos = 0;
+        for &x in page.segments.iter() {
+            if header_count(codec, &stream.packets).is_some_and(|x| stream.packets.len() >= x) {
+                return Err(tag_error("Ogg audio data shares a page with the headers"));
+            }
+            packet.extend_from_slice(&page.data[pos..pos + x as usize]);
+            pos += x as usize;
+            if x < 255 {
+                stream.packets.push(std::mem::take(&mut packet));
+            }
+        }
+    }
+    if !packet.is_empty() {
+        return Err(tag_error("Ogg audio data shares a page with the headers"));
+    }
+
+    stream.end = input.stream_position()?;
+    Ok(stream)
+}
+
+pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    let stream = read_stream(input)?;
+
+    match stream.codec {
+        Codec::Vorbis => match stream.packets[1].strip_prefix(b"\x03vorbis") {
+            Some(x) => vorbis::get_tags(&mut Cursor::new(x)),
+            None => Err(tag_error("Vorbis comment header not found")),
+        },
+        Codec::Speex => vorbis::get_tags(&mut Cursor::new(&stream.packets[1])),
+        // one metadata block in each packet after the first
+        Codec::Flac => {
+            let mut tags = Tags::none();
+            let mut image: Option<Image> = None;
+            let mut found_best_cover = false;
+
+            for packet in stream.packets[1..].iter().filter(|p| p.len() >= 4) {
+                let mut block = Cursor::new(&packet[4..]);
+                match packet[0] & 0x7F {
+                    4 => tags = vorbis::get_tags(&mut block)?,
+                    6 => {
+                        let length = packet.len() as u32 - 4;
+                        let (img, apic_type) = flac::read_image(&mut block, length)?;
+                        if apic_type == 0x03 || !found_best_cover {
+                            image = Some(img);
+                            found_best_cover = apic_type == 0x03;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            if let Some(x) = image {
+                tags.front_cover = TagOption::Some(x);
+            }
+            Ok(tags)
+        }
+    }
+}
+
+// a FLAC metadata block, without the last block flag
+fn new_block(kind: u8, data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() > 0x00FF_FFFF {
+        return Err(tag_error(
+            "Could not write FLAC metadata block as it is larger than 16,777,215 bytes",
+        ));
+    }
+    let mut vec = encode_int_be_u32(data.len() as u32).to_vec();
+    vec[0] = kind;
+    vec.extend_from_slice(data);
+    Ok(vec)
+}
+
+pub fn set<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    new: &Tags,
+) -> Result<(), Error> {
+    let stream = read_stream(input)?;
+    let tags = delta(&get(input)?, new);
+
+    let mut packets = stream.packets.clone();
+    match stream.codec {
+        Codec::Vorbis => {
+            let mut vec = b"\x03vorbis".to_vec();
+            vec.append(&mut vorbis::from_tags(&tags, true));
+            // framing bit
+            vec.push(1);
+            packets[1] = vec;
+        }
+        Codec::Speex => packets[1] = vorbis::from_tags(&tags, true),
+        Codec::Flac => {
+            // the comment has to come right after the first packet,
+            // pictures go last, and everything else stays in between
+            packets.retain(|p| !matches!(p.first().map(|x| x & 0x7F), Some(4) | Some(6)));
+            packets.insert(1, new_block(4, &vorbis::from_tags(&tags, false))?);
+            if let TagOption::Some(ref x) = tags.front_cover {
+                if x.is_some() {
+                    packets.push(new_block(6, &flac::get_picture_block(x)?)?);
+                }
+            }
+            // whatever was last before may not be any more
+            for packet in packets[1..].iter_mut() {
+                packet[0] &= 0x7F;
+            }
+            let last = packets.len() - 1;
+            packets[last][0] |= 0x80;
+
+            let count = packets.len() as u16 - 1;
+            packets[0][7] = (count >> 8) as u8;
+            packets[0][8] = count as u8;
+        }
+    }
+
+    rewrite(input, output, &stream, &packets)
+}
+
+// Vorbis comments have to be there, so removing the tags leaves an empty one
+pub fn remove<R: Read + Seek, W: Write>(input: &mut R, output: &mut W) -> Result<(), Error> {
+    set(input, output, &Tags::none())
+}
+
+// the header packets in pages of their own: the first packet alone,
+// then the rest as tightly as they fit
+fn paginate(packets: &[Vec<u8>], serial: u32) -> Vec<Page> {
+    let mut pages = vec![Page {
+        flags: FIRST_PAGE,
+        granule: 0,
+        serial,
+        sequence: 0,
+        segments: Vec::new(),
+        data: packets[0].clone(),
+    }];
+
+    let mut page = Page {
+        flags: 0,
+        granule: NO_GRANULE,
+        serial,
+        sequence: 1,
+        segments: Vec::new(),
+        data: Vec::new(),
+    };
+    for (i, packet) in packets.iter().enumerate() {
+        // a packet of a multiple of 255 bytes ends on a 0
+        let mut lacing = vec![255; packet.len() / 255];
+        lacing.push((packet.len() % 255) as u8);
+
+        let mut pos = 0;
+        for x in lacing {
+            if i == 0 {
+                pages[0].segments.push(x);
+                continue;
+            }
+            if page.segments.len() == 255 {
+                let continued = page.segments[254] == 255;
+                let sequence = page.sequence + 1;
+                pages.push(std::mem::replace(
+                    &mut page,
+                    Page {
+                        flags: if continued { CONTINUED } else { 0 },
+                        granule: NO_GRANULE,
+                        serial,
+                        sequence,
+                        segments: Vec::new(),
+                        data: Vec::new(),
+                    },
+                ));
+            }
+            page.segments.push(x);
+            page.data.extend_from_slice(&packet[pos..pos + x as usize]);
+            pos += x as usize;
+            if x < 255 {
+                page.granule = 0;
+            }
+        }
+    }
+    if !page.segments.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+// write new header pages, then copy the rest of the stream
+// with its page numbers moved along if the header got longer or shorter
+fn rewrite<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    stream: &Stream,
+    packets: &[Vec<u8>],
+) -> Result<(), Error> {
+    let pages = paginate(packets, stream.serial);
+    for page in pages.iter() {
+        output.write_all(&page.render())?;
+    }
+
+    let length = input.seek(SeekFrom::End(0))?;
+    input.seek(SeekFrom::Start(stream.end))?;
+
+    if pages.len() as u32 == stream.pages {
+        std::io::copy(input, output)?;
+    } else {
+        let mut pos = stream.end;
+        while pos < length {
+            let mut page = Page::read(input)?;
+            pos = input.stream_position()?;
+            if page.serial == stream.serial {
+                page.sequence = page.sequence + pages.len() as u32 - stream.pages;
+            }
+            output.write_all(&page.render())?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
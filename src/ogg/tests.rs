@@ -0,0 +1,198 @@
+use crate::Image;
+use crate::TagOption;
+use crate::Tags;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::Cursor;
+
+fn read_file(path: &str) -> Vec<u8> {
+    let mut vec = Vec::new();
+    File::open(path).unwrap().read_to_end(&mut vec).unwrap();
+    vec
+}
+
+// all pages of a file
+fn pages(vec: &[u8]) -> Vec<super::Page> {
+    let mut cursor = Cursor::new(vec);
+    let mut pages = Vec::new();
+    while (cursor.position() as usize) < vec.len() {
+        pages.push(super::Page::read(&mut cursor).unwrap());
+    }
+    pages
+}
+
+// an Ogg stream from header packets and a page of "audio"
+fn stream(packets: &[Vec<u8>]) -> Vec<u8> {
+    let mut vec = Vec::new();
+    let pages = super::paginate(packets, 0x1234);
+    let audio = super::Page {
+        flags: 0x04,
+        granule: 4096,
+        serial: 0x1234,
+        sequence: pages.len() as u32,
+        segments: vec![100],
+        data: vec![0xAA; 100],
+    };
+    for page in pages.iter().chain(std::iter::once(&audio)) {
+        vec.append(&mut page.render());
+    }
+    vec
+}
+
+#[test]
+fn ogg_page_test() {
+    // the checksums are verified while reading, and rendering gives back the same bytes
+    let file = read_file("testfiles/ogg.ogg");
+    let pages = pages(&file);
+    let rendered: Vec<u8> = pages.iter().flat_map(|p| p.render()).collect();
+    assert_eq!(rendered, file);
+
+    let mut broken = file.clone();
+    broken[40] ^= 1;
+    assert!(super::Page::read(&mut Cursor::new(&broken)).is_err());
+}
+
+#[test]
+fn ogg_vorbis_test() {
+    let file = read_file("testfiles/ogg.ogg");
+    let tags = super::get(&mut Cursor::new(&file)).unwrap();
+    assert_eq!(tags.album, TagOption::Some("test drips".to_string()));
+    assert_eq!(tags.genre, TagOption::Some("recording".to_string()));
+
+    let new = Tags {
+        title: TagOption::Some("Title ÆØÅ".to_string()),
+        front_cover: TagOption::Some(Image::PNG(read_file("testfiles/id3v24-utf8-png-cover.png"))),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&file), &mut output, &new).unwrap();
+
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("Title ÆØÅ".to_string()));
+    assert_eq!(tags.album, TagOption::Some("test drips".to_string()));
+    assert_eq!(tags.front_cover, new.front_cover);
+
+    // the cover spans pages, so everything after the headers is renumbered
+    let old = super::read_stream(&mut Cursor::new(&file)).unwrap();
+    let new = super::read_stream(&mut Cursor::new(&output)).unwrap();
+    assert!(new.pages > old.pages);
+    let old_audio = pages(&file[old.end as usize..]);
+    let new_audio = pages(&output[new.end as usize..]);
+    assert_eq!(old_audio.len(), new_audio.len());
+    for (a, b) in old_audio.iter().zip(new_audio.iter()) {
+        assert_eq!(a.data, b.data);
+        assert_eq!(a.granule, b.granule);
+        assert_eq!(a.sequence + new.pages - old.pages, b.sequence);
+    }
+
+    let mut removed = Vec::new();
+    super::remove(&mut Cursor::new(&output), &mut removed).unwrap();
+    assert_eq!(
+        super::get(&mut Cursor::new(&removed)).unwrap(),
+        Tags::none()
+    );
+    assert_eq!(
+        super::read_stream(&mut Cursor::new(&removed))
+            .unwrap()
+            .packets[2],
+        old.packets[2]
+    );
+}
+
+#[test]
+fn ogg_flac_test() {
+    // identification with STREAMINFO, a comment, an APPLICATION block and a picture
+    let mut first = b"\x7FFLAC\x01\x00\x00\x03fLaC\x00\x00\x00\x22".to_vec();
+    first.resize(51, 0x11);
+    let old = Tags {
+        title: TagOption::Some("Old".to_string()),
+        ..Tags::none()
+    };
+    let comment = super::new_block(4, &crate::vorbis::from_tags(&old, false)).unwrap();
+    let application = b"\x02\x00\x00\x04abcd".to_vec();
+    let cover = Image::JPEG(read_file("testfiles/flac-cover.jpg"));
+    let mut picture =
+        super::new_block(6, &crate::flac::get_picture_block(&cover).unwrap()).unwrap();
+    picture[0] |= 0x80;
+    let file = stream(&[first, comment, application.clone(), picture]);
+
+    let tags = super::get(&mut Cursor::new(&file)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("Old".to_string()));
+    assert_eq!(tags.front_cover, TagOption::Some(cover));
+
+    let new = Tags {
+        artist: TagOption::Some("Artist".to_string()),
+        front_cover: TagOption::None,
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&file), &mut output, &new).unwrap();
+
+    let stream = super::read_stream(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(stream.packets.len(), 3);
+    assert_eq!(&stream.packets[0][7..9], &[0, 2]);
+    assert_eq!(stream.packets[1][0], 4);
+    assert_eq!(stream.packets[2], [&[0x82][..], &application[1..]].concat());
+
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("Old".to_string()),
+        artist: TagOption::Some("Artist".to_string()),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+    assert_eq!(pages(&output).last().unwrap().data, vec![0xAA; 100]);
+}
+
+#[test]
+fn ogg_flac_cover_test() {
+    // what flac --ogg writes: STREAMINFO, a comment and padding flagged last
+    let mut first = b"\x7FFLAC\x01\x00\x00\x02fLaC\x00\x00\x00\x22".to_vec();
+    first.resize(51, 0x11);
+    let comment = super::new_block(4, &crate::vorbis::from_tags(&Tags::none(), false)).unwrap();
+    let padding = b"\x81\x00\x00\x04\x00\x00\x00\x00".to_vec();
+    let file = stream(&[first, comment, padding.clone()]);
+
+    let cover = Image::JPEG(read_file("testfiles/flac-cover.jpg"));
+    let new = Tags {
+        front_cover: TagOption::Some(cover.clone()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&file), &mut output, &new).unwrap();
+
+    // only the picture, now at the end, is flagged last
+    let stream = super::read_stream(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(stream.packets.len(), 4);
+    assert_eq!(&stream.packets[0][7..9], &[0, 3]);
+    assert_eq!(stream.packets[1][0], 4);
+    assert_eq!(stream.packets[2], [&[0x01][..], &padding[1..]].concat());
+    assert_eq!(stream.packets[3][0], 0x86);
+
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.front_cover, TagOption::Some(cover));
+}
+
+#[test]
+fn ogg_speex_test() {
+    let mut first = b"Speex   1.2".to_vec();
+    first.resize(80, 0);
+    let file = stream(&[first, crate::vorbis::from_tags(&Tags::none(), false)]);
+    assert_eq!(super::get(&mut Cursor::new(&file)).unwrap(), Tags::none());
+
+    let new = Tags {
+        album: TagOption::Some("Album".to_string()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(&file), &mut output, &new).unwrap();
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.album, TagOption::Some("Album".to_string()));
+    assert_eq!(pages(&output).len(), 3);
+
+    // anything else in Ogg is left alone
+    let mut first = b"OpusHead".to_vec();
+    first.resize(19, 0);
+    let file = stream(&[first, b"OpusTags".to_vec()]);
+    assert!(super::get(&mut Cursor::new(&file)).is_err());
+}
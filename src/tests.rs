@@ -130,3 +130,358 @@ fn id3v2_test() {
 fn invalid_file_test() {
     super::get_tags("testfiles/asdfasdf.mp3").unwrap();
 }
+
+#[test]
+fn write_options_test() {
+    use std::time::{Duration, SystemTime};
+
+    let src_path = "testfiles/flac.flac";
+    let path = "testfiles/test-write-options.flac";
+
+    fs::copy(src_path, path).unwrap();
+
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o640)).unwrap();
+    }
+
+    let options = crate::WriteOptions {
+        preserve_modified_time: true,
+        ..Default::default()
+    };
+    super::set_tags_with_options(path, &Tags::mixed(), &options).unwrap();
+
+    let metadata = fs::metadata(path).unwrap();
+    let leftovers = fs::read_dir("testfiles")
+        .unwrap()
+        .filter(|e| {
+            e.as_ref()
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".test-write-options.flac")
+        })
+        .count();
+
+    fs::remove_file(path).unwrap();
+
+    assert_eq!(metadata.modified().unwrap(), modified);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+    }
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+fn remove_tags_test() {
+    use crate::TagTypes;
+
+    let src_path = "testfiles/id3v24-utf8-png.mp3";
+    let path = "testfiles/test-remove-tags.mp3";
+
+    fs::copy(src_path, path).unwrap();
+
+    // append a bare APE tag and an ID3v1 tag
+    let id3v1_tags = Tags {
+        title: TagOption::Some("ID3v1 Title".to_string()),
+        ..Default::default()
+    };
+    {
+        let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+        let mut ape = b"APETAGEX\xD0\x07\x00\x00\x20\x00\x00\x00".to_vec();
+        ape.resize(32, 0);
+        file.write_all(&ape).unwrap();
+        file.write_all(&crate::id3v1::render(&id3v1_tags)).unwrap();
+    }
+
+    let which = TagTypes {
+        id3v2: true,
+        ape: true,
+        ..TagTypes::none()
+    };
+    super::remove_tags(path, &which).unwrap();
+
+    let size = fs::metadata(path).unwrap().len();
+    let tags = super::get_tags(path).unwrap();
+
+    super::remove_tags(path, &TagTypes::all()).unwrap();
+    let stripped_size = fs::metadata(path).unwrap().len();
+
+    fs::remove_file(path).unwrap();
+
+    // only the ID3v1 tag is left
+    assert_eq!(tags.title, TagOption::Some("ID3v1 Title".to_string()));
+    assert_eq!(stripped_size, size - 128);
+}
+
+#[test]
+fn copy_with_tags_test() {
+    let src_path = "testfiles/flac.flac";
+    let path = "testfiles/test-copy-with-tags.flac";
+
+    let original = super::get_tags(src_path).unwrap();
+    let tags = Tags {
+        title: TagOption::Some("copied".to_string()),
+        ..Tags::mixed()
+    };
+
+    super::copy_with_tags(src_path, path, &tags).unwrap();
+    let copied = super::get_tags(path).unwrap();
+    let same_file = super::copy_with_tags(path, path, &tags);
+
+    // a failed copy leaves an existing destination alone
+    let broken_path = "testfiles/test-copy-with-tags-broken.flac";
+    fs::write(broken_path, b"not a FLAC file").unwrap();
+    fs::write(path, b"existing").unwrap();
+    let broken = super::copy_with_tags(broken_path, path, &tags);
+    let existing = fs::read(path).unwrap();
+
+    fs::remove_file(broken_path).unwrap();
+    fs::remove_file(path).unwrap();
+
+    assert!(broken.is_err());
+    assert_eq!(existing, b"existing");
+    assert!(same_file.is_err());
+    assert_eq!(super::get_tags(src_path).unwrap(), original);
+    assert_eq!(
+        copied,
+        Tags {
+            title: TagOption::Some("copied".to_string()),
+            ..original
+        }
+    );
+}
+
+#[test]
+fn m4b_test() {
+    let src_path = "testfiles/m4a-aac.m4a";
+    let path = "testfiles/test-audiobook.M4B";
+
+    fs::copy(src_path, path).unwrap();
+
+    let tags = Tags {
+        media_kind: TagOption::Some(2),
+        ..Tags::mixed()
+    };
+    super::set_tags(path, &tags).unwrap();
+    let new_tags = super::get_tags(path).unwrap();
+
+    fs::remove_file(path).unwrap();
+    assert_eq!(new_tags.media_kind, TagOption::Some(2));
+    assert_eq!(new_tags.album, TagOption::Some("Outside".to_string()));
+}
+
+#[test]
+fn mov_test() {
+    let src_path = "testfiles/m4a-aac.m4a";
+    let path = "testfiles/test-movie.mov";
+
+    // a QuickTime movie only claims the qt brand
+    let mut vec = fs::read(src_path).unwrap();
+    let size = crate::tools::decode_int_be_u32(&vec[0..4]) as usize;
+    for i in (8..size).step_by(4).filter(|&i| i != 12) {
+        vec[i..i + 4].copy_from_slice(b"qt  ");
+    }
+    fs::write(path, &vec).unwrap();
+
+    let tags = Tags {
+        title: TagOption::Some("Holiday".to_string()),
+        ..Tags::mixed()
+    };
+    super::set_tags(path, &tags).unwrap();
+    let new_tags = super::get_tags(path).unwrap();
+
+    fs::remove_file(path).unwrap();
+    assert_eq!(new_tags.title, TagOption::Some("Holiday".to_string()));
+    assert_eq!(new_tags.album, TagOption::Some("Outside".to_string()));
+}
+
+#[test]
+fn wavpack_test() {
+    use crate::TagTypes;
+
+    let path = "testfiles/test-wavpack.wv";
+
+    // a single block of 16 bit stereo at 44100 Hz, no tags yet
+    let mut block = b"wvpk\xF8\x03\x00\x00\x10\x04\x00\x00".to_vec();
+    block.extend_from_slice(&crate::tools::encode_int_le_u32(44100));
+    block.extend_from_slice(&[0; 8]);
+    block.extend_from_slice(&crate::tools::encode_int_le_u32(0x1 | 0x1800 | 9 << 23));
+    block.resize(1024, 0);
+    fs::write(path, &block).unwrap();
+
+    let tags = Tags {
+        title: TagOption::Some("WavPack Title".to_string()),
+        track_number: TagOption::Some(2),
+        ..Tags::mixed()
+    };
+    super::set_tags(path, &tags).unwrap();
+    let new_tags = super::get_tags(path).unwrap();
+    let properties = super::get_properties(path).unwrap();
+
+    super::remove_tags(path, &TagTypes::all()).unwrap();
+    let stripped = fs::read(path).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert_eq!(new_tags.title, TagOption::Some("WavPack Title".to_string()));
+    assert_eq!(new_tags.track_number, TagOption::Some(2));
+    assert_eq!(properties.sample_rate, Some(44100));
+    assert_eq!(properties.duration, Some(std::time::Duration::from_secs(1)));
+    // the tag isn't counted as audio
+    assert_eq!(properties.bitrate, Some(8));
+    assert_eq!(stripped, block);
+}
+
+#[test]
+fn properties_overflow_test() {
+    use crate::Properties;
+
+    // a sample count from a crafted header, and a tiny stream claiming to be huge
+    let properties = Properties::from_samples(Some(u64::MAX), 8000, 2, None, 1000);
+    assert_eq!(properties.duration, None);
+    assert_eq!(properties.bitrate, Some(0));
+
+    let properties = Properties::from_samples(Some(1), 1_000_000, 1, None, u64::MAX);
+    assert_eq!(
+        properties.duration,
+        Some(std::time::Duration::from_micros(1))
+    );
+    assert_eq!(properties.bitrate, None);
+}
+
+#[test]
+fn ogg_test() {
+    let src_path = "testfiles/ogg.ogg";
+    let path = "testfiles/test-write-ogg.oga";
+
+    fs::copy(src_path, path).unwrap();
+
+    let tags = Tags {
+        title: TagOption::Some("Ogg Title".to_string()),
+        ..Tags::mixed()
+    };
+    super::set_tags(path, &tags).unwrap();
+    let new_tags = super::get_tags(path).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert_eq!(new_tags.title, TagOption::Some("Ogg Title".to_string()));
+    assert_eq!(new_tags.album, TagOption::Some("test drips".to_string()));
+}
+
+#[test]
+fn flac_metadata_test() {
+    use crate::FlacBlock;
+
+    let src_path = "testfiles/flac.flac";
+    let path = "testfiles/test-flac-metadata.flac";
+
+    fs::copy(src_path, path).unwrap();
+
+    let mut metadata = super::get_flac_metadata(path).unwrap();
+    metadata
+        .blocks
+        .retain(|x| !matches!(x, FlacBlock::Padding(_)));
+    metadata.blocks.push(FlacBlock::Application {
+        id: *b"test",
+        data: Vec::new(),
+    });
+    super::set_flac_metadata(path, &metadata).unwrap();
+    let new_metadata = super::get_flac_metadata(path).unwrap();
+    let tags = super::get_tags(path).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert_eq!(new_metadata, metadata);
+    assert_eq!(tags.title, TagOption::Some("drippy".to_string()));
+    assert!(super::get_flac_metadata("testfiles/ogg.ogg").is_err());
+}
+
+#[test]
+fn cue_sheet_test() {
+    let src_path = "testfiles/flac.flac";
+    let path = "testfiles/test-cue-sheet.flac";
+    let cue_path = "testfiles/test-cue-sheet.cue";
+
+    fs::copy(src_path, path).unwrap();
+    fs::write(
+        cue_path,
+        "FILE \"test-cue-sheet.flac\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"Drip\"\n    INDEX 01 00:00:00\n",
+    )
+    .unwrap();
+
+    let sheet = super::get_cue_sheet(cue_path).unwrap();
+    let no_sheet = super::get_cue_sheet(path).is_err();
+    super::set_cue_sheet(path, &sheet).unwrap();
+    let embedded = super::get_cue_sheet(path).unwrap();
+    let tags = super::get_tags(path).unwrap();
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(cue_path).unwrap();
+
+    assert!(no_sheet);
+    assert_eq!(sheet.tracks[0].title, Some("Drip".to_string()));
+    // the CUESHEET block has no room for titles
+    assert_eq!(embedded.tracks[0].indices, sheet.tracks[0].indices);
+    assert_eq!(embedded.tracks[0].title, None);
+    assert_eq!(tags.title, TagOption::Some("drippy".to_string()));
+}
+
+#[test]
+fn lyrics3_test() {
+    use crate::TagTypes;
+
+    let src_path = "testfiles/id3v24-utf8-png.mp3";
+    let path = "testfiles/test-lyrics3.mp3";
+
+    fs::copy(src_path, path).unwrap();
+
+    // append a Lyrics3v2 tag and an ID3v1 tag
+    let lyrics3 = b"LYRICSBEGININD0000210LYR00011Some lyrics000040LYRICS200";
+    {
+        let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(lyrics3).unwrap();
+        file.write_all(&crate::id3v1::render(&Tags::none()))
+            .unwrap();
+    }
+
+    let tags = Tags {
+        title: TagOption::Some("New Title".to_string()),
+        ..Tags::mixed()
+    };
+    super::set_tags(path, &tags).unwrap();
+    let new_tags = super::get_tags(path).unwrap();
+    let written = fs::read(path).unwrap();
+
+    let which = TagTypes {
+        lyrics3: true,
+        ..TagTypes::none()
+    };
+    super::remove_tags(path, &which).unwrap();
+    let stripped = fs::read(path).unwrap();
+    let stripped_tags = super::get_tags(path).unwrap();
+
+    fs::remove_file(path).unwrap();
+
+    assert_eq!(new_tags.title, TagOption::Some("New Title".to_string()));
+    assert_eq!(new_tags.lyrics, TagOption::Some("Some lyrics".to_string()));
+    // the Lyrics3 tag stays right in front of ID3v1, which got the new title
+    let end = written.len() - 128;
+    assert_eq!(&written[end - lyrics3.len()..end], &lyrics3[..]);
+    assert_eq!(&written[end + 3..end + 12], b"New Title");
+    // and goes on its own
+    assert_eq!(stripped.len(), written.len() - lyrics3.len());
+    assert_eq!(&stripped[stripped.len() - 128..], &written[end..]);
+    assert_eq!(stripped_tags.lyrics, TagOption::None);
+}
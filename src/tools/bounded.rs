@@ -0,0 +1,55 @@
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+// a window of `length` bytes at `start` in another reader, so that code
+// which expects a whole file (like id3v2) can be pointed at a single chunk
+pub struct Bounded<'a, T> {
+    inner: &'a mut T,
+    start: u64,
+    length: u64,
+    pos: u64,
+}
+
+impl<'a, T: Read + Seek> Bounded<'a, T> {
+    pub fn new(inner: &'a mut T, start: u64, length: u64) -> Bounded<'a, T> {
+        Bounded {
+            inner,
+            start,
+            length,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a, T: Read + Seek> Read for Bounded<'a, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.length {
+            return Ok(0);
+        }
+        let max = std::cmp::min(buf.len() as u64, self.length - self.pos) as usize;
+
+        self.inner.seek(SeekFrom::Start(self.start + self.pos))?;
+        let read = self.inner.read(&mut buf[..max])?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<'a, T: Read + Seek> Seek for Bounded<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(x) => x as i64,
+            SeekFrom::Current(x) => self.pos as i64 + x,
+            SeekFrom::End(x) => self.length as i64 + x,
+        };
+        if pos < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            ));
+        }
+        self.pos = pos as u64;
+        Ok(self.pos)
+    }
+}
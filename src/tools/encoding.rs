@@ -38,3 +38,17 @@ pub fn decode_utf16(input: &[u8]) -> String {
     .trim_end_matches('\0')
     .to_string()
 }
+
+// little endian without a BOM, as ASF has it
+pub fn decode_utf16le(input: &[u8]) -> String {
+    use self::encoding::all::UTF_16LE;
+    UTF_16LE
+        .decode(input, DecoderTrap::Replace)
+        .unwrap_or("".to_string())
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+pub fn encode_utf16le(input: &str) -> Vec<u8> {
+    input.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+}
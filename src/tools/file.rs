@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::WriteOptions;
+
+// create a new, empty temporary file next to `path`
+// so that the final rename stays on the same file system
+pub fn create_temp(path: &Path) -> io::Result<(PathBuf, File)> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    loop {
+        let tmp_path = path.with_file_name(format!(
+            ".{}.{}-{}.tmp",
+            name,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        // create_new refuses to reuse leftovers from an earlier crash
+        match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&tmp_path)
+        {
+            Ok(file) => return Ok((tmp_path, file)),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// flush the finished temporary file to disk and move it over `path`
+pub fn replace(
+    tmp_file: File,
+    tmp_path: &Path,
+    path: &Path,
+    options: &WriteOptions,
+) -> io::Result<()> {
+    let metadata = std::fs::metadata(path)?;
+
+    copy_metadata(&metadata, &tmp_file)?;
+    copy_xattrs(path, tmp_path);
+    if options.preserve_modified_time {
+        tmp_file.set_modified(metadata.modified()?)?;
+    }
+    persist(tmp_file, tmp_path, path)
+}
+
+// flush the finished temporary file to disk and move it to `path`,
+// which may or may not exist yet
+pub fn persist(tmp_file: File, tmp_path: &Path, path: &Path) -> io::Result<()> {
+    tmp_file.sync_all()?;
+    drop(tmp_file);
+
+    std::fs::rename(tmp_path, path)?;
+    sync_dir(path)
+}
+
+fn copy_metadata(metadata: &Metadata, tmp_file: &File) -> io::Result<()> {
+    tmp_file.set_permissions(metadata.permissions())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // changing ownership requires privileges we usually don't have,
+        // in which case the file simply ends up owned by us
+        std::os::unix::fs::fchown(tmp_file, Some(metadata.uid()), Some(metadata.gid())).ok();
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn copy_xattrs(path: &Path, tmp_path: &Path) {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    extern crate libc;
+
+    let (src, dst) = match (
+        CString::new(path.as_os_str().as_bytes()),
+        CString::new(tmp_path.as_os_str().as_bytes()),
+    ) {
+        (Ok(s), Ok(d)) => (s, d),
+        _ => return,
+    };
+
+    // extended attributes are best effort - plenty of file systems lack them
+    unsafe {
+        let size = libc::listxattr(src.as_ptr(), std::ptr::null_mut(), 0);
+        if size <= 0 {
+            return;
+        }
+        let mut names = vec![0u8; size as usize];
+        let size = libc::listxattr(src.as_ptr(), names.as_mut_ptr() as *mut _, names.len());
+        if size <= 0 {
+            return;
+        }
+        names.truncate(size as usize);
+
+        for name in names.split(|&c| c == 0).filter(|n| !n.is_empty()) {
+            let name = match CString::new(name) {
+                Ok(n) => n,
+                Err(_) => continue,
+            };
+            let size = libc::getxattr(src.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0);
+            if size < 0 {
+                continue;
+            }
+            let mut value = vec![0u8; size as usize];
+            let size = libc::getxattr(
+                src.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr() as *mut _,
+                value.len(),
+            );
+            if size < 0 {
+                continue;
+            }
+            libc::setxattr(
+                dst.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr() as *const _,
+                size as usize,
+                0,
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn copy_xattrs(_path: &Path, _tmp_path: &Path) {}
+
+// make the rename itself durable
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(p) if p.as_os_str().is_empty() => Path::new("."),
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
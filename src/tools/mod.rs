@@ -1,4 +1,6 @@
+pub mod bounded;
 pub mod encoding;
+pub mod file;
 pub mod tags;
 
 use crate::Error;
@@ -39,6 +41,27 @@ pub fn decode_int_le_u32(input: &[u8]) -> u32 {
     }
     result
 }
+pub fn decode_int_be_u64(input: &[u8]) -> u64 {
+    if input.len() > 8 {
+        panic!(
+            "decode_int_be_u64 expected a slice with max length 8, got slice with length {}",
+            input.len()
+        );
+    }
+    input.iter().fold(0, |result, b| (result << 8) | *b as u64)
+}
+pub fn decode_int_le_u64(input: &[u8]) -> u64 {
+    if input.len() > 8 {
+        panic!(
+            "decode_int_le_u64 expected a slice with max length 8, got slice with length {}",
+            input.len()
+        );
+    }
+    input
+        .iter()
+        .rev()
+        .fold(0, |result, b| (result << 8) | *b as u64)
+}
 pub fn encode_int_be_u16(input: u16) -> Vec<u8> {
     let mut result = vec![0; 2];
     result[0] = (input >> 8) as u8;
@@ -52,6 +75,12 @@ pub fn encode_int_be_u32(input: u32) -> Vec<u8> {
     }
     result
 }
+pub fn encode_int_be_u64(input: u64) -> Vec<u8> {
+    (0..8).map(|i| (input >> (8 * (7 - i))) as u8).collect()
+}
+pub fn encode_int_le_u64(input: u64) -> Vec<u8> {
+    (0..8).map(|i| (input >> (8 * i)) as u8).collect()
+}
 pub fn encode_int_le_u32(input: u32) -> Vec<u8> {
     let mut result = vec![0; 4];
     for i in 0..4 {
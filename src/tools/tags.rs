@@ -36,12 +36,96 @@ pub fn delta(old: &Tags, new: &Tags) -> Tags {
         bpm: delta!(bpm, new, old),
         is_compilation: delta!(is_compilation, new, old),
         comment: delta!(comment, new, old),
+        copyright: delta!(copyright, new, old),
+        lyrics: delta!(lyrics, new, old),
         sort_title: delta!(sort_title, new, old),
         sort_album: delta!(sort_album, new, old),
         sort_artist: delta!(sort_artist, new, old),
         sort_album_artist: delta!(sort_album_artist, new, old),
         sort_composer: delta!(sort_composer, new, old),
         front_cover: delta!(front_cover, new, old),
+        media_kind: delta!(media_kind, new, old),
+        tv_show: delta!(tv_show, new, old),
+        tv_season: delta!(tv_season, new, old),
+        tv_episode: delta!(tv_episode, new, old),
+        description: delta!(description, new, old),
+        long_description: delta!(long_description, new, old),
+        is_hd_video: delta!(is_hd_video, new, old),
+        custom: delta!(custom, new, old),
         ..Tags::none()
     }
 }
+
+// only fill in a field that's still None, so what was found first wins
+pub fn fill<T>(field: &mut TagOption<T>, value: TagOption<T>) {
+    if field.is_none() {
+        *field = value;
+    }
+}
+
+// a number, of which only the part before a slash counts
+pub fn number(value: &str) -> TagOption<i64> {
+    let value = value.split('/').next().unwrap_or("").trim();
+    value.parse::<i64>().ok().into()
+}
+
+// "n" or "n/m", as used for track and disc numbers
+pub fn pair(value: &str, number: &mut TagOption<i64>, total: &mut TagOption<i64>) {
+    let mut split = value.splitn(2, '/');
+    fill(number, self::number(split.next().unwrap_or("")));
+    if let Some(x) = split.next() {
+        fill(total, self::number(x));
+    }
+}
+
+// fill the fields of tags that are None from another set of tags
+macro_rules! merge {
+    ($tags:ident, $other:ident, $($field:ident),*) => {{
+        $(
+            if $tags.$field.is_none() {
+                $tags.$field = $other.$field;
+            }
+        )*
+    }};
+}
+
+// combine tags from two places in a file, the first one taking precedence
+pub fn merge(mut tags: Tags, other: Tags) -> Tags {
+    merge!(
+        tags,
+        other,
+        title,
+        album,
+        artist,
+        album_artist,
+        composer,
+        grouping,
+        genre,
+        date,
+        track_number,
+        track_total,
+        disc_number,
+        disc_total,
+        bpm,
+        is_compilation,
+        comment,
+        copyright,
+        lyrics,
+        sort_title,
+        sort_album,
+        sort_artist,
+        sort_album_artist,
+        sort_composer,
+        front_cover,
+        rating,
+        media_kind,
+        tv_show,
+        tv_season,
+        tv_episode,
+        description,
+        long_description,
+        is_hd_video,
+        custom
+    );
+    tags
+}
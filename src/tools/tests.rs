@@ -5,6 +5,30 @@ fn int_encode_test() {
     assert_eq!(&encode_int_le_u32(0x12345678), &[0x78, 0x56, 0x34, 0x12]);
 }
 
+#[test]
+fn int_u64_test() {
+    use crate::tools::{decode_int_be_u64, encode_int_be_u64};
+    assert_eq!(
+        &encode_int_be_u64(0x0123456789ABCDEF),
+        &[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF]
+    );
+    assert_eq!(
+        decode_int_be_u64(&[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF]),
+        0x0123456789ABCDEF
+    );
+    assert_eq!(decode_int_be_u64(&[0x01, 0x7f]), 0x017f);
+
+    use crate::tools::{decode_int_le_u64, encode_int_le_u64};
+    assert_eq!(
+        &encode_int_le_u64(0x0123456789ABCDEF),
+        &[0xEF, 0xCD, 0xAB, 0x89, 0x67, 0x45, 0x23, 0x01]
+    );
+    assert_eq!(
+        decode_int_le_u64(&[0xEF, 0xCD, 0xAB, 0x89, 0x67, 0x45, 0x23, 0x01]),
+        0x0123456789ABCDEF
+    );
+}
+
 #[test]
 fn int_be_test() {
     use crate::tools::decode_int_be_u32;
@@ -33,3 +57,52 @@ fn utf16_decode_test() {
     assert_eq!(decode_utf16(&utf16le), "Test");
     assert_eq!(decode_utf16(&longstr), "こんにちは。てすとですよ～　👀");
 }
+
+#[test]
+fn utf16le_test() {
+    use crate::tools::encoding::{decode_utf16le, encode_utf16le};
+    assert_eq!(encode_utf16le("Test"), b"T\0e\0s\0t\0");
+    assert_eq!(decode_utf16le(b"T\0e\0s\0t\0\0\0"), "Test");
+    assert_eq!(decode_utf16le(&encode_utf16le("てすと👀")), "てすと👀");
+}
+
+#[test]
+fn bounded_test() {
+    use crate::tools::bounded::Bounded;
+    use std::io::prelude::*;
+    use std::io::{Cursor, SeekFrom};
+
+    let mut cursor = Cursor::new((0..32).collect::<Vec<u8>>());
+    let mut bounded = Bounded::new(&mut cursor, 8, 8);
+
+    let mut vec = Vec::new();
+    bounded.read_to_end(&mut vec).unwrap();
+    assert_eq!(vec, (8..16).collect::<Vec<u8>>());
+
+    assert_eq!(bounded.seek(SeekFrom::End(-2)).unwrap(), 6);
+    let mut arr = [0; 4];
+    assert_eq!(bounded.read(&mut arr).unwrap(), 2);
+    assert_eq!(&arr[..2], &[14, 15]);
+    assert!(bounded.seek(SeekFrom::Current(-9)).is_err());
+}
+
+#[test]
+fn number_pair_test() {
+    use crate::tools::tags::{fill, number, pair};
+    use crate::TagOption;
+
+    assert_eq!(number(" 7 "), TagOption::Some(7));
+    assert_eq!(number("3/12"), TagOption::Some(3));
+    assert_eq!(number("x"), TagOption::None);
+
+    let mut n = TagOption::None;
+    let mut total = TagOption::None;
+    pair("3/12", &mut n, &mut total);
+    assert_eq!((&n, &total), (&TagOption::Some(3), &TagOption::Some(12)));
+
+    // what was found first stays
+    pair("4", &mut n, &mut total);
+    assert_eq!((&n, &total), (&TagOption::Some(3), &TagOption::Some(12)));
+    fill(&mut n, TagOption::Some(5));
+    assert_eq!(n, TagOption::Some(3));
+}
@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+// a cue sheet, from a .cue file or embedded in a FLAC file
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct CueSheet {
+    // media catalog number (UPC/EAN)
+    pub catalog: Option<String>,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    // the audio file the sheet refers to
+    pub file: Option<String>,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct CueTrack {
+    pub number: u8,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub isrc: Option<String>,
+    // AUDIO, as opposed to data tracks like MODE1/2352
+    pub is_audio: bool,
+    pub pre_emphasis: bool,
+    pub indices: Vec<CueIndex>,
+}
+
+// index 0 is the pregap, index 1 the start of the track
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct CueIndex {
+    pub number: u8,
+    // from the start of the file
+    pub time: Duration,
+}
@@ -9,6 +9,16 @@ use crate::tools::decode_int_be_u32;
 use crc::crc32::checksum_ieee;
 
 impl Image {
+    // tell JPEG and PNG apart by their magic bytes
+    pub fn from_bytes(data: &[u8]) -> Image {
+        if data.starts_with(&[0xFF, 0xD8]) {
+            Image::JPEG(data.to_vec())
+        } else if data.starts_with(b"\x89PNG") {
+            Image::PNG(data.to_vec())
+        } else {
+            Image::None
+        }
+    }
     pub fn mime(&self) -> String {
         match self {
             Image::PNG(_) => "image/png".to_string(),
@@ -7,3 +7,18 @@ pub use tags::Tags;
 
 mod datetime;
 pub use datetime::DateTime;
+
+mod properties;
+pub use properties::Properties;
+
+mod restrictions;
+pub use restrictions::Id3v2Restrictions;
+
+mod options;
+pub use options::TagTypes;
+pub use options::WriteOptions;
+
+mod cuesheet;
+pub use cuesheet::CueIndex;
+pub use cuesheet::CueSheet;
+pub use cuesheet::CueTrack;
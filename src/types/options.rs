@@ -0,0 +1,58 @@
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct WriteOptions {
+    // keep the modification time of the original file
+    // (permissions, ownership and extended attributes are always kept)
+    pub preserve_modified_time: bool,
+    // M4A: move the moov atom in front of the media data
+    // so that the file can be played while it is still downloading
+    pub faststart: bool,
+    // FLAC: drop an ID3v2 tag in front of the stream,
+    // instead of keeping it in sync with the Vorbis comment
+    pub strip_leading_id3v2: bool,
+    // ID3v2: add an extended header with a CRC-32 of the frames,
+    // for MP3 and other MPEG streams and the ID3v2 tag in front of FLAC
+    pub id3v2_crc: bool,
+}
+
+// which kinds of tags to strip from a file
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct TagTypes {
+    pub id3v2: bool,
+    pub id3v1: bool,
+    // Lyrics3 in front of ID3v1, which goes along with ID3v1 as well
+    pub lyrics3: bool,
+    pub ape: bool,
+    // FLAC VORBIS_COMMENT and PICTURE blocks, and the comment header
+    // of Ogg streams (which stays, but empty)
+    pub vorbis: bool,
+    // M4A moov.udta.meta and QuickTime moov.meta
+    pub m4a: bool,
+    // WAV LIST/INFO chunk (the id3 chunk counts as id3v2)
+    pub riff_info: bool,
+    // AIFF NAME, AUTH, ANNO and (c) chunks (the ID3 chunk counts as id3v2)
+    pub aiff_text: bool,
+    // Matroska Tags element and the cover art attachment
+    pub matroska: bool,
+    // ASF content description, extended content description and metadata library
+    pub asf: bool,
+}
+
+impl TagTypes {
+    pub fn none() -> TagTypes {
+        Default::default()
+    }
+    pub fn all() -> TagTypes {
+        TagTypes {
+            id3v2: true,
+            id3v1: true,
+            lyrics3: true,
+            ape: true,
+            vorbis: true,
+            m4a: true,
+            riff_info: true,
+            aiff_text: true,
+            matroska: true,
+            asf: true,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::convert::TryFrom;
+use std::time::Duration;
+
+// technical details of the audio stream, as far as the format tells us
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Properties {
+    pub duration: Option<Duration>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+    pub bits_per_sample: Option<u8>,
+    // average over the whole file, in kbit/s
+    pub bitrate: Option<u32>,
+}
+
+impl Properties {
+    // work out duration and bitrate from the number of samples (per channel)
+    // and the size of the audio data, a sample rate of 0 means unknown
+    pub fn from_samples(
+        samples: Option<u64>,
+        sample_rate: u32,
+        channels: u8,
+        bits_per_sample: Option<u8>,
+        audio_bytes: u64,
+    ) -> Properties {
+        let (duration, bitrate) = match (samples, sample_rate) {
+            (None, _) | (_, 0) => (None, None),
+            // a crafted header can make these large enough to overflow,
+            // so whatever doesn't fit is unknown
+            (Some(samples), rate) => {
+                let micros = samples as u128 * 1_000_000 / rate as u128;
+                let bitrate = match micros {
+                    0 => None,
+                    x => u32::try_from(audio_bytes as u128 * 8 * 1000 / x).ok(),
+                };
+                let duration = u64::try_from(micros).ok().map(Duration::from_micros);
+                (duration, bitrate)
+            }
+        };
+
+        Properties {
+            duration,
+            sample_rate: match sample_rate {
+                0 => None,
+                x => Some(x),
+            },
+            channels: Some(channels),
+            bits_per_sample,
+            bitrate,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+// the limits an ID3v2.4 tag can declare in its extended header,
+// for players with little memory or a small display
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Id3v2Restrictions {
+    // at most this many frames, and bytes in the whole tag
+    pub max_frames: u32,
+    pub max_size: u32,
+    // text only in ISO-8859-1 or UTF-8
+    pub latin1_or_utf8: bool,
+    // in characters
+    pub max_text_length: Option<u32>,
+    // images only as PNG or JPEG
+    pub png_or_jpeg: bool,
+    // images no larger than this many pixels on either side,
+    // or exactly that large
+    pub max_image_size: Option<u32>,
+    pub exact_image_size: bool,
+}
+
+// the restrictions byte is %ppqrrstt
+impl From<u8> for Id3v2Restrictions {
+    fn from(x: u8) -> Id3v2Restrictions {
+        let (max_frames, max_size) = match x >> 6 {
+            0 => (128, 1024 * 1024),
+            1 => (64, 128 * 1024),
+            2 => (32, 40 * 1024),
+            _ => (32, 4 * 1024),
+        };
+        let max_text_length = match (x >> 3) & 0x03 {
+            0 => None,
+            1 => Some(1024),
+            2 => Some(128),
+            _ => Some(30),
+        };
+        let max_image_size = match x & 0x03 {
+            0 => None,
+            1 => Some(256),
+            _ => Some(64),
+        };
+
+        Id3v2Restrictions {
+            max_frames,
+            max_size,
+            latin1_or_utf8: x & 0x20 != 0,
+            max_text_length,
+            png_or_jpeg: x & 0x04 != 0,
+            max_image_size,
+            exact_image_size: x & 0x03 == 3,
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use crate::DateTime;
 use crate::Image;
 
+use std::collections::BTreeMap;
+
 #[derive(PartialEq, Debug, Default)]
 pub struct Tags {
     pub title: TagOption<String>,
@@ -28,6 +30,10 @@ pub struct Tags {
     pub is_compilation: TagOption<bool>,
 
     pub comment: TagOption<String>,
+    pub copyright: TagOption<String>,
+
+    // unsynchronized lyrics, lines separated by \n
+    pub lyrics: TagOption<String>,
 
     pub sort_title: TagOption<String>,
     pub sort_album: TagOption<String>,
@@ -38,6 +44,23 @@ pub struct Tags {
     pub front_cover: TagOption<Image>,
 
     pub rating: TagOption<u8>,
+
+    // iTunes media kind (stik): 1 music, 2 audiobook, 6 music video,
+    // 9 movie, 10 TV show, 11 booklet, 14 ringtone, 21 podcast
+    pub media_kind: TagOption<u8>,
+
+    pub tv_show: TagOption<String>,
+    pub tv_season: TagOption<i64>,
+    pub tv_episode: TagOption<i64>,
+
+    pub description: TagOption<String>,
+    pub long_description: TagOption<String>,
+
+    pub is_hd_video: TagOption<bool>,
+
+    // fields without a dedicated tag, keyed by their full name
+    // (e.g. "com.apple.iTunes:iTunNORM" for iTunes freeform atoms)
+    pub custom: TagOption<BTreeMap<String, String>>,
 }
 
 impl Tags {
@@ -61,6 +84,8 @@ impl Tags {
             bpm: TagOption::Mixed,
             is_compilation: TagOption::Mixed,
             comment: TagOption::Mixed,
+            copyright: TagOption::Mixed,
+            lyrics: TagOption::Mixed,
             sort_title: TagOption::Mixed,
             sort_album: TagOption::Mixed,
             sort_artist: TagOption::Mixed,
@@ -68,6 +93,14 @@ impl Tags {
             sort_composer: TagOption::Mixed,
             front_cover: TagOption::Mixed,
             rating: TagOption::Mixed,
+            media_kind: TagOption::Mixed,
+            tv_show: TagOption::Mixed,
+            tv_season: TagOption::Mixed,
+            tv_episode: TagOption::Mixed,
+            description: TagOption::Mixed,
+            long_description: TagOption::Mixed,
+            is_hd_video: TagOption::Mixed,
+            custom: TagOption::Mixed,
         }
     }
 }
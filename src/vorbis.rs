@@ -85,6 +85,7 @@ pub fn from_tags(tags: &Tags, include_image: bool) -> Vec<u8> {
     }
 
     write_comment!(vec, tags.comment, "COMMENT", cc);
+    write_comment!(vec, tags.copyright, "COPYRIGHT", cc);
     write_comment!(vec, tags.sort_title, "TITLESORT", cc);
     write_comment!(vec, tags.sort_album, "ALBUMSORT", cc);
     write_comment!(vec, tags.sort_artist, "ARTISTSORT", cc);
@@ -174,6 +175,7 @@ pub fn get_tags<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
             }
 
             "COMMENT" => tags.comment = TagOption::Some(value),
+            "COPYRIGHT" => tags.copyright = TagOption::Some(value),
             "TITLESORT" => tags.sort_title = TagOption::Some(value),
             "ALBUMSORT" => tags.sort_album = TagOption::Some(value),
             "ARTISTSORT" => tags.sort_artist = TagOption::Some(value),
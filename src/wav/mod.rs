@@ -0,0 +1,256 @@
+use std::io::prelude::*;
+use std::io::Cursor;
+use std::io::SeekFrom;
+
+use crate::DateTime;
+use crate::Error;
+use crate::TagOption;
+use crate::Tags;
+
+use crate::id3v2;
+use crate::iff;
+use crate::iff::read_chunk;
+use crate::iff::Chunk;
+use crate::iff::Form;
+use crate::iff::Piece;
+use crate::iff::RIFF;
+use crate::tools::decode_int_le_u64;
+use crate::tools::encode_int_le_u64;
+use crate::tools::encoding::decode_iso_8859_1;
+use crate::tools::tag_error;
+use crate::tools::tags::delta;
+use crate::tools::tags::fill;
+use crate::tools::tags::number;
+
+// walk the chunks of a RIFF/WAVE or RF64/WAVE file
+fn read_riff<T: Read + Seek>(input: &mut T) -> Result<Form, Error> {
+    let mut form = RIFF.read_form(input)?;
+
+    let rf64 = match &form.id {
+        b"RIFF" => false,
+        b"RF64" => true,
+        _ => return Err(tag_error("Not a RIFF file")),
+    };
+    if &form.kind != b"WAVE" {
+        return Err(tag_error("Not a WAVE file"));
+    }
+
+    // RF64 keeps the real sizes in the ds64 chunk
+    let length = form.length;
+    let mut data_size = None;
+    RIFF.read_chunks(input, &mut form, |input, chunk, end| {
+        if !rf64 {
+            return Ok(());
+        }
+        // RIFF size, data size, sample count and the length of a table
+        if &chunk.id == b"ds64" {
+            if chunk.size < 28 {
+                return Err(tag_error("RF64 ds64 chunk is too short"));
+            }
+            let mut sizes: [u8; 16] = [0; 16];
+            input.read_exact(&mut sizes)?;
+            *end = std::cmp::min(decode_int_le_u64(&sizes[0..8]).saturating_add(8), length);
+            data_size = Some(decode_int_le_u64(&sizes[8..16]));
+        }
+        if &chunk.id == b"data" && chunk.size == 0xFFFF_FFFF {
+            chunk.size = data_size.unwrap_or(end.saturating_sub(chunk.body()));
+        }
+        Ok(())
+    })?;
+
+    Ok(form)
+}
+
+fn is_info<T: Read + Seek>(input: &mut T, chunk: &Chunk) -> Result<bool, Error> {
+    if &chunk.id != b"LIST" || chunk.size < 4 {
+        return Ok(false);
+    }
+    let mut arr: [u8; 4] = [0; 4];
+    input.seek(SeekFrom::Start(chunk.body()))?;
+    input.read_exact(&mut arr)?;
+    Ok(&arr == b"INFO")
+}
+
+// the sub-chunks of a LIST/INFO chunk, without the "INFO" in front
+fn collect_info(vec: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+    RIFF.collect(vec, 4)
+}
+
+// INFO text is zero terminated, and often not in any particular encoding
+fn decode_info(value: &[u8]) -> String {
+    let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+    match std::str::from_utf8(&value[..end]) {
+        Ok(x) => x.to_string(),
+        Err(_) => decode_iso_8859_1(&value[..end]),
+    }
+}
+
+pub fn get<T: Read + Seek>(input: &mut T) -> Result<Tags, Error> {
+    let riff = read_riff(input)?;
+
+    let mut tags = match riff.chunks.iter().find(|c| c.is_id3()) {
+        Some(chunk) => match id3v2::get(&mut Cursor::new(read_chunk(input, chunk)?)) {
+            Ok(t) => t,
+            Err(Error::TagError(_)) => Tags::none(),
+            Err(e) => return Err(e),
+        },
+        None => Tags::none(),
+    };
+
+    for chunk in riff.chunks.iter() {
+        if !is_info(input, chunk)? {
+            continue;
+        }
+        for (id, value) in collect_info(&read_chunk(input, chunk)?) {
+            let text = decode_info(&value);
+            match &id {
+                b"INAM" => fill(&mut tags.title, TagOption::Some(text)),
+                b"IART" => fill(&mut tags.artist, TagOption::Some(text)),
+                b"IPRD" => fill(&mut tags.album, TagOption::Some(text)),
+                b"ICMT" => fill(&mut tags.comment, TagOption::Some(text)),
+                b"IGNR" => fill(&mut tags.genre, TagOption::Some(text)),
+                b"ICOP" => fill(&mut tags.copyright, TagOption::Some(text)),
+                b"ICRD" => fill(&mut tags.date, DateTime::from_iso_8601(&text).into()),
+                // sometimes written as "n/m"
+                b"ITRK" | b"IPRT" => fill(&mut tags.track_number, number(&text)),
+                _ => (),
+            }
+        }
+    }
+
+    Ok(tags)
+}
+
+macro_rules! write_info {
+    ($items:ident, $text:expr, $id:expr) => {{
+        if let TagOption::Some(ref t) = $text {
+            if t.as_str() != "" {
+                let mut value = t.as_bytes().to_vec();
+                value.push(0);
+                $items.push(RIFF.new_chunk($id, &value));
+            }
+        }
+    }};
+}
+
+// a whole LIST/INFO chunk, or nothing if there is nothing to write
+// sub-chunks we don't know about are kept as they are
+fn render_info(tags: &Tags, old: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    let mut items = Vec::new();
+
+    write_info!(items, tags.title, b"INAM");
+    write_info!(items, tags.artist, b"IART");
+    write_info!(items, tags.album, b"IPRD");
+    write_info!(items, tags.comment, b"ICMT");
+    write_info!(items, tags.genre, b"IGNR");
+    write_info!(items, tags.copyright, b"ICOP");
+    if let TagOption::Some(ref x) = tags.date {
+        write_info!(items, TagOption::Some(x.to_iso_8601()), b"ICRD");
+    }
+    if let TagOption::Some(x) = tags.track_number {
+        write_info!(items, TagOption::Some(x.to_string()), b"ITRK");
+    }
+
+    for (id, value) in old.iter() {
+        match id {
+            b"INAM" | b"IART" | b"IPRD" | b"ICMT" | b"IGNR" | b"ICOP" | b"ICRD" | b"ITRK"
+            | b"IPRT" => (),
+            _ => items.push(RIFF.new_chunk(id, value)),
+        }
+    }
+
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let mut vec = b"INFO".to_vec();
+    for item in items.iter() {
+        vec.extend_from_slice(item);
+    }
+    RIFF.new_chunk(b"LIST", &vec)
+}
+
+pub fn set<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    new: &Tags,
+) -> Result<(), Error> {
+    let riff = read_riff(input)?;
+    let tags = delta(&get(input)?, new);
+
+    let mut old_info = Vec::new();
+    for chunk in riff.chunks.iter() {
+        if is_info(input, chunk)? {
+            old_info.append(&mut collect_info(&read_chunk(input, chunk)?));
+        }
+    }
+    let info = render_info(&tags, old_info);
+
+    // the id3 chunk carries everything INFO has no room for
+    let (id, old_id3) = match riff.chunks.iter().find(|c| c.is_id3()) {
+        Some(chunk) => (chunk.id, read_chunk(input, chunk)?),
+        None => (*b"id3 ", Vec::new()),
+    };
+    let mut vec = Vec::new();
+    id3v2::set(&mut Cursor::new(old_id3), &mut vec, &tags)?;
+    let id3 = RIFF.new_chunk(&id, &vec);
+
+    rewrite(input, output, &riff, Some(info), Some(id3))
+}
+
+// copy the file without the INFO list and/or the id3 chunk
+pub fn remove<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    info: bool,
+    id3: bool,
+) -> Result<(), Error> {
+    let riff = read_riff(input)?;
+    let info = if info { Some(Vec::new()) } else { None };
+    let id3 = if id3 { Some(Vec::new()) } else { None };
+    rewrite(input, output, &riff, info, id3)
+}
+
+// copy the file, replacing the INFO list and the id3 chunk where they are given;
+// missing ones are appended, empty ones are dropped
+fn rewrite<R: Read + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    riff: &Form,
+    mut info: Option<Vec<u8>>,
+    mut id3: Option<Vec<u8>>,
+) -> Result<(), Error> {
+    let mut pieces = iff::pieces(riff, |chunk| {
+        let replacement = if chunk.is_id3() {
+            id3.as_mut()
+        } else if is_info(input, chunk)? {
+            info.as_mut()
+        } else {
+            None
+        };
+        // duplicates of the chunk are dropped once it's written
+        Ok(replacement.map(std::mem::take))
+    })?;
+    for vec in [info, id3].iter_mut() {
+        if let Some(vec) = vec.take() {
+            pieces.push(Piece::Data(vec));
+        }
+    }
+    let size = iff::size(&pieces);
+
+    // RF64 keeps the RIFF size in ds64, which always comes first
+    if &riff.id == b"RF64" {
+        output.write_all(b"RF64\xFF\xFF\xFF\xFFWAVE")?;
+        if let Some(chunk) = riff.chunks.first().filter(|c| &c.id == b"ds64") {
+            let mut vec = read_chunk(input, chunk)?;
+            vec[0..8].copy_from_slice(&encode_int_le_u64(size));
+            pieces[0] = Piece::Data(RIFF.new_chunk(b"ds64", &vec));
+        }
+    } else {
+        output.write_all(&RIFF.form_header(riff, size)?)?;
+    }
+
+    iff::write_pieces(input, output, riff, pieces)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,151 @@
+use crate::DateTime;
+use crate::TagOption;
+use crate::Tags;
+use std::io::Cursor;
+
+use crate::iff::RIFF;
+
+use crate::tools::decode_int_le_u32;
+use crate::tools::decode_int_le_u64;
+use crate::tools::encode_int_le_u32;
+use crate::tools::encode_int_le_u64;
+
+// fmt chunk, odd-sized data chunk and an INFO list
+fn wav(rf64: bool) -> Vec<u8> {
+    let mut chunks = Vec::new();
+    if rf64 {
+        // riff size, data size, sample count and an empty table
+        let mut ds64 = vec![0; 28];
+        ds64[8] = 5;
+        chunks.append(&mut RIFF.new_chunk(b"ds64", &ds64));
+    }
+    chunks.append(&mut RIFF.new_chunk(
+        b"fmt ",
+        &[1, 0, 1, 0, 0x44, 0xAC, 0, 0, 0x44, 0xAC, 0, 0, 1, 0, 8, 0],
+    ));
+    let mut data = RIFF.new_chunk(b"data", &[1, 2, 3, 4, 5]);
+    if rf64 {
+        data[4..8].copy_from_slice(&[0xFF; 4]);
+    }
+    chunks.append(&mut data);
+    chunks.append(
+        &mut RIFF.new_chunk(
+            b"LIST",
+            &[
+                &b"INFO"[..],
+                &RIFF.new_chunk(b"INAM", b"Old Title\0"),
+                &RIFF.new_chunk(b"ISFT", b"Recorder\0"),
+            ]
+            .concat(),
+        ),
+    );
+
+    let mut vec = match rf64 {
+        true => b"RF64\xFF\xFF\xFF\xFF".to_vec(),
+        false => [&b"RIFF"[..], &encode_int_le_u32(chunks.len() as u32 + 4)].concat(),
+    };
+    vec.extend_from_slice(b"WAVE");
+    vec.append(&mut chunks);
+    if rf64 {
+        let size = vec.len() as u64 - 8;
+        vec[20..28].copy_from_slice(&encode_int_le_u64(size));
+    }
+    vec
+}
+
+// the contents of the data chunk
+fn data(vec: &[u8]) -> Vec<u8> {
+    let mut cursor = Cursor::new(vec);
+    let riff = super::read_riff(&mut cursor).unwrap();
+    let chunk = riff.chunks.iter().find(|c| &c.id == b"data").unwrap();
+    super::read_chunk(&mut cursor, chunk).unwrap()
+}
+
+#[test]
+fn wav_info_test() {
+    let tags = super::get(&mut Cursor::new(wav(false))).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("Old Title".to_string()),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+}
+
+#[test]
+fn wav_set_test() {
+    let new = Tags {
+        title: TagOption::Some("Title ÆØÅ".to_string()),
+        artist: TagOption::Some("Artist".to_string()),
+        date: DateTime::from_iso_8601("2019-03-04").into(),
+        track_number: TagOption::Some(3),
+        composer: TagOption::Some("Composer".to_string()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(wav(false)), &mut output, &new).unwrap();
+
+    assert_eq!(decode_int_le_u32(&output[4..8]) as usize, output.len() - 8);
+    assert_eq!(data(&output), vec![1, 2, 3, 4, 5]);
+
+    // the composer only fits into the id3 chunk
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("Title ÆØÅ".to_string()));
+    assert_eq!(tags.composer, TagOption::Some("Composer".to_string()));
+    assert_eq!(tags.track_number, TagOption::Some(3));
+
+    // INFO alone still has the basics, and the unknown ISFT
+    let mut info_only = Vec::new();
+    super::remove(&mut Cursor::new(&output), &mut info_only, false, true).unwrap();
+    assert_eq!(
+        decode_int_le_u32(&info_only[4..8]) as usize,
+        info_only.len() - 8
+    );
+    let tags = super::get(&mut Cursor::new(&info_only)).unwrap();
+    let ideal = Tags {
+        title: TagOption::Some("Title ÆØÅ".to_string()),
+        artist: TagOption::Some("Artist".to_string()),
+        date: DateTime::from_iso_8601("2019-03-04").into(),
+        track_number: TagOption::Some(3),
+        ..Tags::none()
+    };
+    assert_eq!(tags, ideal);
+    assert!(info_only.windows(4).any(|w| w == b"ISFT"));
+
+    let mut bare = Vec::new();
+    super::remove(&mut Cursor::new(&output), &mut bare, true, true).unwrap();
+    assert_eq!(super::get(&mut Cursor::new(&bare)).unwrap(), Tags::none());
+    assert_eq!(data(&bare), vec![1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn wav_rf64_test() {
+    let new = Tags {
+        album: TagOption::Some("Album".to_string()),
+        ..Tags::mixed()
+    };
+    let mut output = Vec::new();
+    super::set(&mut Cursor::new(wav(true)), &mut output, &new).unwrap();
+
+    assert_eq!(&output[0..8], b"RF64\xFF\xFF\xFF\xFF");
+    assert_eq!(
+        decode_int_le_u64(&output[20..28]) as usize,
+        output.len() - 8
+    );
+    assert_eq!(data(&output), vec![1, 2, 3, 4, 5]);
+
+    let tags = super::get(&mut Cursor::new(&output)).unwrap();
+    assert_eq!(tags.title, TagOption::Some("Old Title".to_string()));
+    assert_eq!(tags.album, TagOption::Some("Album".to_string()));
+}
+
+#[test]
+fn wav_rf64_short_ds64_test() {
+    // a ds64 chunk without the sample count and table length
+    let mut file = b"RF64\xFF\xFF\xFF\xFFWAVE".to_vec();
+    file.append(&mut RIFF.new_chunk(b"ds64", &[0; 16]));
+    file.append(&mut RIFF.new_chunk(b"data", &[1, 2, 3, 4, 5]));
+
+    assert!(super::get(&mut Cursor::new(&file)).is_err());
+    let mut output = Vec::new();
+    assert!(super::set(&mut Cursor::new(&file), &mut output, &Tags::mixed()).is_err());
+}
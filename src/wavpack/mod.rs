@@ -0,0 +1,93 @@
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::Error;
+use crate::Properties;
+
+use crate::ape;
+use crate::tools::decode_int_le_u32;
+use crate::tools::tag_error;
+
+const SAMPLE_RATES: [u32; 15] = [
+    6000, 8000, 9600, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000,
+    192000,
+];
+
+// block header flags
+const BYTES_PER_SAMPLE: u32 = 0x3;
+const MONO: u32 = 0x4;
+const INITIAL_BLOCK: u32 = 0x800;
+const FINAL_BLOCK: u32 = 0x1000;
+
+// multichannel streams are a run of blocks, which we won't follow forever
+const MAX_BLOCKS: usize = 64;
+
+struct Block {
+    // size of the whole block, header included
+    size: u64,
+    total_samples: Option<u64>,
+    flags: u32,
+}
+
+fn read_block<T: Read + Seek>(input: &mut T, pos: u64) -> Result<Block, Error> {
+    let mut arr: [u8; 32] = [0; 32];
+    input.seek(SeekFrom::Start(pos))?;
+    input.read_exact(&mut arr)?;
+
+    if &arr[0..4] != b"wvpk" {
+        return Err(tag_error("WavPack block header not found"));
+    }
+
+    // the total is 40 bits wide, all ones when unknown
+    let total_samples = match decode_int_le_u32(&arr[12..16]) {
+        0xFFFF_FFFF => None,
+        x => Some(x as u64 | (arr[11] as u64) << 32),
+    };
+
+    Ok(Block {
+        size: decode_int_le_u32(&arr[4..8]) as u64 + 8,
+        total_samples,
+        flags: decode_int_le_u32(&arr[24..28]),
+    })
+}
+
+// audio properties from the first block header(s)
+pub fn get_properties<T: Read + Seek>(input: &mut T) -> Result<Properties, Error> {
+    let first = read_block(input, 0)?;
+    let flags = first.flags;
+    let total_samples = first.total_samples;
+
+    // one block per mono or stereo pair, until the final block of the first frame
+    let mut channels = 0;
+    let mut pos = 0;
+    let mut block = first;
+    for _ in 0..MAX_BLOCKS {
+        channels += if block.flags & MONO != 0 { 1 } else { 2 };
+        if block.flags & FINAL_BLOCK != 0 {
+            break;
+        }
+        pos += block.size;
+        block = read_block(input, pos)?;
+        if block.flags & INITIAL_BLOCK != 0 {
+            break;
+        }
+    }
+
+    let bits = ((flags & BYTES_PER_SAMPLE) + 1) as u8 * 8;
+    // index 15 means a custom rate in the metadata, which we don't read
+    let sample_rate = match SAMPLE_RATES.get(((flags >> 23) & 0xF) as usize) {
+        Some(&x) => x,
+        None => 0,
+    };
+
+    Ok(Properties::from_samples(
+        total_samples,
+        sample_rate,
+        channels,
+        Some(bits),
+        ape::audio_end(input)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,43 @@
+use crate::Properties;
+use std::io::Cursor;
+use std::time::Duration;
+
+use crate::tools::encode_int_le_u32;
+
+fn block(size: u32, samples: u32, flags: u32) -> Vec<u8> {
+    let mut vec = b"wvpk".to_vec();
+    vec.extend_from_slice(&encode_int_le_u32(size - 8));
+    vec.extend_from_slice(&[0x10, 0x04, 0, 0]);
+    vec.extend_from_slice(&encode_int_le_u32(samples));
+    vec.extend_from_slice(&[0; 8]);
+    vec.extend_from_slice(&encode_int_le_u32(flags));
+    vec.extend_from_slice(&[0; 4]);
+    vec.resize(size as usize, 0);
+    vec
+}
+
+#[test]
+fn wavpack_properties_test() {
+    // 16 bit stereo at 44100 Hz, one second in 11025 bytes
+    let file = block(11025, 44100, 0x1 | 0x800 | 0x1000 | 9 << 23);
+    let properties = super::get_properties(&mut Cursor::new(file)).unwrap();
+    let ideal = Properties {
+        duration: Some(Duration::from_secs(1)),
+        sample_rate: Some(44100),
+        channels: Some(2),
+        bits_per_sample: Some(16),
+        bitrate: Some(88),
+    };
+    assert_eq!(properties, ideal);
+
+    // 5.1 as three stereo blocks, 24 bit at 48000 Hz
+    let mut file = block(64, 96000, 0x2 | 0x800 | 10 << 23);
+    file.append(&mut block(64, 96000, 0x2 | 10 << 23));
+    file.append(&mut block(64, 96000, 0x2 | 0x1000 | 10 << 23));
+    let properties = super::get_properties(&mut Cursor::new(file)).unwrap();
+    assert_eq!(properties.channels, Some(6));
+    assert_eq!(properties.bits_per_sample, Some(24));
+    assert_eq!(properties.duration, Some(Duration::from_secs(2)));
+
+    assert!(super::get_properties(&mut Cursor::new(vec![0; 64])).is_err());
+}